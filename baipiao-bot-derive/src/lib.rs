@@ -0,0 +1,341 @@
+//! Derive macros for `baipiao-bot-rust`, re-exported from its `commands`
+//! module.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, Fields, GenericArgument, Lit,
+    LitStr, Meta, PathArguments, Type,
+};
+
+const NUMBER_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize",
+];
+
+/// Turns an enum into a set of bot commands.
+///
+/// Each variant is a command named after the variant in kebab case (or
+/// `#[command(rename = "..")]`), documented by its doc comment. Named fields
+/// are its arguments: `String` is a word, `#[command(user)] String` a login,
+/// integers are numbers, `Vec<String>` takes the rest of the line and
+/// `Option<_>` makes an argument optional. `#[command(requires = "..")]`
+/// restricts a command to a role such as `collaborator` or `team:org/team`.
+#[proc_macro_derive(BotCommand, attributes(command))]
+pub fn derive_bot_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+enum Kind {
+    Word,
+    User,
+    Number,
+    List,
+}
+
+struct Arg {
+    ident: syn::Ident,
+    kind: Kind,
+    optional: bool,
+}
+
+struct Variant {
+    ident: syn::Ident,
+    name: String,
+    requires: Option<String>,
+    description: String,
+    args: Vec<Arg>,
+    named: bool,
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let data = match input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "BotCommand can only be derived for enums",
+            ))
+        }
+    };
+    let variants = data
+        .variants
+        .iter()
+        .map(parse_variant)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let krate = quote!(::baipiao_bot_rust::commands);
+    let specs = variants.iter().map(|variant| {
+        let name = &variant.name;
+        let description = &variant.description;
+        let requires = variant.requires.as_ref().map(|role| {
+            quote! {
+                .requires(::std::str::FromStr::from_str(#role).unwrap())
+            }
+        });
+        let args = variant.args.iter().map(|arg| {
+            let arg_name = arg.ident.to_string();
+            let kind = match arg.kind {
+                Kind::Word => quote!(#krate::ArgKind::Word),
+                Kind::User => quote!(#krate::ArgKind::User),
+                Kind::Number => quote!(#krate::ArgKind::Number),
+                Kind::List => quote!(#krate::ArgKind::List),
+            };
+            if arg.optional {
+                quote!(.optional_arg(#arg_name, #kind))
+            } else {
+                quote!(.arg(#arg_name, #kind))
+            }
+        });
+        quote! {
+            #krate::CommandSpec::new(#name).description(#description) #requires #(#args)*
+        }
+    });
+    let arms = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let name = &variant.name;
+        if !variant.named {
+            return quote!(#name => ::std::result::Result::Ok(Self::#ident),);
+        }
+        let fields = variant.args.iter().map(|arg| {
+            let field = &arg.ident;
+            let arg_name = field.to_string();
+            let missing = quote! {
+                #krate::CommandError::new(
+                    command,
+                    #krate::CommandErrorKind::MissingArgument(#arg_name.to_string()),
+                )
+            };
+            let convert = quote! {
+                ::std::convert::TryFrom::try_from(number).map_err(|_| {
+                    #krate::CommandError::new(
+                        command,
+                        #krate::CommandErrorKind::InvalidNumber {
+                            arg: #arg_name.to_string(),
+                            value: number.to_string(),
+                        },
+                    )
+                })?
+            };
+            let value = match (&arg.kind, arg.optional) {
+                (Kind::List, false) => quote!(command.list(#arg_name).to_vec()),
+                (Kind::List, true) => quote! {{
+                    let list = command.list(#arg_name);
+                    if list.is_empty() {
+                        ::std::option::Option::None
+                    } else {
+                        ::std::option::Option::Some(list.to_vec())
+                    }
+                }},
+                (Kind::Number, false) => quote! {{
+                    let number = command.number(#arg_name).ok_or_else(|| #missing)?;
+                    #convert
+                }},
+                (Kind::Number, true) => quote! {
+                    match command.number(#arg_name) {
+                        ::std::option::Option::Some(number) => {
+                            ::std::option::Option::Some(#convert)
+                        }
+                        ::std::option::Option::None => ::std::option::Option::None,
+                    }
+                },
+                (_, false) => quote! {
+                    command.word(#arg_name).ok_or_else(|| #missing)?.to_string()
+                },
+                (_, true) => quote!(command.word(#arg_name).map(str::to_string)),
+            };
+            quote!(#field: #value,)
+        });
+        quote!(#name => ::std::result::Result::Ok(Self::#ident { #(#fields)* }),)
+    });
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::BotCommand for #ident #ty_generics #where_clause {
+            fn specs() -> ::std::vec::Vec<#krate::CommandSpec> {
+                ::std::vec![#(#specs),*]
+            }
+
+            fn from_command(
+                command: &#krate::Command,
+            ) -> ::std::result::Result<Self, #krate::CommandError> {
+                match command.name.as_str() {
+                    #(#arms)*
+                    _ => ::std::result::Result::Err(#krate::CommandError::new(
+                        command,
+                        #krate::CommandErrorKind::UnknownCommand,
+                    )),
+                }
+            }
+        }
+    })
+}
+
+fn parse_variant(variant: &syn::Variant) -> Result<Variant, Error> {
+    let mut name = kebab_case(&variant.ident.to_string());
+    let mut requires = None;
+    for attr in command_attrs(&variant.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                name = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else if meta.path.is_ident("requires") {
+                let role = meta.value()?.parse::<LitStr>()?;
+                if !is_role(&role.value()) {
+                    return Err(Error::new_spanned(
+                        role,
+                        "expected anyone, owner, maintainer, collaborator, pr-author or team:org/team",
+                    ));
+                }
+                requires = Some(role.value());
+                Ok(())
+            } else {
+                Err(meta.error("expected `rename = \"..\"` or `requires = \"..\"`"))
+            }
+        })?;
+    }
+    let (args, named) = match &variant.fields {
+        Fields::Unit => (Vec::new(), false),
+        Fields::Named(fields) => (
+            fields
+                .named
+                .iter()
+                .map(parse_field)
+                .collect::<Result<Vec<_>, _>>()?,
+            true,
+        ),
+        Fields::Unnamed(fields) => {
+            return Err(Error::new_spanned(
+                fields,
+                "command arguments must be named fields",
+            ))
+        }
+    };
+    Ok(Variant {
+        ident: variant.ident.clone(),
+        name,
+        requires,
+        description: doc_comment(&variant.attrs),
+        args,
+        named,
+    })
+}
+
+fn parse_field(field: &syn::Field) -> Result<Arg, Error> {
+    let mut user = false;
+    for attr in command_attrs(&field.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("user") {
+                user = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `user`"))
+            }
+        })?;
+    }
+    let (ty, optional) = match generic_argument(&field.ty, "Option") {
+        Some(inner) => (inner, true),
+        None => (&field.ty, false),
+    };
+    let kind = if generic_argument(ty, "Vec").is_some_and(|it| type_name(it) == "String") {
+        Kind::List
+    } else if NUMBER_TYPES.contains(&type_name(ty).as_str()) {
+        Kind::Number
+    } else if type_name(ty) == "String" {
+        if user {
+            Kind::User
+        } else {
+            Kind::Word
+        }
+    } else {
+        return Err(Error::new_spanned(
+            &field.ty,
+            "unsupported argument type, expected String, an integer, Vec<String> or Option<_>",
+        ));
+    };
+    Ok(Arg {
+        ident: field.ident.clone().unwrap(),
+        kind,
+        optional,
+    })
+}
+
+/// Mirrors `Role::from_str`, so that typos fail at compile time.
+fn is_role(role: &str) -> bool {
+    match role {
+        "anyone" | "owner" | "maintainer" | "collaborator" | "pr-author" => true,
+        _ => role
+            .strip_prefix("team:")
+            .and_then(|it| it.split_once('/'))
+            .is_some_and(|(org, team)| !org.is_empty() && !team.is_empty()),
+    }
+}
+
+fn command_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("command"))
+}
+
+fn doc_comment(attrs: &[Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(doc) => Some(doc.value().trim().to_string()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    lines.join(" ").trim().to_string()
+}
+
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string())
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+fn generic_argument<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let segment = match ty {
+        Type::Path(path) => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != wrapper {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn kebab_case(ident: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in ident.chars().enumerate() {
+        if c.is_uppercase() {
+            if i != 0 {
+                result.push('-');
+            }
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
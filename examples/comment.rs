@@ -1,88 +1,119 @@
-use async_trait::async_trait;
 use baipiao_bot_rust::{
-    Bot, CommentCreatedEvent, Dispatcher, IssueCreatedEvent, PullRequestCreatedEvent, Repository,
-    RunningInfo
+    action_log,
+    auth::Auth,
+    commands::{BotCommand, Command},
+    config::Config,
+    logging::{self, LogFormat},
+    subscriptions::WorkflowFile,
+    templates::{event_vars, Templates},
+    Context, Dispatcher, Event, GitHub,
 };
-use octocrab::{Octocrab, OctocrabBuilder};
+use serde::Deserialize;
+use serde_json::json;
 use std::env;
 
-struct CommentBot {
-    github_client: Octocrab,
+#[derive(BotCommand)]
+enum Cmd {
+    /// 叫一下白嫖机器人
+    Ping,
+    /// 显示这张表
+    Help,
 }
 
-impl CommentBot {
-    fn new(token: String) -> Self {
-        Self {
-            github_client: OctocrabBuilder::new()
-                .personal_token(token)
-                .build()
-                .unwrap(),
-        }
-    }
+#[derive(Deserialize)]
+struct Settings {
+    #[serde(default = "default_templates")]
+    templates: Templates,
+}
 
-    async fn comment(&self, repo: Repository, issue_id: usize, content: &str) {
-        self.github_client
-            .issues(repo.owner, repo.name)
-            .create_comment(issue_id as u64, content)
-            .await
-            .unwrap();
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            templates: default_templates(),
+        }
     }
 }
 
-#[async_trait]
-impl Bot for CommentBot {
-    async fn on_issue_created(&self, repo: Repository,
-                              _running_info: RunningInfo,
-                              event: IssueCreatedEvent) {
-        self.comment(
-            repo,
-            event.id,
-            "我 劝 开发者耗子尾汁，耗耗反思，不要再犯这样的错误，小错误啊",
-        )
-        .await
-    }
+impl Config for Settings {}
 
-    async fn on_issue_closed(&self, repo: Repository,
-                             _running_info: RunningInfo,
-                             issue_id: usize) {
-        self.comment(repo, issue_id, "我啪的一下就修好了，很快啊")
-            .await
+fn default_templates() -> Templates {
+    #[derive(Deserialize)]
+    struct File {
+        templates: Templates,
     }
 
-    async fn on_pull_request_created(&self, repo: Repository,
-                                     _running_info: RunningInfo,
-                                     event: PullRequestCreatedEvent) {
-        self.comment(
-            repo,
-            event.id,
-            &format!(
-                "按 传统发pr的点到为止 @{} 已经赢了，如果这个pr合进去，一个pr就把问题给解决了",
-                event.user
-            ),
-        )
+    let file: File = toml::from_str(include_str!("baipiao.toml")).unwrap();
+    file.templates
+}
+
+async fn comment(ctx: &Context, issue_id: usize, template: &str, vars: serde_json::Value) {
+    ctx.record("template", template);
+    let templates = &ctx.config::<Settings>().unwrap().templates;
+    let content = templates
+        .render(template, &ctx.sender.login, &vars)
+        .unwrap();
+    ctx.github
+        .create_comment(&ctx.repo, issue_id, &content)
         .await
-    }
+        .unwrap();
+}
 
-    async fn on_comment_created(&self, repo: Repository,
-                                _running_info: RunningInfo,
-                                event: CommentCreatedEvent) {
-        if event.body.contains("@baipiao-bot") {
-            self.comment(
-                repo,
-                event.target.id(),
-                &format!("@{} 发生甚么事了", event.user),
-            )
-            .await
+async fn on_command(ctx: Context, command: Command) {
+    let target = command.source.target_id();
+    let content = match Cmd::from_command(&command) {
+        Ok(Cmd::Ping) => {
+            return comment(&ctx, target, "ping", json!({ "user": command.user })).await
         }
-    }
+        Ok(Cmd::Help) => Cmd::help(),
+        Err(error) => error.to_string(),
+    };
+    ctx.github
+        .create_comment(&ctx.repo, target, &content)
+        .await
+        .unwrap();
 }
 
 #[tokio::main]
 async fn main() {
-    let token = env::var("BAIPIAO_BOT_TOKEN").unwrap();
-    let bot = CommentBot::new(token);
-    let dispatcher = Dispatcher::new(bot);
+    let dispatcher = Dispatcher::builder()
+        .on_issue_created(|ctx, event| async move {
+            let vars = event_vars(&ctx, &Event::IssueCreated(event.clone()));
+            comment(&ctx, event.id, "issue_created", vars).await
+        })
+        .on_issue_closed(|ctx, issue_id| async move {
+            let vars = event_vars(&ctx, &Event::IssueClosed(issue_id));
+            comment(&ctx, issue_id, "issue_closed", vars).await
+        })
+        .on_pull_request_created(|ctx, event| async move {
+            let vars = event_vars(&ctx, &Event::PullRequestCreated(event.clone()));
+            comment(&ctx, event.id, "pull_request_created", vars).await
+        })
+        .on_command(Cmd::specs(), on_command)
+        .build();
+    // `cargo run --example comment -- workflow` regenerates
+    // .github/workflows/baipiao.yml
+    if env::args().nth(1).as_deref() == Some("workflow") {
+        let workflow = WorkflowFile::new()
+            .name("make baipiao bot work")
+            .docker("baipiaobot/baipiao-bot-rust:latest");
+        print!(
+            "{}",
+            workflow.render(&dispatcher.subscriptions(), &dispatcher.permissions())
+        );
+        return;
+    }
+    logging::init(LogFormat::from_env());
+    let dry_run = action_log::dry_run_requested();
+    let dispatcher = dispatcher
+        .with_github(GitHub::new(Auth::from_env().unwrap()))
+        .with_login("baipiao-bot")
+        .with_dry_run(dry_run.is_some())
+        .with_config::<Settings>()
+        .with_permission_check(true);
     let content = env::var("JSON").unwrap();
     let input: serde_json::Value = serde_json::from_str(&content).unwrap();
-    dispatcher.dispatch_event(input).await;
+    let report = dispatcher.dispatch(input).await;
+    if let Some(format) = dry_run {
+        print!("{}", report.render(format));
+    }
 }
@@ -1,8 +1,7 @@
 use async_trait::async_trait;
 use baipiao_bot_rust::{
-    Bot, CommentCreatedEvent, CommentUpdatedEvent, Dispatcher, IssueCreatedEvent,
-    IssueUpdatedEvent, PullRequestCreatedEvent, PullRequestUpdatedEvent, Repository,
-    RunningInfo
+    Bot, CommentCreatedEvent, CommentUpdatedEvent, Context, Dispatcher, IssueCreatedEvent,
+    IssueUpdatedEvent, PullRequestCreatedEvent, PullRequestUpdatedEvent,
 };
 use std::io::Read;
 
@@ -10,58 +9,43 @@ struct EchoBot;
 
 #[async_trait]
 impl Bot for EchoBot {
-    async fn on_issue_created(&self, repo: Repository,
-                              _running_info: RunningInfo,
-                              event: IssueCreatedEvent) {
-        println!("on_issue_created: {:?}, {:?}", repo, event)
+    async fn on_issue_created(&self, ctx: &Context, event: IssueCreatedEvent) {
+        println!("on_issue_created: {:?}, {:?}", ctx.repo, event)
     }
 
-    async fn on_issue_updated(&self, repo: Repository,
-                              _running_info: RunningInfo,
-                              event: IssueUpdatedEvent) {
-        println!("on_issue_updated: {:?}, {:?}", repo, event)
+    async fn on_issue_updated(&self, ctx: &Context, event: IssueUpdatedEvent) {
+        println!("on_issue_updated: {:?}, {:?}", ctx.repo, event)
     }
 
-    async fn on_issue_closed(&self, repo: Repository,
-                             _running_info: RunningInfo,
-                             issue_id: usize) {
-        println!("on_issue_closed: {:?}, {:?}", repo, issue_id)
+    async fn on_issue_closed(&self, ctx: &Context, issue_id: usize) {
+        println!("on_issue_closed: {:?}, {:?}", ctx.repo, issue_id)
     }
 
-    async fn on_pull_request_created(&self, repo: Repository,
-                                     _running_info: RunningInfo,
-                                     event: PullRequestCreatedEvent) {
-        println!("on_pull_request_created: {:?}, {:?}", repo, event)
+    async fn on_pull_request_created(&self, ctx: &Context, event: PullRequestCreatedEvent) {
+        println!("on_pull_request_created: {:?}, {:?}", ctx.repo, event)
     }
 
-    async fn on_pull_request_updated(&self, repo: Repository,
-                                     _running_info: RunningInfo,
-                                     event: PullRequestUpdatedEvent) {
-        println!("on_pull_request_updated: {:?}, {:?}", repo, event)
+    async fn on_pull_request_updated(&self, ctx: &Context, event: PullRequestUpdatedEvent) {
+        println!("on_pull_request_updated: {:?}, {:?}", ctx.repo, event)
     }
 
-    async fn on_pull_request_closed(&self, repo: Repository,
-                                    _running_info: RunningInfo,
-                                    pull_request_id: usize) {
-        println!("on_pull_request_closed: {:?}, {:?}", repo, pull_request_id)
+    async fn on_pull_request_closed(&self, ctx: &Context, pull_request_id: usize) {
+        println!(
+            "on_pull_request_closed: {:?}, {:?}",
+            ctx.repo, pull_request_id
+        )
     }
 
-    async fn on_comment_created(&self, repo: Repository,
-                                _running_info: RunningInfo,
-                                event: CommentCreatedEvent) {
-        println!("on_comment_created: {:?}, {:?}", repo, event)
+    async fn on_comment_created(&self, ctx: &Context, event: CommentCreatedEvent) {
+        println!("on_comment_created: {:?}, {:?}", ctx.repo, event)
     }
 
-    async fn on_comment_updated(&self, repo: Repository,
-                                _running_info: RunningInfo,
-                                event: CommentUpdatedEvent) {
-        println!("on_comment_updated: {:?}, {:?}", repo, event)
+    async fn on_comment_updated(&self, ctx: &Context, event: CommentUpdatedEvent) {
+        println!("on_comment_updated: {:?}, {:?}", ctx.repo, event)
     }
 
-    async fn on_comment_deleted(&self, repo: Repository,
-                                _running_info: RunningInfo,
-                                comment_id: usize) {
-        println!("on_comment_deleted: {:?}, {:?}", repo, comment_id)
+    async fn on_comment_deleted(&self, ctx: &Context, comment_id: usize) {
+        println!("on_comment_deleted: {:?}, {:?}", ctx.repo, comment_id)
     }
 }
 
@@ -0,0 +1,407 @@
+use crate::client::{ClientError, CollaboratorPermission, Comment, File, Issue, ReviewEvent, User};
+use crate::layers::Outcome;
+use crate::scopes::Manifest;
+use crate::{GitHubApi, Repository};
+use async_trait::async_trait;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::{env, fmt};
+
+/// A change a bot made, or would have made in a dry run, through the API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    CreateComment {
+        issue_id: usize,
+        body: String,
+    },
+    UpdateComment {
+        comment_id: usize,
+        body: String,
+    },
+    DeleteComment {
+        comment_id: usize,
+    },
+    AddLabels {
+        issue_id: usize,
+        labels: Vec<String>,
+    },
+    RemoveLabel {
+        issue_id: usize,
+        label: String,
+    },
+    CloseIssue {
+        issue_id: usize,
+    },
+    CreateReview {
+        pull_request_id: usize,
+        review: String,
+        body: String,
+    },
+    PutFile {
+        branch: String,
+        path: String,
+    },
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::CreateComment { issue_id, .. } => write!(f, "commented on #{}", issue_id),
+            Action::UpdateComment { comment_id, .. } => write!(f, "edited comment {}", comment_id),
+            Action::DeleteComment { comment_id } => write!(f, "deleted comment {}", comment_id),
+            Action::AddLabels { issue_id, labels } => {
+                write!(f, "labelled #{} with `{}`", issue_id, labels.join("`, `"))
+            }
+            Action::RemoveLabel { issue_id, label } => {
+                write!(f, "removed label `{}` from #{}", label, issue_id)
+            }
+            Action::CloseIssue { issue_id } => write!(f, "closed #{}", issue_id),
+            Action::CreateReview {
+                pull_request_id,
+                review,
+                ..
+            } => write!(f, "reviewed #{} ({})", pull_request_id, review),
+            Action::PutFile { branch, path } => write!(f, "committed `{}` to `{}`", path, branch),
+        }
+    }
+}
+
+impl Action {
+    /// The text written, if any.
+    pub fn body(&self) -> Option<&str> {
+        match self {
+            Action::CreateComment { body, .. }
+            | Action::UpdateComment { body, .. }
+            | Action::CreateReview { body, .. } => Some(body),
+            _ => None,
+        }
+    }
+}
+
+/// How a `Report` is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanFormat {
+    Text,
+    Json,
+}
+
+/// Whether a dry run was asked for, with `--dry-run[=json]` on the command
+/// line or `BAIPIAO_DRY_RUN=1` (or `json`) in the environment.
+pub fn dry_run_requested() -> Option<PlanFormat> {
+    let flag = env::args().find_map(|arg| match arg.as_str() {
+        "--dry-run" => Some("1".to_string()),
+        _ => arg.strip_prefix("--dry-run=").map(str::to_string),
+    });
+    match flag.or_else(|| env::var("BAIPIAO_DRY_RUN").ok())?.as_str() {
+        "json" => Some(PlanFormat::Json),
+        "" | "0" | "false" | "no" => None,
+        _ => Some(PlanFormat::Text),
+    }
+}
+
+/// The changes made while handling an event, shared between clones.
+#[derive(Debug, Clone, Default)]
+pub struct ActionLog {
+    actions: Arc<Mutex<Vec<Action>>>,
+}
+
+impl ActionLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, action: Action) {
+        self.actions.lock().unwrap().push(action);
+    }
+
+    pub fn actions(&self) -> Vec<Action> {
+        self.actions.lock().unwrap().clone()
+    }
+
+    /// Wraps `api` so that successful writes through it are logged here.
+    pub fn record(&self, api: Arc<dyn GitHubApi>) -> Recorded {
+        Recorded {
+            api,
+            log: self.clone(),
+            dry_run: false,
+        }
+    }
+
+    /// Wraps `api` so that writes are only logged here, never sent; reads
+    /// still go to `api`, which may be a mock.
+    pub fn dry_run(&self, api: Arc<dyn GitHubApi>) -> Recorded {
+        Recorded {
+            api,
+            log: self.clone(),
+            dry_run: true,
+        }
+    }
+
+    /// A readable list of the actions, with the text they would write.
+    pub fn plan(&self) -> String {
+        plan(&self.actions())
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self.actions()).unwrap()
+    }
+}
+
+fn plan(actions: &[Action]) -> String {
+    if actions.is_empty() {
+        return "Nothing to do.\n".to_string();
+    }
+    let mut plan = String::new();
+    for (i, action) in actions.iter().enumerate() {
+        plan.push_str(&format!("{}. {}\n", i + 1, action));
+        for line in action.body().unwrap_or_default().lines() {
+            plan.push_str(&format!("   > {}\n", line));
+        }
+    }
+    plan
+}
+
+/// What became of an event, returned by `Dispatcher::dispatch`, with the
+/// changes made, or in a dry run those that would have been.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// `event_name.action`, e.g. `issues.opened`.
+    pub event: String,
+    /// `owner/name`, followed by `#number` for issues and pull requests.
+    pub target: String,
+    pub outcome: Outcome,
+    pub actions: Vec<Action>,
+    pub dry_run: bool,
+}
+
+impl Report {
+    pub fn render(&self, format: PlanFormat) -> String {
+        match format {
+            PlanFormat::Text => format!(
+                "{} of `{}` on {} ({}), {}:\n{}",
+                if self.dry_run { "Dry run" } else { "Run" },
+                self.event,
+                self.target,
+                self.outcome,
+                if self.dry_run { "would have" } else { "did" },
+                plan(&self.actions)
+            ),
+            PlanFormat::Json => format!(
+                "{}\n",
+                serde_json::json!({
+                    "event": self.event,
+                    "target": self.target,
+                    "outcome": self.outcome.to_string(),
+                    "dry_run": self.dry_run,
+                    "actions": self.actions,
+                })
+            ),
+        }
+    }
+}
+
+/// A `GitHubApi` logging its writes, made by `ActionLog::record` or
+/// `ActionLog::dry_run`.
+#[derive(Debug)]
+pub struct Recorded {
+    api: Arc<dyn GitHubApi>,
+    log: ActionLog,
+    dry_run: bool,
+}
+
+impl Recorded {
+    /// What a dry run pretends to have written.
+    fn fake_comment(id: usize, body: &str) -> Comment {
+        Comment {
+            id,
+            body: body.to_string(),
+            user: User {
+                login: "dry-run".to_string(),
+            },
+            updated_at: None,
+        }
+    }
+}
+
+#[async_trait]
+impl GitHubApi for Recorded {
+    async fn create_comment(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+        body: &str,
+    ) -> Result<Comment, ClientError> {
+        let comment = if self.dry_run {
+            Self::fake_comment(0, body)
+        } else {
+            self.api.create_comment(repo, issue_id, body).await?
+        };
+        self.log.push(Action::CreateComment {
+            issue_id,
+            body: body.to_string(),
+        });
+        Ok(comment)
+    }
+
+    async fn update_comment(
+        &self,
+        repo: &Repository,
+        comment_id: usize,
+        body: &str,
+    ) -> Result<Comment, ClientError> {
+        let comment = if self.dry_run {
+            Self::fake_comment(comment_id, body)
+        } else {
+            self.api.update_comment(repo, comment_id, body).await?
+        };
+        self.log.push(Action::UpdateComment {
+            comment_id,
+            body: body.to_string(),
+        });
+        Ok(comment)
+    }
+
+    async fn delete_comment(
+        &self,
+        repo: &Repository,
+        comment_id: usize,
+    ) -> Result<(), ClientError> {
+        if !self.dry_run {
+            self.api.delete_comment(repo, comment_id).await?;
+        }
+        self.log.push(Action::DeleteComment { comment_id });
+        Ok(())
+    }
+
+    async fn add_labels(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+        labels: &[String],
+    ) -> Result<(), ClientError> {
+        if !self.dry_run {
+            self.api.add_labels(repo, issue_id, labels).await?;
+        }
+        self.log.push(Action::AddLabels {
+            issue_id,
+            labels: labels.to_vec(),
+        });
+        Ok(())
+    }
+
+    async fn remove_label(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+        label: &str,
+    ) -> Result<(), ClientError> {
+        if !self.dry_run {
+            self.api.remove_label(repo, issue_id, label).await?;
+        }
+        self.log.push(Action::RemoveLabel {
+            issue_id,
+            label: label.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn close_issue(&self, repo: &Repository, issue_id: usize) -> Result<(), ClientError> {
+        if !self.dry_run {
+            self.api.close_issue(repo, issue_id).await?;
+        }
+        self.log.push(Action::CloseIssue { issue_id });
+        Ok(())
+    }
+
+    async fn create_review(
+        &self,
+        repo: &Repository,
+        pull_request_id: usize,
+        review: ReviewEvent,
+        body: &str,
+    ) -> Result<(), ClientError> {
+        if !self.dry_run {
+            self.api
+                .create_review(repo, pull_request_id, review, body)
+                .await?;
+        }
+        self.log.push(Action::CreateReview {
+            pull_request_id,
+            review: review.as_str().to_string(),
+            body: body.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn comments(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+    ) -> Result<Vec<Comment>, ClientError> {
+        self.api.comments(repo, issue_id).await
+    }
+
+    async fn issue(&self, repo: &Repository, issue_id: usize) -> Result<Issue, ClientError> {
+        self.api.issue(repo, issue_id).await
+    }
+
+    async fn pull_request_files(
+        &self,
+        repo: &Repository,
+        pull_request_id: usize,
+    ) -> Result<Vec<String>, ClientError> {
+        self.api.pull_request_files(repo, pull_request_id).await
+    }
+
+    async fn file(
+        &self,
+        repo: &Repository,
+        path: &str,
+        git_ref: Option<&str>,
+    ) -> Result<Option<File>, ClientError> {
+        self.api.file(repo, path, git_ref).await
+    }
+
+    async fn put_file(
+        &self,
+        repo: &Repository,
+        branch: &str,
+        path: &str,
+        content: &str,
+        sha: Option<&str>,
+        message: &str,
+    ) -> Result<String, ClientError> {
+        let sha = if self.dry_run {
+            "dry-run".to_string()
+        } else {
+            self.api
+                .put_file(repo, branch, path, content, sha, message)
+                .await?
+        };
+        self.log.push(Action::PutFile {
+            branch: branch.to_string(),
+            path: path.to_string(),
+        });
+        Ok(sha)
+    }
+
+    async fn collaborator_permission(
+        &self,
+        repo: &Repository,
+        user: &str,
+    ) -> Result<CollaboratorPermission, ClientError> {
+        self.api.collaborator_permission(repo, user).await
+    }
+
+    async fn is_team_member(&self, org: &str, team: &str, user: &str) -> Result<bool, ClientError> {
+        self.api.is_team_member(org, team, user).await
+    }
+
+    async fn granted_permissions(
+        &self,
+        repo: &Repository,
+    ) -> Result<Option<Manifest>, ClientError> {
+        self.api.granted_permissions(repo).await
+    }
+}
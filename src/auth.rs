@@ -0,0 +1,218 @@
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::{env, fmt, sync::Arc};
+use tokio::sync::Mutex;
+
+pub const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// Installation tokens are refreshed this long before GitHub expires them.
+const REFRESH_MARGIN_SECONDS: i64 = 5 * 60;
+
+#[derive(Debug)]
+pub enum AuthError {
+    MissingEnv(&'static str),
+    InvalidAppId(String),
+    InvalidKey(jsonwebtoken::errors::Error),
+    MissingInstallation,
+    Http(reqwest::Error),
+    Status { status: u16, message: String },
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingEnv(name) => write!(f, "environment variable {} is not set", name),
+            AuthError::InvalidAppId(id) => write!(f, "invalid GitHub App id: {}", id),
+            AuthError::InvalidKey(err) => write!(f, "cannot sign app JWT: {}", err),
+            AuthError::MissingInstallation => {
+                write!(f, "the event payload carries no installation id")
+            }
+            AuthError::Http(err) => write!(f, "cannot request installation token: {}", err),
+            AuthError::Status { status, message } => write!(
+                f,
+                "installation token request failed with {}: {}",
+                status, message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<reqwest::Error> for AuthError {
+    fn from(err: reqwest::Error) -> Self {
+        AuthError::Http(err)
+    }
+}
+
+#[derive(Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstallationToken {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    /// What the token may do, e.g. `"pull_requests": "write"`.
+    #[serde(default)]
+    pub permissions: HashMap<String, String>,
+}
+
+impl InstallationToken {
+    fn is_fresh(&self) -> bool {
+        self.expires_at - Utc::now() > Duration::seconds(REFRESH_MARGIN_SECONDS)
+    }
+}
+
+/// Authenticates as a GitHub App and hands out installation tokens.
+///
+/// Tokens are cached per installation and exchanged again shortly before
+/// they expire.
+pub struct AppAuth {
+    app_id: u64,
+    key: EncodingKey,
+    base_url: String,
+    http: reqwest::Client,
+    tokens: Mutex<HashMap<u64, InstallationToken>>,
+}
+
+impl AppAuth {
+    pub fn new(app_id: u64, private_key_pem: &[u8]) -> Result<Self, AuthError> {
+        Ok(AppAuth {
+            app_id,
+            key: EncodingKey::from_rsa_pem(private_key_pem).map_err(AuthError::InvalidKey)?,
+            base_url: DEFAULT_API_URL.to_string(),
+            http: reqwest::Client::new(),
+            tokens: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Points the token exchange at another API root, e.g. GitHub Enterprise
+    /// or a local stub.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn app_id(&self) -> u64 {
+        self.app_id
+    }
+
+    /// Signs a short-lived JWT identifying the app itself.
+    pub fn jwt(&self) -> Result<String, AuthError> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            // backdated to tolerate clock drift, as GitHub recommends
+            iat: now - 60,
+            exp: now + 9 * 60,
+            iss: self.app_id.to_string(),
+        };
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)
+            .map_err(AuthError::InvalidKey)
+    }
+
+    pub async fn installation_token(&self, installation_id: u64) -> Result<String, AuthError> {
+        Ok(self.fresh_token(installation_id).await?.token)
+    }
+
+    /// The permissions the installation's token was granted.
+    pub async fn installation_permissions(
+        &self,
+        installation_id: u64,
+    ) -> Result<HashMap<String, String>, AuthError> {
+        Ok(self.fresh_token(installation_id).await?.permissions)
+    }
+
+    async fn fresh_token(&self, installation_id: u64) -> Result<InstallationToken, AuthError> {
+        let mut tokens = self.tokens.lock().await;
+        if let Some(token) = tokens.get(&installation_id).filter(|it| it.is_fresh()) {
+            return Ok(token.clone());
+        }
+        let token = self.request_installation_token(installation_id).await?;
+        tokens.insert(installation_id, token.clone());
+        Ok(token)
+    }
+
+    async fn request_installation_token(
+        &self,
+        installation_id: u64,
+    ) -> Result<InstallationToken, AuthError> {
+        let response = self
+            .http
+            .post(format!(
+                "{}/app/installations/{}/access_tokens",
+                self.base_url, installation_id
+            ))
+            .bearer_auth(self.jwt()?)
+            .header(reqwest::header::ACCEPT, "application/vnd.github.v3+json")
+            .header(reqwest::header::USER_AGENT, "baipiao-bot-rust")
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(AuthError::Status {
+                status: status.as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            });
+        }
+        Ok(response.json().await?)
+    }
+}
+
+/// How the bot authenticates against the GitHub API.
+#[derive(Clone)]
+pub enum Auth {
+    None,
+    Token(String),
+    App(Arc<AppAuth>),
+}
+
+impl Auth {
+    /// Reads `BAIPIAO_APP_ID` and `BAIPIAO_APP_PRIVATE_KEY` if present, and
+    /// falls back to the personal token in `BAIPIAO_BOT_TOKEN`, then to the
+    /// workflow's own `GITHUB_TOKEN`. Empty variables count as unset, as
+    /// workflows pass secrets that do not exist as empty strings.
+    pub fn from_env() -> Result<Self, AuthError> {
+        if let Ok(app_id) = env::var("BAIPIAO_APP_ID") {
+            let app_id = app_id
+                .trim()
+                .parse()
+                .map_err(|_| AuthError::InvalidAppId(app_id.clone()))?;
+            let key = env::var("BAIPIAO_APP_PRIVATE_KEY")
+                .map_err(|_| AuthError::MissingEnv("BAIPIAO_APP_PRIVATE_KEY"))?;
+            return Ok(Auth::App(Arc::new(AppAuth::new(app_id, key.as_bytes())?)));
+        }
+        ["BAIPIAO_BOT_TOKEN", "GITHUB_TOKEN"]
+            .iter()
+            .find_map(|name| env::var(name).ok().filter(|it| !it.is_empty()))
+            .map(Auth::Token)
+            .ok_or(AuthError::MissingEnv("BAIPIAO_BOT_TOKEN"))
+    }
+
+    /// The token to send for an event from `installation_id`, if any.
+    pub async fn token(&self, installation_id: Option<u64>) -> Result<Option<String>, AuthError> {
+        match self {
+            Auth::None => Ok(None),
+            Auth::Token(token) => Ok(Some(token.clone())),
+            Auth::App(app) => {
+                let installation_id = installation_id.ok_or(AuthError::MissingInstallation)?;
+                app.installation_token(installation_id).await.map(Some)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Auth::None => write!(f, "Auth::None"),
+            Auth::Token(_) => write!(f, "Auth::Token(..)"),
+            Auth::App(app) => write!(f, "Auth::App({})", app.app_id),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use async_trait::async_trait;
+use baipiao_bot_rust::cli::Cli;
+use baipiao_bot_rust::{Bot, Context, Event};
+
+/// Prints every event it gets.
+struct Echo;
+
+#[async_trait]
+impl Bot for Echo {
+    async fn handle(&self, ctx: &Context, event: Event) {
+        println!("{}/{}: {:#?}", ctx.repo.owner, ctx.repo.name, event);
+    }
+
+    fn receive_bot_events(&self) -> bool {
+        true
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let code = Cli::new().bot("echo", Echo).run().await;
+    std::process::exit(code);
+}
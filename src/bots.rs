@@ -0,0 +1,32 @@
+use crate::Sender;
+
+/// What happens to events sent by bot accounts other than the bot itself,
+/// such as Dependabot or `github-actions[bot]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BotPolicy {
+    #[default]
+    Ignore,
+    Allow,
+    /// Only the listed logins, e.g. `dependabot[bot]`, get through.
+    AllowList(Vec<String>),
+}
+
+/// Whether an event from `sender` should be dropped before any handler runs.
+///
+/// Events from `own_login` are always dropped, so that a bot replying to
+/// comments cannot trigger itself forever.
+pub fn is_ignored(sender: &Sender, own_login: Option<&str>, policy: &BotPolicy) -> bool {
+    if own_login.is_some_and(|login| login.eq_ignore_ascii_case(&sender.login)) {
+        return true;
+    }
+    if !sender.is_bot {
+        return false;
+    }
+    match policy {
+        BotPolicy::Ignore => true,
+        BotPolicy::Allow => false,
+        BotPolicy::AllowList(logins) => !logins
+            .iter()
+            .any(|login| login.eq_ignore_ascii_case(&sender.login)),
+    }
+}
@@ -0,0 +1,39 @@
+//! Synthetic payloads, written by hand after GitHub's webhook documentation,
+//! the same ones the conformance tests parse.
+
+macro_rules! fixtures {
+    ($($name:literal,)*) => {
+        /// Each fixture's name, e.g. `issues.opened`, and payload.
+        pub const FIXTURES: &[(&str, &str)] = &[$(
+            ($name, include_str!(concat!("../../tests/fixtures/events/synthetic/", $name, ".json"))),
+        )*];
+    };
+}
+
+fixtures! {
+    "issue_comment.created",
+    "issue_comment.created.bot",
+    "issue_comment.created.pull-request",
+    "issue_comment.deleted",
+    "issue_comment.edited",
+    "issues.closed",
+    "issues.edited.body",
+    "issues.edited.title",
+    "issues.opened",
+    "issues.opened.empty-body",
+    "issues.reopened",
+    "pull_request.closed.merged",
+    "pull_request.edited.base",
+    "pull_request.edited.title",
+    "pull_request.opened",
+    "pull_request.opened.fork",
+    "pull_request.reopened",
+    "pull_request.synchronize",
+}
+
+pub fn get(name: &str) -> Option<&'static str> {
+    FIXTURES
+        .iter()
+        .find(|(it, _)| *it == name)
+        .map(|(_, payload)| *payload)
+}
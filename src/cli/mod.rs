@@ -0,0 +1,402 @@
+//! The `baipiao` command line, for reproducing a run from the event JSON a
+//! workflow received.
+//!
+//! The `baipiao` binary only knows a bot printing the events it gets; to
+//! replay payloads against your own bots, build the same command line with
+//! them:
+//!
+//! ```ignore
+//! #[tokio::main]
+//! async fn main() {
+//!     let code = Cli::new()
+//!         .bot("triage", Triage)
+//!         .login("baipiao-bot")
+//!         .config::<Settings>()
+//!         .run()
+//!         .await;
+//!     std::process::exit(code);
+//! }
+//! ```
+
+pub mod fixtures;
+
+use crate::action_log::PlanFormat;
+use crate::auth::Auth;
+use crate::config::{Config, ConfigLoader};
+use crate::layers::Outcome;
+use crate::logging::{self, LogFormat};
+use crate::payload;
+use crate::scopes::Manifest;
+use crate::subscriptions::{Subscriptions, WorkflowFile};
+use crate::templates::Templates;
+#[cfg(feature = "testing")]
+use crate::testing::MockGitHub;
+use crate::{Bot, Dispatcher, Event, GitHub, GitHubApi};
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::{env, fs};
+
+const USAGE: &str = "\
+usage: baipiao <command>
+
+commands:
+  parse <file> [--event <name>]       show the event a payload parses to
+  replay <file> [--event <name>] [--bot <name>] [--json] [--offline]
+                                      handle a payload in a dry run
+  fixtures list                       list the example payloads
+  fixtures show <name>                print one of them
+  validate-config [<file>]            check .github/baipiao.toml or <file>
+  workflow [--bot <name>] [--docker <image>]
+                                      print a workflow running the bots on
+                                      the events they handle
+
+<file> is the `github` context of a run, `toJson(github)`, or `-` for stdin.
+With --event, it is a webhook payload instead, like the workflow's
+$GITHUB_EVENT_PATH, of the given event, e.g. `issues`.
+Replays read from GitHub with the credentials in BAIPIAO_APP_ID and
+BAIPIAO_APP_PRIVATE_KEY or BAIPIAO_BOT_TOKEN, if set. With --offline, they
+read from an empty in-memory GitHub instead, which has no issues, comments
+or files; this needs the `testing` feature.
+";
+
+/// Checks only what every bot shares, the `templates` table.
+#[derive(Default, Deserialize)]
+struct Basic {
+    #[serde(default)]
+    #[allow(dead_code)]
+    templates: Templates,
+}
+
+impl Config for Basic {}
+
+/// The `baipiao` command line with a set of bots to replay payloads against.
+pub struct Cli {
+    bots: Vec<(String, Arc<dyn Bot>)>,
+    login: Option<String>,
+    config: Option<ConfigLoader>,
+    api: Option<Arc<dyn GitHubApi>>,
+    workflow: WorkflowFile,
+}
+
+impl Default for Cli {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cli {
+    pub fn new() -> Self {
+        Cli {
+            bots: Vec::new(),
+            login: None,
+            config: None,
+            api: None,
+            workflow: WorkflowFile::new(),
+        }
+    }
+
+    /// Makes `bot` available as `replay --bot <name>`.
+    pub fn bot(mut self, name: impl Into<String>, bot: impl Bot + 'static) -> Self {
+        self.bots.push((name.into(), Arc::new(bot)));
+        self
+    }
+
+    /// The bot's own login, as passed to `Dispatcher::with_login`.
+    pub fn login(mut self, login: impl Into<String>) -> Self {
+        self.login = Some(login.into());
+        self
+    }
+
+    /// Loads and checks the configuration as `C`, see
+    /// `Dispatcher::with_config`.
+    pub fn config<C: Config>(mut self) -> Self {
+        self.config = Some(ConfigLoader::new::<C>());
+        self
+    }
+
+    /// Reads from `api` instead of GitHub during replays, e.g. a mock.
+    pub fn api(mut self, api: Arc<dyn GitHubApi>) -> Self {
+        self.api = Some(api);
+        self
+    }
+
+    /// What `workflow` prints, with the `on:` list filled in.
+    pub fn workflow(mut self, workflow: WorkflowFile) -> Self {
+        self.workflow = workflow;
+        self
+    }
+
+    /// Runs the command in the process's arguments, returning the exit code.
+    /// Logs go to stderr, as JSON if `BAIPIAO_LOG_FORMAT` is `json`, see
+    /// `logging::init`.
+    pub async fn run(&self) -> i32 {
+        logging::init(LogFormat::from_env());
+        let args: Vec<String> = env::args().skip(1).collect();
+        let stdout = io::stdout();
+        let stderr = io::stderr();
+        self.run_with(&args, &mut stdout.lock(), &mut stderr.lock())
+            .await
+    }
+
+    /// Runs the command in `args`, without the program name, returning the
+    /// exit code: 0 on success, 1 when the command failed and 2 for a bad
+    /// command line.
+    pub async fn run_with(&self, args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let result = match args.as_slice() {
+            ["parse", rest @ ..] => match Options::parse(rest) {
+                Ok(options) if options.file.is_some() => self.parse(&options, out),
+                Ok(_) => return usage(err, "missing <file>"),
+                Err(message) => return usage(err, &message),
+            },
+            ["replay", rest @ ..] => match Options::parse(rest) {
+                Ok(options) if options.file.is_some() => self.replay(&options, out).await,
+                Ok(_) => return usage(err, "missing <file>"),
+                Err(message) => return usage(err, &message),
+            },
+            ["fixtures", "list"] => list_fixtures(out),
+            ["fixtures", "show", name] => match fixtures::get(name) {
+                Some(payload) => write!(out, "{}", payload).map_err(|err| err.to_string()),
+                None => Err(format!("no fixture named `{}`, see `fixtures list`", name)),
+            },
+            ["workflow", rest @ ..] => match Options::parse(rest) {
+                Ok(Options {
+                    file: Some(file), ..
+                }) => return usage(err, &format!("unexpected argument `{}`", file)),
+                Ok(options) => self.print_workflow(&options, out),
+                Err(message) => return usage(err, &message),
+            },
+            ["validate-config"] => self.validate_config(None, out),
+            ["validate-config", file] => self.validate_config(Some(file), out),
+            ["help"] | ["--help"] | ["-h"] => {
+                let _ = write!(out, "{}", USAGE);
+                return 0;
+            }
+            [] => return usage(err, "missing command"),
+            [command, ..] => return usage(err, &format!("unknown command `{}`", command)),
+        };
+        match result {
+            Ok(()) => 0,
+            Err(message) => {
+                let _ = writeln!(err, "error: {}", message);
+                1
+            }
+        }
+    }
+
+    fn parse(&self, options: &Options<'_>, out: &mut dyn Write) -> Result<(), String> {
+        let payload = options.payload()?;
+        let event = Event::parse(&payload).map_err(|err| err.to_string())?;
+        writeln!(out, "{:#?}", event).map_err(|err| err.to_string())
+    }
+
+    fn find_bot(&self, name: &str) -> Result<&Arc<dyn Bot>, String> {
+        self.bots
+            .iter()
+            .find(|(it, _)| it == name)
+            .map(|(_, bot)| bot)
+            .ok_or_else(|| format!("no bot named `{}`, {}", name, self.bot_names()))
+    }
+
+    async fn replay(&self, options: &Options<'_>, out: &mut dyn Write) -> Result<(), String> {
+        let (name, bot) = match (options.bot, self.bots.as_slice()) {
+            (Some(name), _) => (name, self.find_bot(name)?),
+            (None, [(name, bot)]) => (name.as_str(), bot),
+            (None, _) => return Err(format!("pick a bot with --bot, {}", self.bot_names())),
+        };
+        let payload = options.payload()?;
+        // the dispatcher skips unsupported events, which are a mistake here
+        Event::parse(&payload).map_err(|err| err.to_string())?;
+        let format = if options.json {
+            PlanFormat::Json
+        } else {
+            PlanFormat::Text
+        };
+        let mut dispatcher = Dispatcher::new(bot.clone())
+            .with_step_summary(false)
+            .with_dry_run(true);
+        if let Some(config) = &self.config {
+            dispatcher = dispatcher.with_config_loader(config.clone());
+        }
+        dispatcher = match &self.api {
+            Some(api) => dispatcher.with_api(api.clone()),
+            #[cfg(feature = "testing")]
+            None if options.offline => {
+                let login = self.login.as_deref().unwrap_or("baipiao-bot");
+                dispatcher.with_api(MockGitHub::new().login(login).api())
+            }
+            #[cfg(not(feature = "testing"))]
+            None if options.offline => {
+                return Err("--offline needs baipiao built with the `testing` feature".to_string())
+            }
+            None => dispatcher.with_github(GitHub::new(Auth::from_env().unwrap_or(Auth::None))),
+        };
+        if let Some(login) = &self.login {
+            dispatcher = dispatcher.with_login(login.clone());
+        }
+        let report = dispatcher.dispatch(payload).await;
+        write!(out, "{}", report.render(format)).map_err(|err| err.to_string())?;
+        if !options.json {
+            writeln!(out, "{}: {}", name, report.outcome).map_err(|err| err.to_string())?;
+        }
+        match report.outcome {
+            Outcome::Failed(message) => Err(message),
+            _ => Ok(()),
+        }
+    }
+
+    /// The workflow for the bot picked with `--bot`, or for all of them.
+    fn print_workflow(&self, options: &Options<'_>, out: &mut dyn Write) -> Result<(), String> {
+        let (subscriptions, permissions) = match options.bot {
+            Some(name) => {
+                let bot = self.find_bot(name)?;
+                (bot.subscriptions(), bot.permissions())
+            }
+            None => self.bots.iter().fold(
+                (Subscriptions::none(), Manifest::new()),
+                |(subscriptions, permissions), (_, bot)| {
+                    (
+                        subscriptions.union(&bot.subscriptions()),
+                        permissions.union(&bot.permissions()),
+                    )
+                },
+            ),
+        };
+        if subscriptions.is_empty() {
+            return Err("the bots handle no events".to_string());
+        }
+        let yaml = match options.docker {
+            Some(image) => {
+                let workflow = WorkflowFile::new().docker(image);
+                workflow.render(&subscriptions, &permissions)
+            }
+            None => self.workflow.render(&subscriptions, &permissions),
+        };
+        write!(out, "{}", yaml).map_err(|err| err.to_string())
+    }
+
+    fn bot_names(&self) -> String {
+        if self.bots.is_empty() {
+            return "but there are no bots".to_string();
+        }
+        let names: Vec<&str> = self.bots.iter().map(|(name, _)| name.as_str()).collect();
+        format!("one of `{}`", names.join("`, `"))
+    }
+
+    fn validate_config(&self, file: Option<&str>, out: &mut dyn Write) -> Result<(), String> {
+        let basic = ConfigLoader::new::<Basic>();
+        let config = self.config.as_ref().unwrap_or(&basic);
+        let (path, text) = match file {
+            Some(file) => (file.to_string(), read(file)?),
+            None => {
+                let found = config.paths().iter().find(|it| Path::new(it).exists());
+                match found {
+                    Some(path) => (path.clone(), read(path)?),
+                    None => {
+                        let paths = config.paths().join(", ");
+                        return Err(format!("no configuration at {}", paths));
+                    }
+                }
+            }
+        };
+        config
+            .validate(&text, &path)
+            .map_err(|err| err.to_string())?;
+        writeln!(out, "{} is valid", path).map_err(|err| err.to_string())
+    }
+}
+
+fn usage(err: &mut dyn Write, message: &str) -> i32 {
+    let _ = write!(err, "error: {}\n\n{}", message, USAGE);
+    2
+}
+
+fn list_fixtures(out: &mut dyn Write) -> Result<(), String> {
+    for (name, payload) in fixtures::FIXTURES {
+        let payload: Value = serde_json::from_str(payload).map_err(|err| err.to_string())?;
+        let event = match Event::parse(&payload) {
+            Ok(event) => event.name().to_string(),
+            Err(err) => err.to_string(),
+        };
+        writeln!(out, "{:<40}{}", name, event).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+fn read(file: &str) -> Result<String, String> {
+    if file == "-" {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|err| format!("cannot read stdin: {}", err))?;
+        return Ok(text);
+    }
+    fs::read_to_string(file).map_err(|err| format!("cannot read {}: {}", file, err))
+}
+
+struct Options<'a> {
+    file: Option<&'a str>,
+    event: Option<&'a str>,
+    bot: Option<&'a str>,
+    docker: Option<&'a str>,
+    json: bool,
+    offline: bool,
+}
+
+impl<'a> Options<'a> {
+    fn parse(args: &[&'a str]) -> Result<Self, String> {
+        let mut options = Options {
+            file: None,
+            event: None,
+            bot: None,
+            docker: None,
+            json: false,
+            offline: false,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match *arg {
+                "--event" => options.event = Some(value(args.next(), arg)?),
+                "--bot" => options.bot = Some(value(args.next(), arg)?),
+                "--docker" => options.docker = Some(value(args.next(), arg)?),
+                "--json" => options.json = true,
+                "--offline" => options.offline = true,
+                "-" => options.file = Some(arg),
+                flag if flag.starts_with('-') => {
+                    return Err(format!("unknown option `{}`", flag));
+                }
+                file if options.file.is_none() => options.file = Some(file),
+                extra => return Err(format!("unexpected argument `{}`", extra)),
+            }
+        }
+        Ok(options)
+    }
+
+    /// The payload in the `github` context shape, wrapping webhook payloads
+    /// given with `--event`.
+    fn payload(&self) -> Result<Value, String> {
+        let file = self.file.unwrap_or("-");
+        let text = read(file)?;
+        let payload: Value =
+            serde_json::from_str(&text).map_err(|err| format!("{}: {}", file, err))?;
+        match self.event {
+            Some(event_name) => Ok(payload::wrap(event_name, payload)),
+            None if payload.get("event_name").is_none() && payload.get("action").is_some() => {
+                Err(format!(
+                    "{} looks like a webhook payload, pass its event with --event",
+                    file
+                ))
+            }
+            None => Ok(payload),
+        }
+    }
+}
+
+fn value<'a>(value: Option<&&'a str>, flag: &str) -> Result<&'a str, String> {
+    value
+        .copied()
+        .ok_or_else(|| format!("`{}` needs a value", flag))
+}
@@ -0,0 +1,796 @@
+use crate::auth::{Auth, AuthError, DEFAULT_API_URL};
+use crate::metrics::Metrics;
+use crate::rate_limit::{Limited, RateLimits, WriteQueue};
+use crate::scopes::Manifest;
+use crate::Repository;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use reqwest::{Method, RequestBuilder, Response};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+use std::fmt;
+use tracing::Instrument;
+
+#[derive(Debug)]
+pub enum ClientError {
+    Auth(AuthError),
+    Http(reqwest::Error),
+    Status {
+        status: u16,
+        message: String,
+    },
+    /// A rate limit turned the call away and waiting it out was not an
+    /// option; `secondary` for the limits on bursts and content creation
+    /// rather than the hourly budget.
+    RateLimited {
+        secondary: bool,
+        retry_at: Option<DateTime<Utc>>,
+    },
+    /// The response did not hold what it should.
+    Decode(String),
+    /// The operation needs an issue or pull request, but the event is not
+    /// about one.
+    NoIssue,
+    /// The operation needs the bot's own login, e.g. to tell its comments
+    /// from those of others, but it is not known.
+    NoLogin,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Auth(err) => write!(f, "{}", err),
+            ClientError::Http(err) => write!(f, "request failed: {}", err),
+            ClientError::Status { status, message } => {
+                write!(f, "GitHub API returned {}: {}", status, message)
+            }
+            ClientError::RateLimited {
+                secondary,
+                retry_at,
+            } => {
+                let limit = if *secondary {
+                    "a secondary rate limit"
+                } else {
+                    "the rate limit"
+                };
+                match retry_at {
+                    Some(at) => write!(f, "{} of GitHub was hit, retry after {}", limit, at),
+                    None => write!(f, "{} of GitHub was hit, retry later", limit),
+                }
+            }
+            ClientError::Decode(message) => write!(f, "cannot decode response: {}", message),
+            ClientError::NoIssue => write!(f, "the event is not about an issue or pull request"),
+            ClientError::NoLogin => write!(
+                f,
+                "the bot's login is not known, set it with `Dispatcher::with_login`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<AuthError> for ClientError {
+    fn from(err: AuthError) -> Self {
+        ClientError::Auth(err)
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(err: reqwest::Error) -> Self {
+        ClientError::Http(err)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct User {
+    pub login: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Comment {
+    pub id: usize,
+    pub body: String,
+    pub user: User,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Issue {
+    pub number: usize,
+    pub title: String,
+    pub body: Option<String>,
+    pub user: User,
+}
+
+/// What a pull request review says.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewEvent {
+    Approve,
+    RequestChanges,
+    Comment,
+}
+
+impl ReviewEvent {
+    /// The name the API uses, e.g. `REQUEST_CHANGES`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReviewEvent::Approve => "APPROVE",
+            ReviewEvent::RequestChanges => "REQUEST_CHANGES",
+            ReviewEvent::Comment => "COMMENT",
+        }
+    }
+}
+
+/// A file in a repository, as of some commit.
+#[derive(Debug, Clone)]
+pub struct File {
+    /// The decoded text.
+    pub content: String,
+    /// The blob SHA, needed to update the file.
+    pub sha: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CollaboratorPermission {
+    /// `admin`, `write`, `read` or `none`.
+    pub permission: String,
+    /// The finer grained role, e.g. `maintain` or `triage`.
+    #[serde(default)]
+    pub role_name: String,
+}
+
+/// The GitHub operations bots use, implemented by `GitHub` and by mocks in
+/// tests.
+#[async_trait]
+pub trait GitHubApi: fmt::Debug + Send + Sync {
+    async fn create_comment(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+        body: &str,
+    ) -> Result<Comment, ClientError>;
+
+    async fn update_comment(
+        &self,
+        repo: &Repository,
+        comment_id: usize,
+        body: &str,
+    ) -> Result<Comment, ClientError>;
+
+    async fn delete_comment(&self, repo: &Repository, comment_id: usize)
+        -> Result<(), ClientError>;
+
+    async fn add_labels(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+        labels: &[String],
+    ) -> Result<(), ClientError>;
+
+    /// Removes `label` from the issue, if it is there.
+    async fn remove_label(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+        label: &str,
+    ) -> Result<(), ClientError>;
+
+    /// Closes an issue or pull request.
+    async fn close_issue(&self, repo: &Repository, issue_id: usize) -> Result<(), ClientError>;
+
+    async fn create_review(
+        &self,
+        repo: &Repository,
+        pull_request_id: usize,
+        review: ReviewEvent,
+        body: &str,
+    ) -> Result<(), ClientError>;
+
+    /// Every comment on an issue or pull request, oldest first.
+    async fn comments(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+    ) -> Result<Vec<Comment>, ClientError>;
+
+    async fn issue(&self, repo: &Repository, issue_id: usize) -> Result<Issue, ClientError>;
+
+    /// Paths of the files a pull request touches.
+    async fn pull_request_files(
+        &self,
+        repo: &Repository,
+        pull_request_id: usize,
+    ) -> Result<Vec<String>, ClientError>;
+
+    /// The file at `path`, at `git_ref` or the default branch, or `None` if
+    /// there is no such file.
+    async fn file(
+        &self,
+        repo: &Repository,
+        path: &str,
+        git_ref: Option<&str>,
+    ) -> Result<Option<File>, ClientError>;
+
+    /// Commits `content` to `path` on `branch`, returning the new blob SHA.
+    /// `sha` is the blob being replaced, `None` for a new file; GitHub
+    /// answers 409 when it is out of date.
+    async fn put_file(
+        &self,
+        repo: &Repository,
+        branch: &str,
+        path: &str,
+        content: &str,
+        sha: Option<&str>,
+        message: &str,
+    ) -> Result<String, ClientError>;
+
+    async fn collaborator_permission(
+        &self,
+        repo: &Repository,
+        user: &str,
+    ) -> Result<CollaboratorPermission, ClientError>;
+
+    /// Whether `user` is an active member of `org/team`.
+    async fn is_team_member(&self, org: &str, team: &str, user: &str) -> Result<bool, ClientError>;
+
+    /// What the token may do in `repo`, or `None` when that cannot be told,
+    /// e.g. for a workflow's own token.
+    async fn granted_permissions(
+        &self,
+        _repo: &Repository,
+    ) -> Result<Option<Manifest>, ClientError> {
+        Ok(None)
+    }
+}
+
+/// A small GitHub REST client, bound to the installation that triggered the
+/// current event.
+#[derive(Debug, Clone)]
+pub struct GitHub {
+    http: reqwest::Client,
+    auth: Auth,
+    installation_id: Option<u64>,
+    base_url: String,
+    metrics: Metrics,
+    limits: RateLimits,
+    writes: WriteQueue,
+}
+
+impl GitHub {
+    pub fn new(auth: Auth) -> Self {
+        GitHub {
+            http: reqwest::Client::new(),
+            auth,
+            installation_id: None,
+            base_url: DEFAULT_API_URL.to_string(),
+            metrics: Metrics::new(),
+            limits: RateLimits::default(),
+            writes: WriteQueue::default(),
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Records each call and the rate limit left in `metrics`.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// How long to wait out rate limits, and how many writes to allow.
+    /// Clones share the queue of writes of each token.
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// A client acting on behalf of `installation_id`, sharing the token cache.
+    pub fn installation(&self, installation_id: Option<u64>) -> Self {
+        GitHub {
+            installation_id,
+            ..self.clone()
+        }
+    }
+
+    pub fn auth(&self) -> &Auth {
+        &self.auth
+    }
+
+    async fn request(&self, method: Method, path: &str) -> Result<RequestBuilder, ClientError> {
+        let builder = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path))
+            .header(reqwest::header::ACCEPT, "application/vnd.github.v3+json")
+            .header(reqwest::header::USER_AGENT, "baipiao-bot-rust");
+        Ok(match self.auth.token(self.installation_id).await? {
+            Some(token) => {
+                builder.header(reqwest::header::AUTHORIZATION, format!("token {}", token))
+            }
+            None => builder,
+        })
+    }
+
+    async fn send<T: DeserializeOwned>(&self, builder: RequestBuilder) -> Result<T, ClientError> {
+        let response = self.execute(builder).await?;
+        Ok(response.json().await?)
+    }
+
+    async fn send_empty(&self, builder: RequestBuilder) -> Result<(), ClientError> {
+        self.execute(builder).await?;
+        Ok(())
+    }
+
+    /// Sends the request, retrying it while a rate limit turns it away if
+    /// it is idempotent. Writes wait for their turn in the write queue.
+    async fn execute(&self, builder: RequestBuilder) -> Result<Response, ClientError> {
+        let (http, request) = builder.build_split();
+        let request = request?;
+        let method = request.method().clone();
+        let idempotent = matches!(
+            method,
+            Method::GET | Method::HEAD | Method::PUT | Method::DELETE
+        );
+        let _turn = if method == Method::GET || method == Method::HEAD {
+            None
+        } else {
+            // a token is shared by every installation, an app's are not
+            let installation = match self.auth {
+                Auth::Token(_) => None,
+                _ => self.installation_id,
+            };
+            let turn = self.writes.turn(installation, &self.limits).await;
+            Some(turn.map_err(|wait| {
+                ClientError::RateLimited {
+                    secondary: true,
+                    retry_at: chrono::Duration::from_std(wait)
+                        .ok()
+                        .map(|it| Utc::now() + it),
+                }
+            })?)
+        };
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            // bodies are built in memory, never streamed
+            let retry = request.try_clone().expect("request body is a stream");
+            let limited = match self.attempt(&http, retry).await? {
+                Ok(response) => return Ok(response),
+                Err(limited) => limited,
+            };
+            let wait = limited.wait(attempt, &self.limits);
+            if !idempotent || attempt > self.limits.retries || wait > self.limits.max_wait {
+                return Err(ClientError::RateLimited {
+                    secondary: limited.secondary,
+                    retry_at: limited.retry_at(),
+                });
+            }
+            tracing::warn!(
+                ?wait,
+                attempt,
+                secondary = limited.secondary,
+                "rate limited, retrying"
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Sends the request once, in a `github_api` span of its own.
+    async fn attempt(
+        &self,
+        http: &reqwest::Client,
+        request: reqwest::Request,
+    ) -> Result<Result<Response, Limited>, ClientError> {
+        let span = tracing::info_span!(
+            "github_api",
+            method = %request.method(),
+            path = request.url().path(),
+            status = tracing::field::Empty,
+        );
+        let method = request.method().to_string();
+        async move {
+            let response = match http.execute(request).await {
+                Ok(response) => response,
+                Err(err) => {
+                    let labels = [("method", method.as_str()), ("status", "error")];
+                    self.metrics.increment("baipiao_api_calls_total", &labels);
+                    return Err(err.into());
+                }
+            };
+            let status = response.status().as_u16();
+            tracing::Span::current().record("status", status);
+            let status = status.to_string();
+            let labels = [("method", method.as_str()), ("status", status.as_str())];
+            self.metrics.increment("baipiao_api_calls_total", &labels);
+            self.record_rate_limit(&response);
+            Self::check(response).await
+        }
+        .instrument(span)
+        .await
+    }
+
+    fn record_rate_limit(&self, response: &Response) {
+        let header = |name| response.headers().get(name).and_then(|it| it.to_str().ok());
+        if let Some(remaining) = header("x-ratelimit-remaining").and_then(|it| it.parse().ok()) {
+            let resource = header("x-ratelimit-resource").unwrap_or("core");
+            self.metrics.set(
+                "baipiao_rate_limit_remaining",
+                &[("resource", resource)],
+                remaining,
+            );
+        }
+    }
+
+    /// Tells failures from rate limits, which `execute` may wait out.
+    async fn check(response: Response) -> Result<Result<Response, Limited>, ClientError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Ok(response));
+        }
+        if let Some(limited) = Limited::from_headers(status.as_u16(), response.headers()) {
+            return Ok(Err(limited));
+        }
+        // a 403 names the permissions that would have been accepted
+        let accepted = response
+            .headers()
+            .get("x-accepted-github-permissions")
+            .and_then(|it| it.to_str().ok())
+            .map(str::to_string);
+        let mut message = response.text().await.unwrap_or_default();
+        if status.as_u16() == 403 && message.contains("secondary rate limit") {
+            return Ok(Err(Limited {
+                secondary: true,
+                wait: None,
+            }));
+        }
+        if let Some(accepted) = accepted.filter(|_| status.as_u16() == 403) {
+            message = format!("{} (needs {})", message, accepted);
+        }
+        Err(ClientError::Status {
+            status: status.as_u16(),
+            message,
+        })
+    }
+
+    /// The login the credentials act as: the token's user, or `slug[bot]`
+    /// for a GitHub App.
+    pub async fn authenticated_login(&self) -> Result<String, ClientError> {
+        if let Auth::App(app) = &self.auth {
+            #[derive(Deserialize)]
+            struct App {
+                slug: String,
+            }
+
+            let builder = self
+                .http
+                .get(format!("{}/app", self.base_url))
+                .bearer_auth(app.jwt()?)
+                .header(reqwest::header::ACCEPT, "application/vnd.github.v3+json")
+                .header(reqwest::header::USER_AGENT, "baipiao-bot-rust");
+            let app: App = self.send(builder).await?;
+            return Ok(format!("{}[bot]", app.slug));
+        }
+        let user: User = self.send(self.request(Method::GET, "/user").await?).await?;
+        Ok(user.login)
+    }
+}
+
+/// Percent-encodes `text` as one path segment or query value, e.g. a label
+/// like `good first issue` or `c#`.
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Percent-encodes each segment of a file path, keeping the slashes.
+fn encode_path(path: &str) -> String {
+    path.split('/').map(encode).collect::<Vec<_>>().join("/")
+}
+
+#[async_trait]
+impl GitHubApi for GitHub {
+    async fn create_comment(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+        body: &str,
+    ) -> Result<Comment, ClientError> {
+        let path = format!(
+            "/repos/{}/{}/issues/{}/comments",
+            repo.owner, repo.name, issue_id
+        );
+        let builder = self.request(Method::POST, &path).await?;
+        self.send(builder.json(&json!({ "body": body }))).await
+    }
+
+    async fn update_comment(
+        &self,
+        repo: &Repository,
+        comment_id: usize,
+        body: &str,
+    ) -> Result<Comment, ClientError> {
+        let path = format!(
+            "/repos/{}/{}/issues/comments/{}",
+            repo.owner, repo.name, comment_id
+        );
+        let builder = self.request(Method::PATCH, &path).await?;
+        self.send(builder.json(&json!({ "body": body }))).await
+    }
+
+    async fn delete_comment(
+        &self,
+        repo: &Repository,
+        comment_id: usize,
+    ) -> Result<(), ClientError> {
+        let path = format!(
+            "/repos/{}/{}/issues/comments/{}",
+            repo.owner, repo.name, comment_id
+        );
+        self.send_empty(self.request(Method::DELETE, &path).await?)
+            .await
+    }
+
+    async fn add_labels(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+        labels: &[String],
+    ) -> Result<(), ClientError> {
+        let path = format!(
+            "/repos/{}/{}/issues/{}/labels",
+            repo.owner, repo.name, issue_id
+        );
+        let builder = self.request(Method::POST, &path).await?;
+        self.send_empty(builder.json(&json!({ "labels": labels })))
+            .await
+    }
+
+    async fn remove_label(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+        label: &str,
+    ) -> Result<(), ClientError> {
+        let path = format!(
+            "/repos/{}/{}/issues/{}/labels/{}",
+            repo.owner,
+            repo.name,
+            issue_id,
+            encode(label)
+        );
+        match self
+            .send_empty(self.request(Method::DELETE, &path).await?)
+            .await
+        {
+            Err(ClientError::Status { status: 404, .. }) => Ok(()),
+            result => result,
+        }
+    }
+
+    async fn close_issue(&self, repo: &Repository, issue_id: usize) -> Result<(), ClientError> {
+        let path = format!("/repos/{}/{}/issues/{}", repo.owner, repo.name, issue_id);
+        let builder = self.request(Method::PATCH, &path).await?;
+        self.send_empty(builder.json(&json!({ "state": "closed" })))
+            .await
+    }
+
+    async fn create_review(
+        &self,
+        repo: &Repository,
+        pull_request_id: usize,
+        review: ReviewEvent,
+        body: &str,
+    ) -> Result<(), ClientError> {
+        let path = format!(
+            "/repos/{}/{}/pulls/{}/reviews",
+            repo.owner, repo.name, pull_request_id
+        );
+        let builder = self.request(Method::POST, &path).await?;
+        self.send_empty(builder.json(&json!({ "event": review.as_str(), "body": body })))
+            .await
+    }
+
+    async fn comments(
+        &self,
+        repo: &Repository,
+        issue_id: usize,
+    ) -> Result<Vec<Comment>, ClientError> {
+        let mut comments = Vec::new();
+        for page in 1.. {
+            let path = format!(
+                "/repos/{}/{}/issues/{}/comments?per_page=100&page={}",
+                repo.owner, repo.name, issue_id, page
+            );
+            let batch: Vec<Comment> = self.send(self.request(Method::GET, &path).await?).await?;
+            let done = batch.len() < 100;
+            comments.extend(batch);
+            if done {
+                break;
+            }
+        }
+        Ok(comments)
+    }
+
+    async fn issue(&self, repo: &Repository, issue_id: usize) -> Result<Issue, ClientError> {
+        let path = format!("/repos/{}/{}/issues/{}", repo.owner, repo.name, issue_id);
+        self.send(self.request(Method::GET, &path).await?).await
+    }
+
+    async fn pull_request_files(
+        &self,
+        repo: &Repository,
+        pull_request_id: usize,
+    ) -> Result<Vec<String>, ClientError> {
+        #[derive(Deserialize)]
+        struct File {
+            filename: String,
+        }
+
+        let mut files = Vec::new();
+        for page in 1.. {
+            let path = format!(
+                "/repos/{}/{}/pulls/{}/files?per_page=100&page={}",
+                repo.owner, repo.name, pull_request_id, page
+            );
+            let batch: Vec<File> = self.send(self.request(Method::GET, &path).await?).await?;
+            let done = batch.len() < 100;
+            files.extend(batch.into_iter().map(|file| file.filename));
+            if done {
+                break;
+            }
+        }
+        Ok(files)
+    }
+
+    async fn file(
+        &self,
+        repo: &Repository,
+        path: &str,
+        git_ref: Option<&str>,
+    ) -> Result<Option<File>, ClientError> {
+        #[derive(Deserialize)]
+        struct Contents {
+            content: String,
+            sha: String,
+        }
+
+        let mut path = format!(
+            "/repos/{}/{}/contents/{}",
+            repo.owner,
+            repo.name,
+            encode_path(path)
+        );
+        if let Some(git_ref) = git_ref {
+            path.push_str(&format!("?ref={}", encode(git_ref)));
+        }
+        let contents = match self
+            .send::<Contents>(self.request(Method::GET, &path).await?)
+            .await
+        {
+            Ok(contents) => contents,
+            Err(ClientError::Status { status: 404, .. }) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        // the content is base64 with a line break every 60 characters
+        let encoded: String = contents.content.split_whitespace().collect();
+        let decoded = STANDARD
+            .decode(encoded)
+            .map_err(|err| ClientError::Decode(err.to_string()))?;
+        let content =
+            String::from_utf8(decoded).map_err(|err| ClientError::Decode(err.to_string()))?;
+        Ok(Some(File {
+            content,
+            sha: contents.sha,
+        }))
+    }
+
+    async fn put_file(
+        &self,
+        repo: &Repository,
+        branch: &str,
+        path: &str,
+        content: &str,
+        sha: Option<&str>,
+        message: &str,
+    ) -> Result<String, ClientError> {
+        #[derive(Deserialize)]
+        struct Blob {
+            sha: String,
+        }
+        #[derive(Deserialize)]
+        struct Written {
+            content: Blob,
+        }
+
+        let path = format!(
+            "/repos/{}/{}/contents/{}",
+            repo.owner,
+            repo.name,
+            encode_path(path)
+        );
+        let mut body = json!({
+            "message": message,
+            "content": STANDARD.encode(content),
+            "branch": branch,
+        });
+        if let Some(sha) = sha {
+            body["sha"] = json!(sha);
+        }
+        let builder = self.request(Method::PUT, &path).await?;
+        let written: Written = self.send(builder.json(&body)).await?;
+        Ok(written.content.sha)
+    }
+
+    async fn collaborator_permission(
+        &self,
+        repo: &Repository,
+        user: &str,
+    ) -> Result<CollaboratorPermission, ClientError> {
+        let path = format!(
+            "/repos/{}/{}/collaborators/{}/permission",
+            repo.owner,
+            repo.name,
+            encode(user)
+        );
+        self.send(self.request(Method::GET, &path).await?).await
+    }
+
+    async fn is_team_member(&self, org: &str, team: &str, user: &str) -> Result<bool, ClientError> {
+        #[derive(Deserialize)]
+        struct Membership {
+            state: String,
+        }
+
+        let path = format!(
+            "/orgs/{}/teams/{}/memberships/{}",
+            encode(org),
+            encode(team),
+            encode(user)
+        );
+        match self
+            .send::<Membership>(self.request(Method::GET, &path).await?)
+            .await
+        {
+            Ok(membership) => Ok(membership.state == "active"),
+            Err(ClientError::Status { status: 404, .. }) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// An app's token lists its permissions; a personal token its scopes in
+    /// `X-OAuth-Scopes`. Workflow and fine-grained tokens tell neither.
+    async fn granted_permissions(
+        &self,
+        repo: &Repository,
+    ) -> Result<Option<Manifest>, ClientError> {
+        match &self.auth {
+            Auth::None => Ok(None),
+            Auth::App(app) => {
+                let installation_id = self.installation_id.ok_or(AuthError::MissingInstallation)?;
+                let permissions = app.installation_permissions(installation_id).await?;
+                Ok(Some(Manifest::from_app(&permissions)))
+            }
+            Auth::Token(_) => {
+                let path = format!("/repos/{}/{}", repo.owner, repo.name);
+                let builder = self.request(Method::GET, &path).await?;
+                let response = self.execute(builder).await?;
+                Ok(response
+                    .headers()
+                    .get("x-oauth-scopes")
+                    .and_then(|it| it.to_str().ok())
+                    .map(Manifest::from_oauth_scopes))
+            }
+        }
+    }
+}
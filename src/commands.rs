@@ -0,0 +1,460 @@
+use crate::permissions::{self, Role};
+use crate::{AuthorAssociation, Bot, CommentTarget, Context};
+use std::fmt;
+
+#[cfg(feature = "derive")]
+pub use baipiao_bot_derive::BotCommand;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// A single word, or a double-quoted phrase.
+    Word,
+    /// A GitHub login, with or without the leading `@`.
+    User,
+    Number,
+    /// Every remaining word.
+    List,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArgSpec {
+    pub name: String,
+    pub kind: ArgKind,
+    pub required: bool,
+}
+
+/// Declares a command a bot understands, e.g.
+/// `CommandSpec::new("label").arg("names", ArgKind::List)`.
+#[derive(Debug, Clone)]
+pub struct CommandSpec {
+    pub name: String,
+    pub description: String,
+    pub args: Vec<ArgSpec>,
+    /// Who may run the command; anyone by default.
+    pub role: Role,
+}
+
+impl CommandSpec {
+    pub fn new(name: impl Into<String>) -> Self {
+        CommandSpec {
+            name: name.into(),
+            description: String::new(),
+            args: Vec::new(),
+            role: Role::Anyone,
+        }
+    }
+
+    pub fn requires(mut self, role: Role) -> Self {
+        self.role = role;
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn arg(mut self, name: impl Into<String>, kind: ArgKind) -> Self {
+        self.args.push(ArgSpec {
+            name: name.into(),
+            kind,
+            required: true,
+        });
+        self
+    }
+
+    pub fn optional_arg(mut self, name: impl Into<String>, kind: ArgKind) -> Self {
+        self.args.push(ArgSpec {
+            name: name.into(),
+            kind,
+            required: false,
+        });
+        self
+    }
+
+    /// How to invoke the command, e.g. `/assign <@user> [times]`.
+    pub fn usage(&self) -> String {
+        let mut usage = format!("/{}", self.name);
+        for arg in &self.args {
+            let name = match arg.kind {
+                ArgKind::User => format!("@{}", arg.name),
+                ArgKind::List => format!("{}...", arg.name),
+                _ => arg.name.clone(),
+            };
+            if arg.required {
+                usage.push_str(&format!(" <{}>", name));
+            } else {
+                usage.push_str(&format!(" [{}]", name));
+            }
+        }
+        usage
+    }
+}
+
+/// A Markdown table listing every command, suitable for a `/help` reply.
+pub fn help_table(specs: &[CommandSpec]) -> String {
+    let mut table = String::from("| Command | Description |\n| --- | --- |\n");
+    for spec in specs {
+        table.push_str(&format!(
+            "| `{}` | {} |\n",
+            spec.usage(),
+            spec.description.replace('|', "\\|")
+        ));
+    }
+    table
+}
+
+/// A typed set of commands, usually derived with `#[derive(BotCommand)]`.
+///
+/// ```ignore
+/// #[derive(BotCommand)]
+/// enum Cmd {
+///     /// Adds labels to the issue
+///     Label { names: Vec<String> },
+///     /// Closes the issue
+///     Close,
+///     /// Assigns the issue to someone
+///     Assign {
+///         #[command(user)]
+///         user: String,
+///     },
+/// }
+/// ```
+pub trait BotCommand: Sized {
+    fn specs() -> Vec<CommandSpec>;
+
+    fn from_command(command: &Command) -> Result<Self, CommandError>;
+
+    fn help() -> String {
+        help_table(&Self::specs())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgValue {
+    Word(String),
+    User(String),
+    Number(i64),
+    List(Vec<String>),
+}
+
+/// Where a command was written.
+#[derive(Debug, Clone)]
+pub enum CommandSource {
+    Comment { id: usize, target: CommentTarget },
+    IssueBody(usize),
+    PullRequestBody(usize),
+}
+
+impl CommandSource {
+    /// The issue or pull request a reply should go to.
+    pub fn target_id(&self) -> usize {
+        match self {
+            CommandSource::Comment { target, .. } => target.id(),
+            CommandSource::IssueBody(id) => *id,
+            CommandSource::PullRequestBody(id) => *id,
+        }
+    }
+}
+
+/// Text that may contain commands, with who wrote it and where.
+#[derive(Debug, Clone)]
+pub struct CommandText {
+    pub text: String,
+    pub user: String,
+    pub association: AuthorAssociation,
+    pub source: CommandSource,
+}
+
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub name: String,
+    pub args: Vec<(String, ArgValue)>,
+    pub user: String,
+    pub association: AuthorAssociation,
+    pub source: CommandSource,
+}
+
+impl Command {
+    pub fn arg(&self, name: &str) -> Option<&ArgValue> {
+        self.args
+            .iter()
+            .find(|(arg_name, _)| arg_name == name)
+            .map(|(_, value)| value)
+    }
+
+    /// The value of a `Word` or `User` argument.
+    pub fn word(&self, name: &str) -> Option<&str> {
+        match self.arg(name)? {
+            ArgValue::Word(word) | ArgValue::User(word) => Some(word),
+            _ => None,
+        }
+    }
+
+    pub fn number(&self, name: &str) -> Option<i64> {
+        match self.arg(name)? {
+            ArgValue::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn list(&self, name: &str) -> &[String] {
+        match self.arg(name) {
+            Some(ArgValue::List(list)) => list,
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandErrorKind {
+    MissingArgument(String),
+    InvalidNumber { arg: String, value: String },
+    UnexpectedArgument(String),
+    UnknownCommand,
+}
+
+/// A known command was invoked with arguments that do not match its spec.
+#[derive(Debug, Clone)]
+pub struct CommandError {
+    pub name: String,
+    pub user: String,
+    pub source: CommandSource,
+    pub kind: CommandErrorKind,
+}
+
+impl CommandError {
+    pub fn new(command: &Command, kind: CommandErrorKind) -> Self {
+        CommandError {
+            name: command.name.clone(),
+            user: command.user.clone(),
+            source: command.source.clone(),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            CommandErrorKind::MissingArgument(arg) => {
+                write!(f, "`/{}`: missing argument `{}`", self.name, arg)
+            }
+            CommandErrorKind::InvalidNumber { arg, value } => write!(
+                f,
+                "`/{}`: argument `{}` must be a number, got `{}`",
+                self.name, arg, value
+            ),
+            CommandErrorKind::UnexpectedArgument(value) => {
+                write!(f, "`/{}`: unexpected argument `{}`", self.name, value)
+            }
+            CommandErrorKind::UnknownCommand => write!(f, "unknown command `/{}`", self.name),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Finds commands in Markdown text.
+///
+/// A command is a line starting with `/name` or, when a mention is set,
+/// `@login name`. Lines inside fenced or indented code blocks and quoted
+/// lines are skipped, and only commands listed in the specs are recognized.
+pub struct CommandParser {
+    specs: Vec<CommandSpec>,
+    mention: Option<String>,
+}
+
+impl CommandParser {
+    pub fn new(specs: Vec<CommandSpec>) -> Self {
+        CommandParser {
+            specs,
+            mention: None,
+        }
+    }
+
+    pub fn mention(mut self, login: impl Into<String>) -> Self {
+        self.mention = Some(login.into());
+        self
+    }
+
+    pub fn parse(
+        &self,
+        text: &str,
+        user: &str,
+        association: AuthorAssociation,
+        source: &CommandSource,
+    ) -> Vec<Result<Command, CommandError>> {
+        let mut fence: Option<&str> = None;
+        // an indented code block cannot interrupt a paragraph, there an
+        // indented line only continues it
+        let mut in_paragraph = false;
+        let mut result = Vec::new();
+        for raw in text.lines() {
+            let line = raw.trim();
+            let indented = raw.starts_with("    ") || raw.starts_with('\t');
+            if fence.is_none() && indented && !in_paragraph {
+                continue;
+            }
+            in_paragraph = false;
+            if let Some(marker) = fence {
+                if line.starts_with(marker) {
+                    fence = None;
+                }
+                continue;
+            }
+            if line.starts_with("```") {
+                fence = Some("```");
+                continue;
+            }
+            if line.starts_with("~~~") {
+                fence = Some("~~~");
+                continue;
+            }
+            if line.starts_with('>') {
+                continue;
+            }
+            in_paragraph = !line.is_empty();
+            if let Some(command) = self.parse_line(line, user, association, source) {
+                result.push(command);
+            }
+        }
+        result
+    }
+
+    fn parse_line(
+        &self,
+        line: &str,
+        user: &str,
+        association: AuthorAssociation,
+        source: &CommandSource,
+    ) -> Option<Result<Command, CommandError>> {
+        let mut tokens = tokenize(line).into_iter();
+        let first = tokens.next()?;
+        let name = if let Some(name) = first.strip_prefix('/') {
+            name.to_string()
+        } else {
+            let login = first.strip_prefix('@')?;
+            if !self
+                .mention
+                .as_deref()
+                .is_some_and(|it| it.eq_ignore_ascii_case(login))
+            {
+                return None;
+            }
+            tokens.next()?
+        };
+        let spec = self.specs.iter().find(|spec| spec.name == name)?;
+        let error = |kind| CommandError {
+            name: name.clone(),
+            user: user.to_string(),
+            source: source.clone(),
+            kind,
+        };
+        let mut args = Vec::new();
+        for arg in &spec.args {
+            let value = match arg.kind {
+                ArgKind::List => {
+                    let list: Vec<String> = tokens.by_ref().collect();
+                    if list.is_empty() && arg.required {
+                        return Some(Err(error(CommandErrorKind::MissingArgument(
+                            arg.name.clone(),
+                        ))));
+                    }
+                    ArgValue::List(list)
+                }
+                kind => match tokens.next() {
+                    None if arg.required => {
+                        return Some(Err(error(CommandErrorKind::MissingArgument(
+                            arg.name.clone(),
+                        ))))
+                    }
+                    None => continue,
+                    Some(token) => match kind {
+                        ArgKind::User => ArgValue::User(token.trim_start_matches('@').to_string()),
+                        ArgKind::Number => match token.parse() {
+                            Ok(number) => ArgValue::Number(number),
+                            Err(_) => {
+                                return Some(Err(error(CommandErrorKind::InvalidNumber {
+                                    arg: arg.name.clone(),
+                                    value: token,
+                                })))
+                            }
+                        },
+                        _ => ArgValue::Word(token),
+                    },
+                },
+            };
+            args.push((arg.name.clone(), value));
+        }
+        if let Some(extra) = tokens.next() {
+            return Some(Err(error(CommandErrorKind::UnexpectedArgument(extra))));
+        }
+        Some(Ok(Command {
+            name,
+            args,
+            user: user.to_string(),
+            association,
+            source: source.clone(),
+        }))
+    }
+}
+
+/// Splits on whitespace, keeping double-quoted phrases together.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Runs the commands in `text` that `bot` declares, checking permissions
+/// first.
+pub(crate) async fn dispatch<B: Bot + ?Sized>(bot: &B, ctx: &Context, text: CommandText) {
+    let specs = bot.commands();
+    if specs.is_empty() {
+        return;
+    }
+    let mut parser = CommandParser::new(specs.clone());
+    if let Some(login) = &ctx.login {
+        // apps are mentioned by their slug, without the `[bot]` suffix
+        parser = parser.mention(login.trim_end_matches("[bot]"));
+    }
+    for command in parser.parse(&text.text, &text.user, text.association, &text.source) {
+        let command = match command {
+            Ok(command) => command,
+            Err(error) => {
+                bot.on_command_error(ctx, error).await;
+                continue;
+            }
+        };
+        let role = specs
+            .iter()
+            .find(|spec| spec.name == command.name)
+            .map_or(&Role::Anyone, |spec| &spec.role);
+        match permissions::is_allowed(ctx, role, &command).await {
+            Ok(true) => bot.on_command(ctx, command).await,
+            Ok(false) => permissions::deny(ctx, role, &command).await,
+            Err(err) => tracing::warn!(
+                user = command.user.as_str(),
+                command = command.name.as_str(),
+                %err,
+                "cannot check permission"
+            ),
+        }
+    }
+}
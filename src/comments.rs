@@ -0,0 +1,59 @@
+use crate::client::{ClientError, Comment};
+use crate::Context;
+
+/// The hidden HTML comment identifying the bot's comment for `key`.
+pub fn marker(key: &str) -> String {
+    format!("<!-- baipiao:{} -->", key)
+}
+
+impl Context {
+    /// Keeps a single comment for `key` on the event's issue or pull request,
+    /// e.g. a status report: the comment left by an earlier run is edited,
+    /// or a new one is created. Needs the bot's login, see
+    /// `Dispatcher::with_login`.
+    pub async fn upsert_comment(&self, key: &str, body: &str) -> Result<Comment, ClientError> {
+        let issue_id = self.issue.ok_or(ClientError::NoIssue)?;
+        let body = format!("{}\n{}", marker(key), body);
+        match self.find_comment(issue_id, key).await? {
+            Some(comment) if comment.body == body => Ok(comment),
+            Some(comment) => {
+                self.github
+                    .update_comment(&self.repo, comment.id, &body)
+                    .await
+            }
+            None => {
+                self.github
+                    .create_comment(&self.repo, issue_id, &body)
+                    .await
+            }
+        }
+    }
+
+    /// Deletes the comment left for `key` by `upsert_comment`, returning
+    /// whether there was one.
+    pub async fn delete_comment(&self, key: &str) -> Result<bool, ClientError> {
+        let issue_id = self.issue.ok_or(ClientError::NoIssue)?;
+        match self.find_comment(issue_id, key).await? {
+            Some(comment) => {
+                self.github.delete_comment(&self.repo, comment.id).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The bot's comment carrying the marker for `key`. Anyone can write the
+    /// marker, so the bot's login must be known to tell its comment apart.
+    async fn find_comment(
+        &self,
+        issue_id: usize,
+        key: &str,
+    ) -> Result<Option<Comment>, ClientError> {
+        let login = self.login.as_deref().ok_or(ClientError::NoLogin)?;
+        let marker = marker(key);
+        let comments = self.github.comments(&self.repo, issue_id).await?;
+        Ok(comments.into_iter().find(|comment| {
+            comment.body.starts_with(&marker) && comment.user.login.eq_ignore_ascii_case(login)
+        }))
+    }
+}
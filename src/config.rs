@@ -0,0 +1,211 @@
+use crate::client::ClientError;
+use crate::Context;
+use serde::de::DeserializeOwned;
+use std::any::Any;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::{env, fmt, fs, io};
+
+/// Where the configuration is looked for, in order.
+pub const CONFIG_PATHS: &[&str] = &[
+    ".github/baipiao.toml",
+    ".github/baipiao.yml",
+    ".github/baipiao.yaml",
+];
+
+/// A bot's settings, read from `.github/baipiao.toml` in the repository, e.g.
+///
+/// ```ignore
+/// #[derive(Default, Deserialize)]
+/// #[serde(default)]
+/// struct Settings {
+///     welcome: String,
+///     labels: Vec<String>,
+/// }
+///
+/// impl Config for Settings {}
+/// ```
+///
+/// Repositories without a configuration file get the `Default`.
+pub trait Config: DeserializeOwned + Default + Send + Sync + 'static {
+    /// Checks what deserializing cannot, e.g. that a list is not empty.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// Guesses the format from a file name; TOML unless it ends in `.yml` or
+    /// `.yaml`.
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(".yml") || path.ends_with(".yaml") {
+            Format::Yaml
+        } else {
+            Format::Toml
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io { path: String, error: io::Error },
+    Client(ClientError),
+    Parse { path: String, message: String },
+    Invalid { path: String, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => write!(f, "cannot read {}: {}", path, error),
+            ConfigError::Client(err) => write!(f, "cannot fetch configuration: {}", err),
+            ConfigError::Parse { path, message } => write!(f, "cannot parse {}: {}", path, message),
+            ConfigError::Invalid { path, message } => write!(f, "invalid {}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<ClientError> for ConfigError {
+    fn from(err: ClientError) -> Self {
+        ConfigError::Client(err)
+    }
+}
+
+/// Deserializes and validates the configuration in `text`; `path` is only
+/// used in error messages.
+pub fn parse<C: Config>(text: &str, format: Format, path: &str) -> Result<C, ConfigError> {
+    let config: C = match format {
+        Format::Toml => toml::from_str(text).map_err(|err| err.to_string()),
+        Format::Yaml => serde_yaml::from_str(text).map_err(|err| err.to_string()),
+    }
+    .map_err(|message| ConfigError::Parse {
+        path: path.to_string(),
+        message,
+    })?;
+    config.validate().map_err(|message| ConfigError::Invalid {
+        path: path.to_string(),
+        message,
+    })?;
+    Ok(config)
+}
+
+type Parser = fn(&str, Format, &str) -> Result<Arc<dyn Any + Send + Sync>, ConfigError>;
+
+fn parse_any<C: Config>(
+    text: &str,
+    format: Format,
+    path: &str,
+) -> Result<Arc<dyn Any + Send + Sync>, ConfigError> {
+    Ok(Arc::new(parse::<C>(text, format, path)?))
+}
+
+fn default_any<C: Config>() -> Arc<dyn Any + Send + Sync> {
+    Arc::new(C::default())
+}
+
+/// Loads a `Config` for each event, see `Dispatcher::with_config`.
+///
+/// When the workflow has checked out the repository, the file is read from
+/// the workspace; otherwise it is fetched at the commit the workflow runs on.
+/// Pull request events always fetch it from the base of the pull request,
+/// so that a pull request cannot change the configuration of its own run.
+#[derive(Clone)]
+pub struct ConfigLoader {
+    workspace: Option<PathBuf>,
+    paths: Vec<String>,
+    parse: Parser,
+    default: fn() -> Arc<dyn Any + Send + Sync>,
+}
+
+impl fmt::Debug for ConfigLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigLoader")
+            .field("workspace", &self.workspace)
+            .field("paths", &self.paths)
+            .finish()
+    }
+}
+
+impl ConfigLoader {
+    /// Looks in `GITHUB_WORKSPACE` and at `CONFIG_PATHS`.
+    pub fn new<C: Config>() -> Self {
+        ConfigLoader {
+            workspace: env::var_os("GITHUB_WORKSPACE").map(PathBuf::from),
+            paths: CONFIG_PATHS.iter().map(|it| it.to_string()).collect(),
+            parse: parse_any::<C>,
+            default: default_any::<C>,
+        }
+    }
+
+    /// Reads from `dir` instead of `GITHUB_WORKSPACE`, or only uses the API
+    /// with `None`.
+    pub fn workspace(mut self, dir: Option<PathBuf>) -> Self {
+        self.workspace = dir;
+        self
+    }
+
+    /// Looks for the file at `path` only.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.paths = vec![path.into()];
+        self
+    }
+
+    /// Where the configuration is looked for, in order.
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// Parses and validates `text` as if it were read from `path`.
+    pub fn validate(&self, text: &str, path: &str) -> Result<(), ConfigError> {
+        (self.parse)(text, Format::from_path(path), path).map(|_| ())
+    }
+
+    /// Loads the configuration for an event; `base` is the base commit or
+    /// branch of a pull request event.
+    pub(crate) async fn load(
+        &self,
+        ctx: &Context,
+        base: Option<&str>,
+    ) -> Result<Arc<dyn Any + Send + Sync>, ConfigError> {
+        for path in &self.paths {
+            if let Some(text) = self.read(ctx, path, base).await? {
+                return (self.parse)(&text, Format::from_path(path), path);
+            }
+        }
+        Ok((self.default)())
+    }
+
+    async fn read(
+        &self,
+        ctx: &Context,
+        path: &str,
+        base: Option<&str>,
+    ) -> Result<Option<String>, ConfigError> {
+        // without `actions/checkout` the workspace is just an empty directory
+        if let Some(workspace) = self
+            .workspace
+            .as_ref()
+            .filter(|it| base.is_none() && it.join(".git").exists())
+        {
+            return match fs::read_to_string(workspace.join(path)) {
+                Ok(text) => Ok(Some(text)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(error) => Err(ConfigError::Io {
+                    path: path.to_string(),
+                    error,
+                }),
+            };
+        }
+        let git_ref = base.or(ctx.running_info.sha.as_deref());
+        let file = ctx.github.file(&ctx.repo, path, git_ref).await?;
+        Ok(file.map(|file| file.content))
+    }
+}
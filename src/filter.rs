@@ -0,0 +1,267 @@
+use crate::commands::{Command, CommandError, CommandSpec};
+use crate::layers::{Layer, Next, Outcome};
+use crate::scopes::Manifest;
+use crate::subscriptions::Subscriptions;
+use crate::{
+    AuthorAssociation, Bot, Context, Event, IssueUpdatedEvent, PullRequestUpdatedEvent, UpdatedPart,
+};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use regex::Regex;
+use std::ops::{BitAnd, BitOr, Not};
+
+/// Declares which events a handler runs for, e.g.
+///
+/// ```ignore
+/// Filter::event("pull_request_created") & Filter::base_branch("main")
+///     & !Filter::title("^WIP")?
+/// ```
+///
+/// A filter can be added to a `Dispatcher` as a layer, or attached to a
+/// single bot with `Bot::when`, for example before adding it to a `Router`.
+///
+/// Filters only look at what the event carries, so most of them apply to
+/// some events only and never match the others: `base_branch("main")`
+/// matches new pull requests targeting `main`, but no comment on them.
+/// Combine them with `Filter::event` to make that explicit.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// The event's `Event::name`, e.g. `comment_created`.
+    Event(String),
+    /// The issue or pull request carries this label. Only new and reopened
+    /// issues and new pull requests carry their labels.
+    Label(String),
+    /// The pull request targets this branch; new pull requests only.
+    BaseBranch(String),
+    /// The pull request comes from this branch; new pull requests only.
+    HeadBranch(String),
+    /// The event was triggered by this login.
+    Author(String),
+    /// The author of the new issue, pull request or comment is associated
+    /// with the repository in one of these ways.
+    Association(Vec<AuthorAssociation>),
+    /// The title of a new or reopened issue, a new pull request, or the new
+    /// title of an edited one matches.
+    Title(Regex),
+    /// The new or edited pull request touches a file matching this glob;
+    /// looked up with the API.
+    Path(glob::Pattern),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn event(name: impl Into<String>) -> Self {
+        Filter::Event(name.into())
+    }
+
+    pub fn label(name: impl Into<String>) -> Self {
+        Filter::Label(name.into())
+    }
+
+    pub fn base_branch(name: impl Into<String>) -> Self {
+        Filter::BaseBranch(name.into())
+    }
+
+    pub fn head_branch(name: impl Into<String>) -> Self {
+        Filter::HeadBranch(name.into())
+    }
+
+    pub fn author(login: impl Into<String>) -> Self {
+        Filter::Author(login.into())
+    }
+
+    /// Owners, organization members and collaborators.
+    pub fn collaborators() -> Self {
+        Filter::Association(vec![
+            AuthorAssociation::Owner,
+            AuthorAssociation::Member,
+            AuthorAssociation::Collaborator,
+        ])
+    }
+
+    pub fn title(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Filter::Title)
+    }
+
+    pub fn path(pattern: &str) -> Result<Self, glob::PatternError> {
+        glob::Pattern::new(pattern).map(Filter::Path)
+    }
+
+    pub fn and(self, other: Filter) -> Self {
+        Filter::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Filter) -> Self {
+        Filter::Or(Box::new(self), Box::new(other))
+    }
+
+    pub fn matches<'a>(&'a self, ctx: &'a Context, event: &'a Event) -> BoxFuture<'a, bool> {
+        async move {
+            match self {
+                Filter::Event(name) => event.name() == name,
+                Filter::Label(name) => labels(event).iter().any(|label| label == name),
+                Filter::BaseBranch(name) => match event {
+                    Event::PullRequestCreated(event) => &event.to_ref == name,
+                    _ => false,
+                },
+                Filter::HeadBranch(name) => match event {
+                    Event::PullRequestCreated(event) => &event.from_ref == name,
+                    _ => false,
+                },
+                Filter::Author(login) => ctx.sender.login.eq_ignore_ascii_case(login),
+                Filter::Association(associations) => {
+                    association(event).is_some_and(|it| associations.contains(&it))
+                }
+                Filter::Title(regex) => title(event).is_some_and(|it| regex.is_match(it)),
+                Filter::Path(pattern) => {
+                    let id = match event {
+                        Event::PullRequestCreated(event) => event.id,
+                        Event::PullRequestUpdated(event) => event.id,
+                        _ => return false,
+                    };
+                    match ctx.github.pull_request_files(&ctx.repo, id).await {
+                        Ok(files) => files.iter().any(|file| pattern.matches(file)),
+                        Err(err) => {
+                            tracing::warn!(pull_request = id, %err, "cannot list files");
+                            false
+                        }
+                    }
+                }
+                Filter::And(left, right) => {
+                    left.matches(ctx, event).await && right.matches(ctx, event).await
+                }
+                Filter::Or(left, right) => {
+                    left.matches(ctx, event).await || right.matches(ctx, event).await
+                }
+                Filter::Not(inner) => !inner.matches(ctx, event).await,
+            }
+        }
+        .boxed()
+    }
+}
+
+fn labels(event: &Event) -> &[String] {
+    match event {
+        Event::IssueCreated(event) => &event.labels,
+        Event::IssueReopened(event) => &event.labels,
+        Event::PullRequestCreated(event) => &event.labels,
+        _ => &[],
+    }
+}
+
+fn title(event: &Event) -> Option<&str> {
+    match event {
+        Event::IssueCreated(event) => Some(&event.title),
+        Event::IssueReopened(event) => Some(&event.title),
+        Event::PullRequestCreated(event) => Some(&event.title),
+        Event::IssueUpdated(IssueUpdatedEvent {
+            updated_part: UpdatedPart::Title { to, .. },
+            ..
+        })
+        | Event::PullRequestUpdated(PullRequestUpdatedEvent {
+            updated_part: UpdatedPart::Title { to, .. },
+            ..
+        }) => Some(to),
+        _ => None,
+    }
+}
+
+fn association(event: &Event) -> Option<AuthorAssociation> {
+    match event {
+        Event::IssueCreated(event) => Some(event.author_association),
+        Event::PullRequestCreated(event) => Some(event.author_association),
+        Event::CommentCreated(event) => Some(event.author_association),
+        _ => None,
+    }
+}
+
+impl BitAnd for Filter {
+    type Output = Filter;
+
+    fn bitand(self, other: Filter) -> Filter {
+        self.and(other)
+    }
+}
+
+impl BitOr for Filter {
+    type Output = Filter;
+
+    fn bitor(self, other: Filter) -> Filter {
+        self.or(other)
+    }
+}
+
+impl Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter::Not(Box::new(self))
+    }
+}
+
+#[async_trait]
+impl Layer for Filter {
+    async fn call(&self, ctx: &mut Context, event: Event, next: Next<'_>) -> Outcome {
+        if self.matches(ctx, &event).await {
+            next.run(ctx, event).await
+        } else {
+            Outcome::Skipped(format!("{} does not match the filter", event.name()))
+        }
+    }
+}
+
+/// A bot that only sees events matching a filter, made by `Bot::when`.
+pub struct Filtered<B> {
+    bot: B,
+    filter: Filter,
+}
+
+impl<B: Bot> Filtered<B> {
+    pub fn new(bot: B, filter: Filter) -> Self {
+        Filtered { bot, filter }
+    }
+}
+
+#[async_trait]
+impl<B: Bot> Bot for Filtered<B> {
+    async fn handle(&self, ctx: &Context, event: Event) {
+        if self.filter.matches(ctx, &event).await {
+            self.bot.handle(ctx, event).await
+        }
+    }
+
+    async fn handle_event(&self, ctx: &Context, event: Event) -> Outcome {
+        if self.filter.matches(ctx, &event).await {
+            self.bot.handle_event(ctx, event).await
+        } else {
+            Outcome::Skipped(format!("{} does not match the filter", event.name()))
+        }
+    }
+
+    fn receive_bot_events(&self) -> bool {
+        self.bot.receive_bot_events()
+    }
+
+    fn commands(&self) -> Vec<CommandSpec> {
+        self.bot.commands()
+    }
+
+    async fn on_command(&self, ctx: &Context, command: Command) {
+        self.bot.on_command(ctx, command).await
+    }
+
+    async fn on_command_error(&self, ctx: &Context, error: CommandError) {
+        self.bot.on_command_error(ctx, error).await
+    }
+
+    fn subscriptions(&self) -> Subscriptions {
+        self.bot.subscriptions()
+    }
+
+    fn permissions(&self) -> Manifest {
+        self.bot.permissions()
+    }
+}
@@ -0,0 +1,184 @@
+use crate::commands::{Command, CommandSpec};
+use crate::subscriptions::Subscriptions;
+use crate::{
+    Bot, CommentCreatedEvent, CommentUpdatedEvent, Context, Dispatcher, IssueCreatedEvent,
+    IssueReopenedEvent, IssueUpdatedEvent, PullRequestCreatedEvent, PullRequestUpdatedEvent,
+};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::future::Future;
+
+type Handler<E> = Box<dyn Fn(Context, E) -> BoxFuture<'static, ()> + Send + Sync>;
+
+fn boxed<E, F, Fut>(f: F) -> Option<Handler<E>>
+where
+    F: Fn(Context, E) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    Some(Box::new(move |ctx, event| Box::pin(f(ctx, event))))
+}
+
+/// A bot made of closures, built with `Dispatcher::builder()`.
+#[derive(Default)]
+pub struct FnBot {
+    issue_created: Option<Handler<IssueCreatedEvent>>,
+    issue_updated: Option<Handler<IssueUpdatedEvent>>,
+    issue_closed: Option<Handler<usize>>,
+    issue_reopened: Option<Handler<IssueReopenedEvent>>,
+    pull_request_created: Option<Handler<PullRequestCreatedEvent>>,
+    pull_request_updated: Option<Handler<PullRequestUpdatedEvent>>,
+    pull_request_closed: Option<Handler<usize>>,
+    comment_created: Option<Handler<CommentCreatedEvent>>,
+    comment_updated: Option<Handler<CommentUpdatedEvent>>,
+    comment_deleted: Option<Handler<usize>>,
+    commands: Vec<CommandSpec>,
+    command: Option<Handler<Command>>,
+}
+
+/// Registers one closure per hook, e.g.
+///
+/// ```ignore
+/// Dispatcher::builder()
+///     .on_issue_created(|ctx, event| async move {
+///         ctx.github.create_comment(&ctx.repo, event.id, "Thanks!").await.unwrap();
+///     })
+///     .build()
+/// ```
+#[derive(Default)]
+pub struct FnBotBuilder {
+    bot: FnBot,
+}
+
+macro_rules! on {
+    ($($method:ident => $field:ident: $event:ty,)*) => {$(
+        pub fn $method<F, Fut>(mut self, f: F) -> Self
+        where
+            F: Fn(Context, $event) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static,
+        {
+            self.bot.$field = boxed(f);
+            self
+        }
+    )*};
+}
+
+impl FnBotBuilder {
+    on! {
+        on_issue_created => issue_created: IssueCreatedEvent,
+        on_issue_updated => issue_updated: IssueUpdatedEvent,
+        on_issue_closed => issue_closed: usize,
+        on_issue_reopened => issue_reopened: IssueReopenedEvent,
+        on_pull_request_created => pull_request_created: PullRequestCreatedEvent,
+        on_pull_request_updated => pull_request_updated: PullRequestUpdatedEvent,
+        on_pull_request_closed => pull_request_closed: usize,
+        on_comment_created => comment_created: CommentCreatedEvent,
+        on_comment_updated => comment_updated: CommentUpdatedEvent,
+        on_comment_deleted => comment_deleted: usize,
+    }
+
+    /// Handles the commands described by `specs`.
+    pub fn on_command<F, Fut>(mut self, specs: Vec<CommandSpec>, f: F) -> Self
+    where
+        F: Fn(Context, Command) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.bot.commands = specs;
+        self.bot.command = boxed(f);
+        self
+    }
+
+    pub fn build(self) -> Dispatcher<FnBot> {
+        Dispatcher::new(self.bot)
+    }
+}
+
+impl Dispatcher<FnBot> {
+    pub fn builder() -> FnBotBuilder {
+        FnBotBuilder::default()
+    }
+}
+
+async fn call<E>(handler: &Option<Handler<E>>, ctx: &Context, event: E) {
+    if let Some(handler) = handler {
+        handler(ctx.clone(), event).await
+    }
+}
+
+#[async_trait]
+impl Bot for FnBot {
+    async fn on_issue_created(&self, ctx: &Context, event: IssueCreatedEvent) {
+        call(&self.issue_created, ctx, event).await
+    }
+
+    async fn on_issue_updated(&self, ctx: &Context, event: IssueUpdatedEvent) {
+        call(&self.issue_updated, ctx, event).await
+    }
+
+    async fn on_issue_closed(&self, ctx: &Context, issue_id: usize) {
+        call(&self.issue_closed, ctx, issue_id).await
+    }
+
+    async fn on_issue_reopened(&self, ctx: &Context, event: IssueReopenedEvent) {
+        call(&self.issue_reopened, ctx, event).await
+    }
+
+    async fn on_pull_request_created(&self, ctx: &Context, event: PullRequestCreatedEvent) {
+        call(&self.pull_request_created, ctx, event).await
+    }
+
+    async fn on_pull_request_updated(&self, ctx: &Context, event: PullRequestUpdatedEvent) {
+        call(&self.pull_request_updated, ctx, event).await
+    }
+
+    async fn on_pull_request_closed(&self, ctx: &Context, pull_request_id: usize) {
+        call(&self.pull_request_closed, ctx, pull_request_id).await
+    }
+
+    async fn on_comment_created(&self, ctx: &Context, event: CommentCreatedEvent) {
+        call(&self.comment_created, ctx, event).await
+    }
+
+    async fn on_comment_updated(&self, ctx: &Context, event: CommentUpdatedEvent) {
+        call(&self.comment_updated, ctx, event).await
+    }
+
+    async fn on_comment_deleted(&self, ctx: &Context, comment_id: usize) {
+        call(&self.comment_deleted, ctx, comment_id).await
+    }
+
+    fn commands(&self) -> Vec<CommandSpec> {
+        self.commands.clone()
+    }
+
+    async fn on_command(&self, ctx: &Context, command: Command) {
+        call(&self.command, ctx, command).await
+    }
+
+    /// The events with a closure, and where commands come from if there
+    /// is a command closure.
+    fn subscriptions(&self) -> Subscriptions {
+        let handlers = [
+            ("issue_created", self.issue_created.is_some()),
+            ("issue_updated", self.issue_updated.is_some()),
+            ("issue_closed", self.issue_closed.is_some()),
+            ("issue_reopened", self.issue_reopened.is_some()),
+            ("pull_request_created", self.pull_request_created.is_some()),
+            ("pull_request_updated", self.pull_request_updated.is_some()),
+            ("pull_request_closed", self.pull_request_closed.is_some()),
+            ("comment_created", self.comment_created.is_some()),
+            ("comment_updated", self.comment_updated.is_some()),
+            ("comment_deleted", self.comment_deleted.is_some()),
+        ];
+        let subscriptions = handlers
+            .iter()
+            .filter(|(_, set)| *set)
+            .fold(Subscriptions::none(), |subscriptions, (name, _)| {
+                subscriptions.event(name)
+            });
+        if self.command.is_some() {
+            subscriptions.commands()
+        } else {
+            subscriptions
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{Bot, Context, Event};
+use async_trait::async_trait;
+use futures::FutureExt;
+use std::any::Any;
+use std::fmt;
+use std::panic::AssertUnwindSafe;
+use std::time::Instant;
+
+/// What became of a dispatched event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Handled,
+    /// A filter decided the bot should not see the event.
+    Skipped(String),
+    /// The handler panicked, caught by `CatchPanic`, or one of a `Router`'s
+    /// handlers did.
+    Failed(String),
+}
+
+impl Outcome {
+    /// `handled`, `skipped` or `failed`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Handled => "handled",
+            Outcome::Skipped(_) => "skipped",
+            Outcome::Failed(_) => "failed",
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Handled => write!(f, "handled"),
+            Outcome::Skipped(reason) => write!(f, "skipped, {}", reason),
+            Outcome::Failed(message) => write!(f, "failed, {}", message),
+        }
+    }
+}
+
+/// Cross-cutting behavior wrapped around every dispatch.
+///
+/// A layer sees the parsed event and decides whether to pass it on with
+/// `next.run(ctx, event)`. It may change the context before doing so, return
+/// early to skip the bot, or look at the outcome afterwards.
+#[async_trait]
+pub trait Layer: Send + Sync {
+    async fn call(&self, ctx: &mut Context, event: Event, next: Next<'_>) -> Outcome;
+}
+
+/// The rest of the layer stack, ending with the bot.
+pub struct Next<'a> {
+    layers: &'a [Box<dyn Layer>],
+    bot: &'a dyn Bot,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(layers: &'a [Box<dyn Layer>], bot: &'a dyn Bot) -> Self {
+        Next { layers, bot }
+    }
+
+    /// Can be called more than once, e.g. to retry.
+    pub async fn run(&self, ctx: &mut Context, event: Event) -> Outcome {
+        match self.layers.split_first() {
+            Some((layer, rest)) => layer.call(ctx, event, Next::new(rest, self.bot)).await,
+            None => self.bot.handle_event(ctx, event).await,
+        }
+    }
+}
+
+/// Logs each event with its outcome and how long it took, as `tracing`
+/// events inside the event's span.
+pub struct Logging;
+
+#[async_trait]
+impl Layer for Logging {
+    async fn call(&self, ctx: &mut Context, event: Event, next: Next<'_>) -> Outcome {
+        let name = event.name();
+        let start = Instant::now();
+        tracing::info!(event = name, "dispatching");
+        let outcome = next.run(ctx, event).await;
+        tracing::info!(event = name, elapsed = ?start.elapsed(), %outcome, "finished");
+        outcome
+    }
+}
+
+/// Turns a panicking handler into `Outcome::Failed`.
+pub struct CatchPanic;
+
+#[async_trait]
+impl Layer for CatchPanic {
+    async fn call(&self, ctx: &mut Context, event: Event, next: Next<'_>) -> Outcome {
+        match AssertUnwindSafe(next.run(ctx, event)).catch_unwind().await {
+            Ok(outcome) => outcome,
+            Err(panic) => Outcome::Failed(panic_message(panic.as_ref())),
+        }
+    }
+}
+
+pub(crate) fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|it| it.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "handler panicked".to_string())
+}
+
+/// Runs the rest of the stack again while it fails, up to `attempts` times
+/// in total. Needs `CatchPanic` below it to see panics as failures.
+///
+/// Every attempt runs the handler from the start, repeating whatever it
+/// wrote before failing, e.g. posting the same comment twice, so only use it
+/// for handlers that are safe to run again. Calls GitHub turns away for a
+/// rate limit are already retried by `GitHub` itself when they are
+/// idempotent, see `GitHub::with_rate_limits`.
+pub struct Retry {
+    pub attempts: usize,
+}
+
+#[async_trait]
+impl Layer for Retry {
+    async fn call(&self, ctx: &mut Context, event: Event, next: Next<'_>) -> Outcome {
+        let mut outcome = next.run(ctx, event.clone()).await;
+        for _ in 1..self.attempts {
+            if !matches!(outcome, Outcome::Failed(_)) {
+                break;
+            }
+            outcome = next.run(ctx, event.clone()).await;
+        }
+        outcome
+    }
+}
@@ -1,59 +1,155 @@
 use async_trait::async_trait;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::Instrument;
 
-#[derive(Debug)]
+pub mod action_log;
+pub mod auth;
+pub mod bots;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod client;
+pub mod commands;
+pub mod comments;
+pub mod config;
+pub mod filter;
+pub mod fn_bot;
+pub mod layers;
+pub mod logging;
+pub mod metrics;
+pub mod payload;
+pub mod permissions;
+pub mod rate_limit;
+pub mod router;
+pub mod scopes;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod state;
+pub mod subscriptions;
+pub mod templates;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod workflow;
+
+use action_log::{ActionLog, Report};
+use bots::BotPolicy;
+pub use client::{GitHub, GitHubApi};
+use commands::{Command, CommandError, CommandSource, CommandSpec, CommandText};
+use config::{Config, ConfigLoader};
+use filter::{Filter, Filtered};
+use layers::{Layer, Next, Outcome};
+use logging::Trace;
+use metrics::Metrics;
+use payload::ParseError;
+use scopes::Manifest;
+use subscriptions::Subscriptions;
+
+#[derive(Debug, Clone)]
 pub struct Repository {
     pub owner: String,
     pub name: String,
 }
 
-#[derive(Debug)]
+/// How the author of an issue, pull request or comment relates to the
+/// repository, as reported in the payload's `author_association`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthorAssociation {
+    Owner,
+    Member,
+    Collaborator,
+    Contributor,
+    FirstTimeContributor,
+    FirstTimer,
+    Mannequin,
+    None,
+}
+
+impl AuthorAssociation {
+    /// The name used in payloads, e.g. `FIRST_TIME_CONTRIBUTOR`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthorAssociation::Owner => "OWNER",
+            AuthorAssociation::Member => "MEMBER",
+            AuthorAssociation::Collaborator => "COLLABORATOR",
+            AuthorAssociation::Contributor => "CONTRIBUTOR",
+            AuthorAssociation::FirstTimeContributor => "FIRST_TIME_CONTRIBUTOR",
+            AuthorAssociation::FirstTimer => "FIRST_TIMER",
+            AuthorAssociation::Mannequin => "MANNEQUIN",
+            AuthorAssociation::None => "NONE",
+        }
+    }
+}
+
+impl From<&str> for AuthorAssociation {
+    fn from(value: &str) -> Self {
+        match value {
+            "OWNER" => AuthorAssociation::Owner,
+            "MEMBER" => AuthorAssociation::Member,
+            "COLLABORATOR" => AuthorAssociation::Collaborator,
+            "CONTRIBUTOR" => AuthorAssociation::Contributor,
+            "FIRST_TIME_CONTRIBUTOR" => AuthorAssociation::FirstTimeContributor,
+            "FIRST_TIMER" => AuthorAssociation::FirstTimer,
+            "MANNEQUIN" => AuthorAssociation::Mannequin,
+            _ => AuthorAssociation::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct IssueCreatedEvent {
     pub id: usize,
     pub title: String,
     pub body: String,
     pub user: String,
+    pub author_association: AuthorAssociation,
+    pub labels: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum UpdatedPart {
     Title { from: String, to: String },
     Body { from: String, to: String },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IssueUpdatedEvent {
     pub id: usize,
     pub updated_part: UpdatedPart,
     pub user: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IssueReopenedEvent {
     pub id: usize,
     pub title: String,
     pub body: String,
     pub user: String,
+    pub labels: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PullRequestCreatedEvent {
     pub id: usize,
     pub title: String,
     pub body: String,
     pub user: String,
+    pub author_association: AuthorAssociation,
+    pub labels: Vec<String>,
     pub from_repo: Repository,
     pub from_ref: String,
     pub to_ref: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PullRequestUpdatedEvent {
     pub id: usize,
     pub updated_part: UpdatedPart,
     pub user: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum CommentTarget {
     Issue(usize),
     PullRequest(usize),
@@ -68,15 +164,16 @@ impl CommentTarget {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CommentCreatedEvent {
     pub id: usize,
     pub user: String,
+    pub author_association: AuthorAssociation,
     pub target: CommentTarget,
     pub body: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CommentUpdatedEvent {
     pub id: usize,
     pub user: String,
@@ -85,365 +182,630 @@ pub struct CommentUpdatedEvent {
     pub to: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RunningInfo {
     pub run_id: usize,
     pub run_number: usize,
+    pub installation_id: Option<u64>,
+    /// The commit the workflow runs on.
+    pub sha: Option<String>,
 }
 
-#[async_trait]
-pub trait Bot: Send + Sync {
-    async fn on_issue_created(
-        &self,
-        _repo: Repository,
-        _running_info: RunningInfo,
-        _event: IssueCreatedEvent,
-    ) {
+#[derive(Debug, Clone)]
+pub enum Event {
+    IssueCreated(IssueCreatedEvent),
+    IssueUpdated(IssueUpdatedEvent),
+    IssueClosed(usize),
+    IssueReopened(IssueReopenedEvent),
+    PullRequestCreated(PullRequestCreatedEvent),
+    PullRequestUpdated(PullRequestUpdatedEvent),
+    PullRequestClosed(usize),
+    CommentCreated(CommentCreatedEvent),
+    CommentUpdated(CommentUpdatedEvent),
+    CommentDeleted(usize),
+}
+
+impl Event {
+    /// Parses the `github` context of an Actions run, as the workflow passes
+    /// it in with `toJson(github)`.
+    pub fn parse(payload: &serde_json::Value) -> Result<Event, ParseError> {
+        payload::parse(payload)
     }
 
-    async fn on_issue_updated(
-        &self,
-        _repo: Repository,
-        _running_info: RunningInfo,
-        _event: IssueUpdatedEvent,
-    ) {
+    /// Like `parse`, but panics on unsupported or malformed payloads.
+    pub fn from_payload(payload: &serde_json::Value) -> Event {
+        Self::parse(payload).unwrap_or_else(|err| panic!("{}", err))
     }
 
-    async fn on_issue_closed(
-        &self,
-        _repo: Repository,
-        _running_info: RunningInfo,
-        _issue_id: usize,
-    ) {
+    /// The issue or pull request this event is about.
+    pub fn issue_id(&self) -> Option<usize> {
+        match self {
+            Event::IssueCreated(event) => Some(event.id),
+            Event::IssueUpdated(event) => Some(event.id),
+            Event::IssueClosed(id) => Some(*id),
+            Event::IssueReopened(event) => Some(event.id),
+            Event::PullRequestCreated(event) => Some(event.id),
+            Event::PullRequestUpdated(event) => Some(event.id),
+            Event::PullRequestClosed(id) => Some(*id),
+            Event::CommentCreated(event) => Some(event.target.id()),
+            Event::CommentUpdated(event) => Some(event.target.id()),
+            Event::CommentDeleted(_) => None,
+        }
     }
 
-    async fn on_issue_reopened(
-        &self,
-        _repo: Repository,
-        _running_info: RunningInfo,
-        _event: IssueReopenedEvent,
-    ) {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::IssueCreated(_) => "issue_created",
+            Event::IssueUpdated(_) => "issue_updated",
+            Event::IssueClosed(_) => "issue_closed",
+            Event::IssueReopened(_) => "issue_reopened",
+            Event::PullRequestCreated(_) => "pull_request_created",
+            Event::PullRequestUpdated(_) => "pull_request_updated",
+            Event::PullRequestClosed(_) => "pull_request_closed",
+            Event::CommentCreated(_) => "comment_created",
+            Event::CommentUpdated(_) => "comment_updated",
+            Event::CommentDeleted(_) => "comment_deleted",
+        }
     }
 
-    async fn on_pull_request_created(
-        &self,
-        _repo: Repository,
-        _running_info: RunningInfo,
-        _event: PullRequestCreatedEvent,
-    ) {
+    /// The newly written text of this event, in which commands are looked for.
+    pub fn command_text(&self) -> Option<CommandText> {
+        match self {
+            Event::IssueCreated(event) => Some(CommandText {
+                text: event.body.clone(),
+                user: event.user.clone(),
+                association: event.author_association,
+                source: CommandSource::IssueBody(event.id),
+            }),
+            Event::PullRequestCreated(event) => Some(CommandText {
+                text: event.body.clone(),
+                user: event.user.clone(),
+                association: event.author_association,
+                source: CommandSource::PullRequestBody(event.id),
+            }),
+            Event::CommentCreated(event) => Some(CommandText {
+                text: event.body.clone(),
+                user: event.user.clone(),
+                association: event.author_association,
+                source: CommandSource::Comment {
+                    id: event.id,
+                    target: event.target,
+                },
+            }),
+            _ => None,
+        }
     }
+}
 
-    async fn on_pull_request_updated(
-        &self,
-        _repo: Repository,
-        _running_info: RunningInfo,
-        _event: PullRequestUpdatedEvent,
-    ) {
+/// The account whose action triggered the event.
+#[derive(Debug, Clone)]
+pub struct Sender {
+    pub login: String,
+    /// A GitHub App or another `[bot]` account.
+    pub is_bot: bool,
+}
+
+/// Everything a handler needs besides the event itself.
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub repo: Repository,
+    pub running_info: RunningInfo,
+    pub sender: Sender,
+    /// The bot's own login, if known.
+    pub login: Option<String>,
+    /// The sender is a bot account filtered out by the dispatcher's bot
+    /// policy; only handlers that `receive_bot_events` see the event.
+    pub ignored_sender: bool,
+    /// The issue or pull request the event is about, if any.
+    pub issue: Option<usize>,
+    /// Authenticated for the installation that triggered the event.
+    pub github: Arc<dyn GitHubApi>,
+    /// The repository's configuration, loaded by `Dispatcher::with_config`.
+    pub config: Option<Arc<dyn Any + Send + Sync>>,
+    /// The span of the event, see `Context::record`.
+    pub trace: Trace,
+    /// Where the dispatcher records, see `Dispatcher::with_metrics`.
+    pub metrics: Metrics,
+}
+
+impl Context {
+    /// The repository's configuration, if the dispatcher loads a `C`.
+    pub fn config<C: Config>(&self) -> Option<&C> {
+        self.config.as_ref()?.downcast_ref()
     }
 
-    async fn on_pull_request_closed(
-        &self,
-        _repo: Repository,
-        _running_info: RunningInfo,
-        _pull_request_id: usize,
-    ) {
+    /// Adds `key=value` to the fields of the event's span, e.g. the label a
+    /// handler picked, so that it shows on every log line of the event.
+    pub fn record(&self, key: &str, value: impl std::fmt::Display) {
+        self.trace.record(key, value.to_string());
     }
+}
 
-    async fn on_comment_created(
-        &self,
-        _repo: Repository,
-        _running_info: RunningInfo,
-        _event: CommentCreatedEvent,
-    ) {
+/// Reacts to repository events. Every hook has a default that does nothing.
+///
+/// The trait is object safe, so bots can be picked at runtime and held as
+/// `Box<dyn Bot>` or `Arc<dyn Bot>`.
+#[async_trait]
+pub trait Bot: Send + Sync {
+    async fn on_issue_created(&self, _ctx: &Context, _event: IssueCreatedEvent) {}
+
+    async fn on_issue_updated(&self, _ctx: &Context, _event: IssueUpdatedEvent) {}
+
+    async fn on_issue_closed(&self, _ctx: &Context, _issue_id: usize) {}
+
+    async fn on_issue_reopened(&self, _ctx: &Context, _event: IssueReopenedEvent) {}
+
+    async fn on_pull_request_created(&self, _ctx: &Context, _event: PullRequestCreatedEvent) {}
+
+    async fn on_pull_request_updated(&self, _ctx: &Context, _event: PullRequestUpdatedEvent) {}
+
+    async fn on_pull_request_closed(&self, _ctx: &Context, _pull_request_id: usize) {}
+
+    async fn on_comment_created(&self, _ctx: &Context, _event: CommentCreatedEvent) {}
+
+    async fn on_comment_updated(&self, _ctx: &Context, _event: CommentUpdatedEvent) {}
+
+    async fn on_comment_deleted(&self, _ctx: &Context, _comment_id: usize) {}
+
+    /// Commands this bot understands. They are looked for in new comments
+    /// and in the bodies of new issues and pull requests.
+    fn commands(&self) -> Vec<CommandSpec> {
+        Vec::new()
     }
 
-    async fn on_comment_updated(
-        &self,
-        _repo: Repository,
-        _running_info: RunningInfo,
-        _event: CommentUpdatedEvent,
-    ) {
+    async fn on_command(&self, _ctx: &Context, _command: Command) {}
+
+    async fn on_command_error(&self, _ctx: &Context, _error: CommandError) {}
+
+    /// Routes `event` to the matching `on_*` hook, then runs the commands
+    /// written in it.
+    async fn handle(&self, ctx: &Context, event: Event) {
+        let command_text = event.command_text();
+        match event {
+            Event::IssueCreated(event) => self.on_issue_created(ctx, event).await,
+            Event::IssueUpdated(event) => self.on_issue_updated(ctx, event).await,
+            Event::IssueClosed(id) => self.on_issue_closed(ctx, id).await,
+            Event::IssueReopened(event) => self.on_issue_reopened(ctx, event).await,
+            Event::PullRequestCreated(event) => self.on_pull_request_created(ctx, event).await,
+            Event::PullRequestUpdated(event) => self.on_pull_request_updated(ctx, event).await,
+            Event::PullRequestClosed(id) => self.on_pull_request_closed(ctx, id).await,
+            Event::CommentCreated(event) => self.on_comment_created(ctx, event).await,
+            Event::CommentUpdated(event) => self.on_comment_updated(ctx, event).await,
+            Event::CommentDeleted(id) => self.on_comment_deleted(ctx, id).await,
+        }
+        if let Some(command_text) = command_text {
+            commands::dispatch(self, ctx, command_text).await;
+        }
     }
 
-    async fn on_comment_deleted(
-        &self,
-        _repo: Repository,
-        _running_info: RunningInfo,
-        _comment_id: usize,
-    ) {
+    /// Handles `event` and tells what became of it, which is what the
+    /// dispatcher reports. `Handled` once `handle` returns unless overridden,
+    /// e.g. by `Router` to report its failed handlers.
+    async fn handle_event(&self, ctx: &Context, event: Event) -> Outcome {
+        self.handle(ctx, event).await;
+        Outcome::Handled
+    }
+
+    /// Only runs this bot for events matching `filter`.
+    fn when(self, filter: Filter) -> Filtered<Self>
+    where
+        Self: Sized,
+    {
+        Filtered::new(self, filter)
+    }
+
+    /// Opts out of dropping events sent by bot accounts, including this bot
+    /// itself, so that this handler sees every event.
+    fn receive_bot_events(&self) -> bool {
+        false
+    }
+
+    /// The events this bot handles, for generating its workflow; other
+    /// events are skipped. All of them unless overridden.
+    fn subscriptions(&self) -> Subscriptions {
+        Subscriptions::all()
+    }
+
+    /// The token permissions this bot needs, checked by
+    /// `Dispatcher::with_permission_check`. Unless overridden, a guess from
+    /// `subscriptions`.
+    fn permissions(&self) -> Manifest {
+        Manifest::for_subscriptions(&self.subscriptions())
     }
 }
 
+macro_rules! forward_bot {
+    ($($wrapper:ident),*) => {$(
+        /// Forwards everything, so that `Dispatcher` and `Router` accept
+        /// boxed or shared bots chosen at runtime.
+        #[async_trait]
+        impl<B: Bot + ?Sized> Bot for $wrapper<B> {
+            async fn on_issue_created(&self, ctx: &Context, event: IssueCreatedEvent) {
+                (**self).on_issue_created(ctx, event).await
+            }
+
+            async fn on_issue_updated(&self, ctx: &Context, event: IssueUpdatedEvent) {
+                (**self).on_issue_updated(ctx, event).await
+            }
+
+            async fn on_issue_closed(&self, ctx: &Context, issue_id: usize) {
+                (**self).on_issue_closed(ctx, issue_id).await
+            }
+
+            async fn on_issue_reopened(&self, ctx: &Context, event: IssueReopenedEvent) {
+                (**self).on_issue_reopened(ctx, event).await
+            }
+
+            async fn on_pull_request_created(&self, ctx: &Context, event: PullRequestCreatedEvent) {
+                (**self).on_pull_request_created(ctx, event).await
+            }
+
+            async fn on_pull_request_updated(&self, ctx: &Context, event: PullRequestUpdatedEvent) {
+                (**self).on_pull_request_updated(ctx, event).await
+            }
+
+            async fn on_pull_request_closed(&self, ctx: &Context, pull_request_id: usize) {
+                (**self).on_pull_request_closed(ctx, pull_request_id).await
+            }
+
+            async fn on_comment_created(&self, ctx: &Context, event: CommentCreatedEvent) {
+                (**self).on_comment_created(ctx, event).await
+            }
+
+            async fn on_comment_updated(&self, ctx: &Context, event: CommentUpdatedEvent) {
+                (**self).on_comment_updated(ctx, event).await
+            }
+
+            async fn on_comment_deleted(&self, ctx: &Context, comment_id: usize) {
+                (**self).on_comment_deleted(ctx, comment_id).await
+            }
+
+            fn commands(&self) -> Vec<CommandSpec> {
+                (**self).commands()
+            }
+
+            async fn on_command(&self, ctx: &Context, command: Command) {
+                (**self).on_command(ctx, command).await
+            }
+
+            async fn on_command_error(&self, ctx: &Context, error: CommandError) {
+                (**self).on_command_error(ctx, error).await
+            }
+
+            async fn handle(&self, ctx: &Context, event: Event) {
+                (**self).handle(ctx, event).await
+            }
+
+            async fn handle_event(&self, ctx: &Context, event: Event) -> Outcome {
+                (**self).handle_event(ctx, event).await
+            }
+
+            fn receive_bot_events(&self) -> bool {
+                (**self).receive_bot_events()
+            }
+
+            fn subscriptions(&self) -> Subscriptions {
+                (**self).subscriptions()
+            }
+
+            fn permissions(&self) -> Manifest {
+                (**self).permissions()
+            }
+        }
+    )*};
+}
+
+forward_bot!(Box, Arc);
+
 pub struct Dispatcher<T: Bot> {
     core: T,
+    github: GitHub,
+    api: Option<Arc<dyn GitHubApi>>,
+    login: Option<String>,
+    bot_policy: BotPolicy,
+    layers: Vec<Box<dyn Layer>>,
+    config: Option<ConfigLoader>,
+    step_summary: bool,
+    dry_run: bool,
+    permission_check: bool,
+    /// What the token of each installation was granted, once asked.
+    granted: Mutex<HashMap<Option<u64>, Option<Manifest>>>,
+    metrics: Metrics,
 }
 
 impl<T: Bot> Dispatcher<T> {
     pub fn new(core: T) -> Self {
-        Dispatcher { core }
+        Dispatcher {
+            core,
+            github: GitHub::new(auth::Auth::None),
+            api: None,
+            login: None,
+            bot_policy: BotPolicy::default(),
+            layers: Vec::new(),
+            config: None,
+            step_summary: true,
+            dry_run: false,
+            permission_check: false,
+            granted: Mutex::new(HashMap::new()),
+            metrics: Metrics::new(),
+        }
     }
 
-    pub async fn dispatch_event(&self, event: serde_json::Value) {
-        let event_name: &str = event["event_name"].as_str().unwrap();
-        match event_name {
-            "issues" if event["event"]["issue"].get("pull_request").is_some() => {
-                self.dispatch_pull_request_event(event).await
-            }
-            "pull_request" => self.dispatch_pull_request_event(event).await,
-            "issues" => self.dispatch_issues_event(event).await,
-            "issue_comment" => self.dispatch_issue_comment_event(event).await,
-            _ => unimplemented!(),
-        }
+    /// The bot's own login, so that `@login command` is recognized as well
+    /// as `/command`, and events the bot caused itself are ignored.
+    pub fn with_login(mut self, login: impl Into<String>) -> Self {
+        self.login = Some(login.into());
+        self
     }
 
-    async fn dispatch_issues_event(&self, event: serde_json::Value) {
-        let event_action: &str = event["event"]["action"].as_str().unwrap();
-        let repo = Self::extract_repo_info(&event);
-        let running_info = Self::extract_running_info(&event);
-        match event_action {
-            "opened" => {
-                let inner_event = IssueCreatedEvent {
-                    id: event["event"]["issue"]["number"].as_u64().unwrap() as _,
-                    title: event["event"]["issue"]["title"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    body: event["event"]["issue"]["body"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    user: event["event"]["issue"]["user"]["login"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                };
-                self.core
-                    .on_issue_created(repo, running_info, inner_event)
-                    .await;
-            }
-            "closed" => {
-                let id = event["event"]["issue"]["number"].as_u64().unwrap() as usize;
-                self.core.on_issue_closed(repo, running_info, id).await;
-            }
-            "updated" => {
-                let updated_part = if event["event"]["changes"].get("body").is_some() {
-                    UpdatedPart::Body {
-                        from: event["event"]["changes"]["body"]["from"]
-                            .as_str()
-                            .unwrap()
-                            .to_string(),
-                        to: event["event"]["issue"]["body"]
-                            .as_str()
-                            .unwrap()
-                            .to_string(),
-                    }
-                } else {
-                    UpdatedPart::Title {
-                        from: event["event"]["changed"]["body"]["from"]
-                            .as_str()
-                            .unwrap()
-                            .to_string(),
-                        to: event["event"]["issue"]["title"]
-                            .as_str()
-                            .unwrap()
-                            .to_string(),
-                    }
-                };
-                let inner_event = IssueUpdatedEvent {
-                    id: event["event"]["issue"]["number"].as_u64().unwrap() as usize,
-                    updated_part,
-                    user: event["event"]["issue"]["user"]["login"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                };
-                self.core
-                    .on_issue_updated(repo, running_info, inner_event)
-                    .await;
-            }
-            "reopened" => {
-                let inner_event = IssueReopenedEvent {
-                    id: event["event"]["issue"]["number"].as_u64().unwrap() as _,
-                    title: event["event"]["issue"]["title"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    body: event["event"]["issue"]["body"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    user: event["event"]["issue"]["user"]["login"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                };
-                self.core
-                    .on_issue_reopened(repo, running_info, inner_event)
-                    .await;
-            }
-            _ => unimplemented!(),
-        }
+    /// Asks the API who the configured credentials belong to, as an
+    /// alternative to `with_login`.
+    pub async fn detect_login(self) -> Result<Self, client::ClientError> {
+        let login = self.github.authenticated_login().await?;
+        Ok(self.with_login(login))
+    }
+
+    /// How events sent by other bot accounts are treated; ignored by default.
+    pub fn with_bot_policy(mut self, policy: BotPolicy) -> Self {
+        self.bot_policy = policy;
+        self
+    }
+
+    /// Uses `github` as the API client handed to the bot, e.g.
+    /// `GitHub::new(Auth::from_env()?)`.
+    pub fn with_github(mut self, github: GitHub) -> Self {
+        self.github = github;
+        self
     }
 
-    async fn dispatch_pull_request_event(&self, event: serde_json::Value) {
-        let repo = Self::extract_repo_info(&event);
-        let event_action: &str = event["event"]["action"].as_str().unwrap();
-        let running_info = Self::extract_running_info(&event);
-        match event_action {
-            "opened" => {
-                let inner_event = PullRequestCreatedEvent {
-                    id: event["event"]["pull_request"]["number"].as_u64().unwrap() as _,
-                    title: event["event"]["pull_request"]["title"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    body: event["event"]["pull_request"]["body"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    user: event["event"]["pull_request"]["user"]["login"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    from_repo: Repository {
-                        owner: event["event"]["pull_request"]["head"]["user"]["login"]
-                            .as_str()
-                            .unwrap()
-                            .to_string(),
-                        name: event["event"]["pull_request"]["head"]["repo"]["name"]
-                            .as_str()
-                            .unwrap()
-                            .to_string(),
-                    },
-                    from_ref: event["head_ref"].as_str().unwrap().to_string(),
-                    to_ref: event["base_ref"].as_str().unwrap().to_string(),
+    /// Hands `api` to the bot instead of a `GitHub` client, e.g. a mock in
+    /// tests.
+    pub fn with_api(mut self, api: Arc<dyn GitHubApi>) -> Self {
+        self.api = Some(api);
+        self
+    }
+
+    /// Wraps every dispatch in `layer`. Layers run in the order they are
+    /// added, the first one outermost.
+    pub fn layer(mut self, layer: impl Layer + 'static) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    /// Loads `.github/baipiao.toml` (or `.yml`) of the repository for every
+    /// event and hands it to the bot through `Context::config`.
+    pub fn with_config<C: Config>(self) -> Self {
+        self.with_config_loader(ConfigLoader::new::<C>())
+    }
+
+    pub fn with_config_loader(mut self, loader: ConfigLoader) -> Self {
+        self.config = Some(loader);
+        self
+    }
+
+    /// Whether to describe each event and the changes made in the job
+    /// summary of the workflow run; on by default.
+    pub fn with_step_summary(mut self, enabled: bool) -> Self {
+        self.step_summary = enabled;
+        self
+    }
+
+    /// Records writes instead of making them, for `dispatch` to report;
+    /// off by default. Reads still go to the API, so pass a
+    /// `testing::MockGitHub` to `with_api` to serve them from memory instead.
+    /// `action_log::dry_run_requested` tells whether a run asked for one
+    /// with `--dry-run` or `BAIPIAO_DRY_RUN`.
+    pub fn with_dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /// Before handling an event, compares `Bot::permissions` with what the
+    /// token was granted and fails naming what is missing, rather than
+    /// halfway through with a 403. The grant is asked once per installation,
+    /// and the check is skipped with a warning when it cannot be told, e.g.
+    /// for the `GITHUB_TOKEN` of a workflow.
+    pub fn with_permission_check(mut self, enabled: bool) -> Self {
+        self.permission_check = enabled;
+        self
+    }
+
+    /// Records events, outcomes, dispatch latency and the API calls of the
+    /// `GitHub` client in `metrics`, e.g. those a server exposes.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// The events the bot handles, see `Bot::subscriptions`.
+    pub fn subscriptions(&self) -> Subscriptions {
+        self.core.subscriptions()
+    }
+
+    /// The token permissions the bot needs, see `Bot::permissions`.
+    pub fn permissions(&self) -> Manifest {
+        self.core.permissions()
+    }
+
+    pub async fn dispatch_event(&self, event: serde_json::Value) -> Outcome {
+        self.dispatch(event).await.outcome
+    }
+
+    /// Like `dispatch_event`, also returning the changes made, or those that
+    /// would have been in a dry run, e.g. to print them.
+    pub async fn dispatch(&self, event: serde_json::Value) -> Report {
+        let event_name = event["event_name"].as_str().unwrap_or_default();
+        let action = event["event"]["action"].as_str().unwrap_or_default();
+        let name = format!("{}.{}", event_name, action);
+        let mut ctx = match self.extract_context(&event) {
+            Ok(ctx) => ctx,
+            Err(err) => {
+                tracing::error!(event = event_name, action, %err, "event failed");
+                self.metrics.increment(
+                    "baipiao_dispatches_total",
+                    &[("event", event_name), ("outcome", "failed")],
+                );
+                return Report {
+                    event: name,
+                    target: event["repository"].as_str().unwrap_or_default().to_string(),
+                    outcome: Outcome::Failed(err.to_string()),
+                    actions: Vec::new(),
+                    dry_run: self.dry_run,
                 };
-                self.core
-                    .on_pull_request_created(repo, running_info, inner_event)
-                    .await;
             }
-            "closed" => {
-                let id = event["event"]["issue"]["number"].as_u64().unwrap() as usize;
-                self.core
-                    .on_pull_request_closed(repo, running_info, id)
-                    .await;
-            }
-            "edited" => {
-                let updated_part = if event["event"]["changes"].get("body").is_some() {
-                    UpdatedPart::Body {
-                        from: event["event"]["changes"]["body"]["from"]
-                            .as_str()
-                            .unwrap()
-                            .to_string(),
-                        to: event["event"]["pull_request"]["body"]
-                            .as_str()
-                            .unwrap()
-                            .to_string(),
-                    }
-                } else {
-                    UpdatedPart::Title {
-                        from: event["event"]["changed"]["body"]["from"]
-                            .as_str()
-                            .unwrap()
-                            .to_string(),
-                        to: event["event"]["pull_request"]["title"]
-                            .as_str()
-                            .unwrap()
-                            .to_string(),
-                    }
-                };
-                let inner_event = PullRequestUpdatedEvent {
-                    id: event["event"]["issue"]["number"].as_u64().unwrap() as usize,
-                    updated_part,
-                    user: event["event"]["issue"]["user"]["login"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                };
-                self.core
-                    .on_pull_request_updated(repo, running_info, inner_event)
-                    .await;
+        };
+        let log = ActionLog::new();
+        ctx.github = Arc::new(if self.dry_run {
+            log.dry_run(ctx.github.clone())
+        } else {
+            log.record(ctx.github.clone())
+        });
+        let span = logging::event_span(
+            event_name,
+            action,
+            &format!("{}/{}", ctx.repo.owner, ctx.repo.name),
+            ctx.running_info.run_id,
+        );
+        ctx.trace = Trace::new(span.clone());
+        self.metrics.increment(
+            "baipiao_events_received_total",
+            &[("event", event_name), ("action", action)],
+        );
+        let start = Instant::now();
+        let outcome = self.run(&mut ctx, &event).instrument(span.clone()).await;
+        self.metrics.observe(
+            "baipiao_dispatch_duration_seconds",
+            &[("event", event_name)],
+            start.elapsed(),
+        );
+        self.metrics.increment(
+            "baipiao_dispatches_total",
+            &[("event", event_name), ("outcome", outcome.as_str())],
+        );
+        span.record("outcome", outcome.to_string().as_str());
+        span.in_scope(|| match &outcome {
+            Outcome::Failed(message) => tracing::error!(%message, "event failed"),
+            _ => tracing::info!(%outcome, "event dispatched"),
+        });
+        let target = match ctx.issue {
+            Some(issue) => format!("{}/{}#{}", ctx.repo.owner, ctx.repo.name, issue),
+            None => format!("{}/{}", ctx.repo.owner, ctx.repo.name),
+        };
+        let actions = log.actions();
+        if self.step_summary {
+            let summary = workflow::step_summary(&name, &target, &outcome, &actions);
+            if let Err(err) = workflow::append_step_summary(&summary) {
+                tracing::warn!(%err, "cannot write the step summary");
             }
-            _ => unimplemented!(),
+        }
+        Report {
+            event: name,
+            target,
+            outcome,
+            actions,
+            dry_run: self.dry_run,
         }
     }
 
-    async fn dispatch_issue_comment_event(&self, event: serde_json::Value) {
-        let repo = Self::extract_repo_info(&event);
-        let event_action: &str = event["event"]["action"].as_str().unwrap();
-        let running_info = Self::extract_running_info(&event);
-        let target = if event["event"]["issue"].get("pull_request").is_some() {
-            CommentTarget::PullRequest(event["event"]["issue"]["number"].as_u64().unwrap() as _)
-        } else {
-            CommentTarget::Issue(event["event"]["issue"]["number"].as_u64().unwrap() as _)
+    async fn run(&self, ctx: &mut Context, payload: &serde_json::Value) -> Outcome {
+        if ctx.ignored_sender && !self.core.receive_bot_events() {
+            return Outcome::Skipped(format!("sent by bot account {}", ctx.sender.login));
+        }
+        let event = match Event::parse(payload) {
+            Ok(event) => event,
+            Err(err @ ParseError::Unsupported { .. }) => return Outcome::Skipped(err.to_string()),
+            Err(err) => return Outcome::Failed(err.to_string()),
         };
-        match event_action {
-            "created" => {
-                let inner_event = CommentCreatedEvent {
-                    id: event["event"]["comment"]["id"].as_u64().unwrap() as usize,
-                    user: event["event"]["comment"]["user"]["login"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    target,
-                    body: event["event"]["comment"]["body"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                };
-                self.core
-                    .on_comment_created(repo, running_info, inner_event)
-                    .await;
-            }
-            "deleted" => {
-                let id = event["event"]["comment"]["id"].as_u64().unwrap() as usize;
-                self.core.on_comment_deleted(repo, running_info, id).await;
+        if !self.core.subscriptions().has(event.name()) {
+            return Outcome::Skipped(format!("not subscribed to {}", event.name()));
+        }
+        if self.permission_check {
+            match self.granted_permissions(ctx).await {
+                Ok(Some(granted)) => {
+                    if let Some(missing) = self.core.permissions().missing(&granted) {
+                        return Outcome::Failed(missing.to_string());
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => tracing::warn!(%err, "cannot check the token's permissions"),
             }
-            "edited" => {
-                let inner_event = CommentUpdatedEvent {
-                    id: event["event"]["comment"]["id"].as_u64().unwrap() as usize,
-                    user: event["event"]["comment"]["user"]["login"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    target,
-                    from: event["event"]["changes"]["from"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    to: event["event"]["comment"]["body"]
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                };
-                self.core
-                    .on_comment_updated(repo, running_info, inner_event)
-                    .await;
+        }
+        if let Some(loader) = &self.config {
+            let base = [
+                "/event/pull_request/base/sha",
+                "/event/pull_request/base/ref",
+            ]
+            .iter()
+            .find_map(|path| payload.pointer(path).and_then(serde_json::Value::as_str));
+            match loader.load(ctx, base).await {
+                Ok(config) => ctx.config = Some(config),
+                Err(err) => return Outcome::Failed(err.to_string()),
             }
-            _ => unimplemented!(),
         }
+        ctx.issue = event.issue_id();
+        if let Some(issue) = ctx.issue {
+            ctx.trace.span().record("target", issue);
+        }
+        Next::new(&self.layers, &self.core).run(ctx, event).await
     }
 
-    fn extract_repo_info(event: &serde_json::Value) -> Repository {
-        Repository {
-            owner: event["event"]["repository"]["owner"]["login"]
-                .as_str()
-                .unwrap()
-                .to_string(),
-            name: event["repository"]
-                .as_str()
-                .unwrap()
-                .split('/')
-                .nth(1)
-                .unwrap()
-                .to_string(),
+    async fn granted_permissions(
+        &self,
+        ctx: &Context,
+    ) -> Result<Option<Manifest>, client::ClientError> {
+        let installation = ctx.running_info.installation_id;
+        if let Some(granted) = self.granted.lock().unwrap().get(&installation) {
+            return Ok(granted.clone());
+        }
+        let granted = ctx.github.granted_permissions(&ctx.repo).await?;
+        if granted.is_none() {
+            tracing::warn!(
+                "cannot tell what the token was granted, its permissions are not checked"
+            );
         }
+        self.granted
+            .lock()
+            .unwrap()
+            .insert(installation, granted.clone());
+        Ok(granted)
     }
 
-    fn extract_running_info(event: &serde_json::Value) -> RunningInfo {
-        RunningInfo {
-            run_id: usize::from_str_radix(event["run_id"].as_str().unwrap(), 10).unwrap(),
-            run_number: usize::from_str_radix(event["run_number"].as_str().unwrap(), 10).unwrap(),
+    fn extract_sender(event: &serde_json::Value) -> Sender {
+        let login = event["event"]["sender"]["login"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        Sender {
+            is_bot: event["event"]["sender"]["type"] == "Bot" || login.ends_with("[bot]"),
+            login,
         }
     }
+
+    fn extract_context(&self, event: &serde_json::Value) -> Result<Context, ParseError> {
+        let running_info = payload::running_info(event)?;
+        let sender = Self::extract_sender(event);
+        Ok(Context {
+            repo: payload::repository(event)?,
+            ignored_sender: bots::is_ignored(&sender, self.login.as_deref(), &self.bot_policy),
+            sender,
+            login: self.login.clone(),
+            issue: None,
+            github: match &self.api {
+                Some(api) => api.clone(),
+                None => Arc::new(
+                    self.github
+                        .installation(running_info.installation_id)
+                        .with_metrics(self.metrics.clone()),
+                ),
+            },
+            running_info,
+            config: None,
+            trace: Trace::default(),
+            metrics: self.metrics.clone(),
+        })
+    }
 }
@@ -0,0 +1,114 @@
+use std::sync::{Arc, Mutex};
+use tracing::Span;
+
+/// The span of the event being dispatched, with the fields handlers added
+/// through `Context::record`.
+///
+/// Every event gets an `event` span with the event name, action, repository,
+/// target number and run id; `Router` handlers and API calls get spans of
+/// their own inside it. Nothing is printed unless a `tracing` subscriber is
+/// installed, e.g. by `logging::init`.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    span: Span,
+    fields: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new(Span::none())
+    }
+}
+
+impl Trace {
+    pub fn new(span: Span) -> Self {
+        Trace {
+            span,
+            fields: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// The fields added so far, in order.
+    pub fn fields(&self) -> Vec<(String, String)> {
+        self.fields.lock().unwrap().clone()
+    }
+
+    /// Adds `key` to the span's `fields`, replacing an earlier value.
+    ///
+    /// `tracing` only records fields declared when a span is created, so
+    /// they are kept together as `key=value` pairs.
+    pub(crate) fn record(&self, key: &str, value: String) {
+        let mut fields = self.fields.lock().unwrap();
+        match fields.iter_mut().find(|(it, _)| it == key) {
+            Some(field) => field.1 = value,
+            None => fields.push((key.to_string(), value)),
+        }
+        let joined = fields
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.span.record("fields", joined.as_str());
+    }
+}
+
+/// The span `Dispatcher::dispatch_event` runs an event in.
+pub(crate) fn event_span(name: &str, action: &str, repo: &str, run_id: usize) -> Span {
+    tracing::info_span!(
+        "event",
+        event = name,
+        action = action,
+        repo = repo,
+        run_id = run_id,
+        target = tracing::field::Empty,
+        fields = tracing::field::Empty,
+        outcome = tracing::field::Empty,
+    )
+}
+
+/// How `init` prints logs.
+#[cfg(feature = "logging")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, with the fields of the enclosing spans.
+    Json,
+}
+
+#[cfg(feature = "logging")]
+impl LogFormat {
+    /// `Json` when `BAIPIAO_LOG_FORMAT` is `json`, `Text` otherwise.
+    pub fn from_env() -> Self {
+        match std::env::var("BAIPIAO_LOG_FORMAT") {
+            Ok(format) if format.eq_ignore_ascii_case("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        }
+    }
+}
+
+/// Prints logs to stderr in `format`, filtered by `BAIPIAO_LOG` (e.g.
+/// `debug` or `baipiao_bot_rust=info`), `info` by default. Does nothing if a
+/// subscriber is already installed.
+#[cfg(feature = "logging")]
+pub fn init(format: LogFormat) {
+    use tracing_subscriber::fmt::format::FmtSpan;
+    use tracing_subscriber::EnvFilter;
+
+    let filter = EnvFilter::try_from_env("BAIPIAO_LOG").unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_span_events(FmtSpan::CLOSE);
+    let _ = match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+    };
+}
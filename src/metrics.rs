@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Histogram => "histogram",
+        }
+    }
+}
+
+/// Everything recorded, with its kind and help text.
+pub const METRICS: &[(&str, Kind, &str)] = &[
+    (
+        "baipiao_events_received_total",
+        Kind::Counter,
+        "Events received, by event and action.",
+    ),
+    (
+        "baipiao_dispatches_total",
+        Kind::Counter,
+        "Dispatched events, by event and outcome.",
+    ),
+    (
+        "baipiao_dispatch_duration_seconds",
+        Kind::Histogram,
+        "Time taken to dispatch an event, by event.",
+    ),
+    (
+        "baipiao_handler_outcomes_total",
+        Kind::Counter,
+        "Outcomes of `Router` handlers, by handler and outcome.",
+    ),
+    (
+        "baipiao_api_calls_total",
+        Kind::Counter,
+        "GitHub API calls, by method and status.",
+    ),
+    (
+        "baipiao_rate_limit_remaining",
+        Kind::Gauge,
+        "Requests left in the current rate limit window, by resource.",
+    ),
+];
+
+/// Upper bounds of the `baipiao_dispatch_duration_seconds` buckets.
+const BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    counters: BTreeMap<(&'static str, Labels), u64>,
+    gauges: BTreeMap<(&'static str, Labels), f64>,
+    histograms: BTreeMap<(&'static str, Labels), Histogram>,
+}
+
+/// Counters, gauges and latency histograms of a running bot, printed in
+/// the Prometheus text format by `render`, e.g. on the server's `/metrics`.
+///
+/// Clones share their values. The dispatcher records events and outcomes
+/// given `Dispatcher::with_metrics`, `Router` its handlers' outcomes and
+/// `GitHub` its calls and rate limits; names are listed in `METRICS`.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+fn to_labels(labels: &[(&'static str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(name, value)| (*name, value.to_string()))
+        .collect()
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn increment(&self, name: &'static str, labels: &[(&'static str, &str)]) {
+        let mut registry = self.registry.lock().unwrap();
+        *registry
+            .counters
+            .entry((name, to_labels(labels)))
+            .or_insert(0) += 1;
+    }
+
+    pub fn set(&self, name: &'static str, labels: &[(&'static str, &str)], value: f64) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.gauges.insert((name, to_labels(labels)), value);
+    }
+
+    pub fn observe(&self, name: &'static str, labels: &[(&'static str, &str)], elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let mut registry = self.registry.lock().unwrap();
+        let histogram = registry
+            .histograms
+            .entry((name, to_labels(labels)))
+            .or_insert_with(|| Histogram {
+                buckets: vec![0; BUCKETS.len()],
+                ..Histogram::default()
+            });
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= *bound {
+                *bucket += 1;
+            }
+        }
+        histogram.count += 1;
+        histogram.sum += seconds;
+    }
+
+    /// The value of a counter, 0 if it was never incremented.
+    pub fn counter(&self, name: &str, labels: &[(&'static str, &str)]) -> u64 {
+        let registry = self.registry.lock().unwrap();
+        let labels = to_labels(labels);
+        registry
+            .counters
+            .iter()
+            .find(|((it, their), _)| *it == name && *their == labels)
+            .map_or(0, |(_, value)| *value)
+    }
+
+    pub fn gauge(&self, name: &str, labels: &[(&'static str, &str)]) -> Option<f64> {
+        let registry = self.registry.lock().unwrap();
+        let labels = to_labels(labels);
+        registry
+            .gauges
+            .iter()
+            .find(|((it, their), _)| *it == name && *their == labels)
+            .map(|(_, value)| *value)
+    }
+
+    /// Everything recorded so far, in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut text = String::new();
+        for (name, kind, help) in METRICS {
+            // (suffix, labels, value) of each sample
+            let mut samples = Vec::new();
+            for ((_, labels), value) in registry.counters.iter().filter(|((it, _), _)| it == name) {
+                samples.push(("", format_labels(labels, None), value.to_string()));
+            }
+            for ((_, labels), value) in registry.gauges.iter().filter(|((it, _), _)| it == name) {
+                samples.push(("", format_labels(labels, None), value.to_string()));
+            }
+            for ((_, labels), histogram) in
+                registry.histograms.iter().filter(|((it, _), _)| it == name)
+            {
+                for (bound, count) in BUCKETS.iter().zip(&histogram.buckets) {
+                    let le = bound.to_string();
+                    samples.push((
+                        "_bucket",
+                        format_labels(labels, Some(&le)),
+                        count.to_string(),
+                    ));
+                }
+                let count = histogram.count.to_string();
+                samples.push((
+                    "_bucket",
+                    format_labels(labels, Some("+Inf")),
+                    count.clone(),
+                ));
+                samples.push((
+                    "_sum",
+                    format_labels(labels, None),
+                    histogram.sum.to_string(),
+                ));
+                samples.push(("_count", format_labels(labels, None), count));
+            }
+            if samples.is_empty() {
+                continue;
+            }
+            writeln!(text, "# HELP {} {}", name, help).unwrap();
+            writeln!(text, "# TYPE {} {}", name, kind.as_str()).unwrap();
+            for (suffix, labels, value) in samples {
+                writeln!(text, "{}{}{} {}", name, suffix, labels, value).unwrap();
+            }
+        }
+        text
+    }
+}
+
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,247 @@
+//! Turns the `github` context of an Actions run into an `Event`.
+//!
+//! Paths in errors are JSON pointers into that context, e.g.
+//! `/event/pull_request/number`, so a failing payload can be checked
+//! against GitHub's webhook documentation directly.
+
+use crate::{
+    AuthorAssociation, CommentCreatedEvent, CommentTarget, CommentUpdatedEvent, Event,
+    IssueCreatedEvent, IssueReopenedEvent, IssueUpdatedEvent, PullRequestCreatedEvent,
+    PullRequestUpdatedEvent, Repository, RunningInfo, UpdatedPart,
+};
+use serde_json::{json, Value};
+use std::fmt;
+
+/// Builds the parts of the `github` context the dispatcher reads around a
+/// webhook payload, e.g. one delivered to a server or saved from the app's
+/// advanced settings.
+pub fn wrap(event_name: &str, event: Value) -> Value {
+    json!({
+        "event_name": event_name,
+        "repository": event["repository"]["full_name"],
+        "run_id": "0",
+        "run_number": "0",
+        "event": event,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The bot has no handler for this event or action, e.g. `push` or
+    /// `pull_request.synchronize`.
+    Unsupported { event: String, action: String },
+    /// A field is missing or has the wrong type.
+    Field {
+        path: String,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Unsupported { event, action } if action.is_empty() => {
+                write!(f, "unsupported event `{}`", event)
+            }
+            ParseError::Unsupported { event, action } => {
+                write!(f, "unsupported event `{}.{}`", event, action)
+            }
+            ParseError::Field { path, expected } => {
+                write!(f, "expected {} at `{}`", expected, path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn invalid(path: &str, expected: &'static str) -> ParseError {
+    ParseError::Field {
+        path: path.to_string(),
+        expected,
+    }
+}
+
+fn string(payload: &Value, path: &str) -> Result<String, ParseError> {
+    payload
+        .pointer(path)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| invalid(path, "a string"))
+}
+
+/// Issue and pull request bodies are `null` when left empty.
+fn text(payload: &Value, path: &str) -> Result<String, ParseError> {
+    match payload.pointer(path) {
+        Some(Value::Null) => Ok(String::new()),
+        _ => string(payload, path),
+    }
+}
+
+fn number(payload: &Value, path: &str) -> Result<usize, ParseError> {
+    payload
+        .pointer(path)
+        .and_then(Value::as_u64)
+        .map(|it| it as usize)
+        .ok_or_else(|| invalid(path, "a number"))
+}
+
+/// Actions gives run ids and numbers as strings.
+fn numeric_string(payload: &Value, path: &str) -> Result<usize, ParseError> {
+    string(payload, path)?
+        .parse()
+        .map_err(|_| invalid(path, "a number in a string"))
+}
+
+fn labels(payload: &Value, path: &str) -> Vec<String> {
+    payload
+        .pointer(path)
+        .and_then(Value::as_array)
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|label| label["name"].as_str())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn association(payload: &Value, path: &str) -> AuthorAssociation {
+    payload
+        .pointer(path)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .into()
+}
+
+/// What an `edited` action changed in `object`, `issue` or `pull_request`.
+///
+/// Pull requests can also be edited by retargeting them, which changes
+/// neither; that is reported as the title changing to itself.
+fn updated_part(payload: &Value, object: &str) -> Result<UpdatedPart, ParseError> {
+    if payload.pointer("/event/changes/body").is_some() {
+        Ok(UpdatedPart::Body {
+            from: text(payload, "/event/changes/body/from")?,
+            to: text(payload, &format!("/event/{}/body", object))?,
+        })
+    } else {
+        let to = string(payload, &format!("/event/{}/title", object))?;
+        let from = match payload.pointer("/event/changes/title") {
+            Some(_) => string(payload, "/event/changes/title/from")?,
+            None => to.clone(),
+        };
+        Ok(UpdatedPart::Title { from, to })
+    }
+}
+
+/// The repository of the run, from its `owner/name`.
+pub(crate) fn repository(payload: &Value) -> Result<Repository, ParseError> {
+    match string(payload, "/repository")?.split_once('/') {
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() => Ok(Repository {
+            owner: owner.to_string(),
+            name: name.to_string(),
+        }),
+        _ => Err(invalid("/repository", "`owner/name`")),
+    }
+}
+
+pub(crate) fn running_info(payload: &Value) -> Result<RunningInfo, ParseError> {
+    Ok(RunningInfo {
+        run_id: numeric_string(payload, "/run_id")?,
+        run_number: numeric_string(payload, "/run_number")?,
+        installation_id: payload
+            .pointer("/event/installation/id")
+            .and_then(Value::as_u64),
+        sha: payload
+            .pointer("/sha")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+    })
+}
+
+pub(crate) fn parse(payload: &Value) -> Result<Event, ParseError> {
+    let event = string(payload, "/event_name")?;
+    let action = payload
+        .pointer("/event/action")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    match (event.as_str(), action) {
+        ("issues", "opened") => Ok(Event::IssueCreated(IssueCreatedEvent {
+            id: number(payload, "/event/issue/number")?,
+            title: string(payload, "/event/issue/title")?,
+            body: text(payload, "/event/issue/body")?,
+            user: string(payload, "/event/issue/user/login")?,
+            author_association: association(payload, "/event/issue/author_association"),
+            labels: labels(payload, "/event/issue/labels"),
+        })),
+        ("issues", "edited") => Ok(Event::IssueUpdated(IssueUpdatedEvent {
+            id: number(payload, "/event/issue/number")?,
+            updated_part: updated_part(payload, "issue")?,
+            user: string(payload, "/event/issue/user/login")?,
+        })),
+        ("issues", "closed") => Ok(Event::IssueClosed(number(payload, "/event/issue/number")?)),
+        ("issues", "reopened") => Ok(Event::IssueReopened(IssueReopenedEvent {
+            id: number(payload, "/event/issue/number")?,
+            title: string(payload, "/event/issue/title")?,
+            body: text(payload, "/event/issue/body")?,
+            user: string(payload, "/event/issue/user/login")?,
+            labels: labels(payload, "/event/issue/labels"),
+        })),
+        ("pull_request", "opened") => Ok(Event::PullRequestCreated(PullRequestCreatedEvent {
+            id: number(payload, "/event/pull_request/number")?,
+            title: string(payload, "/event/pull_request/title")?,
+            body: text(payload, "/event/pull_request/body")?,
+            user: string(payload, "/event/pull_request/user/login")?,
+            author_association: association(payload, "/event/pull_request/author_association"),
+            labels: labels(payload, "/event/pull_request/labels"),
+            from_repo: Repository {
+                owner: string(payload, "/event/pull_request/head/user/login")?,
+                name: string(payload, "/event/pull_request/head/repo/name")?,
+            },
+            from_ref: string(payload, "/event/pull_request/head/ref")?,
+            to_ref: string(payload, "/event/pull_request/base/ref")?,
+        })),
+        ("pull_request", "edited") => Ok(Event::PullRequestUpdated(PullRequestUpdatedEvent {
+            id: number(payload, "/event/pull_request/number")?,
+            updated_part: updated_part(payload, "pull_request")?,
+            user: string(payload, "/event/pull_request/user/login")?,
+        })),
+        ("pull_request", "closed") => Ok(Event::PullRequestClosed(number(
+            payload,
+            "/event/pull_request/number",
+        )?)),
+        ("issue_comment", "created") => Ok(Event::CommentCreated(CommentCreatedEvent {
+            id: number(payload, "/event/comment/id")?,
+            user: string(payload, "/event/comment/user/login")?,
+            author_association: association(payload, "/event/comment/author_association"),
+            target: comment_target(payload)?,
+            body: string(payload, "/event/comment/body")?,
+        })),
+        ("issue_comment", "edited") => Ok(Event::CommentUpdated(CommentUpdatedEvent {
+            id: number(payload, "/event/comment/id")?,
+            user: string(payload, "/event/comment/user/login")?,
+            target: comment_target(payload)?,
+            from: string(payload, "/event/changes/body/from")?,
+            to: string(payload, "/event/comment/body")?,
+        })),
+        ("issue_comment", "deleted") => {
+            Ok(Event::CommentDeleted(number(payload, "/event/comment/id")?))
+        }
+        _ => Err(ParseError::Unsupported {
+            event,
+            action: action.to_string(),
+        }),
+    }
+}
+
+/// Comments on pull requests are `issue_comment` events too, told apart by
+/// the issue's `pull_request` field.
+fn comment_target(payload: &Value) -> Result<CommentTarget, ParseError> {
+    let id = number(payload, "/event/issue/number")?;
+    if payload.pointer("/event/issue/pull_request").is_some() {
+        Ok(CommentTarget::PullRequest(id))
+    } else {
+        Ok(CommentTarget::Issue(id))
+    }
+}
@@ -0,0 +1,155 @@
+use crate::client::{ClientError, CollaboratorPermission};
+use crate::commands::{Command, CommandSource};
+use crate::{AuthorAssociation, CommentTarget, Context};
+use std::{fmt, str::FromStr};
+
+/// Who may run a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Role {
+    Anyone,
+    /// The user owning the repository, as told by `author_association`;
+    /// members of an organization owning it are not owners.
+    Owner,
+    /// Users with the `maintain` or `admin` permission.
+    Maintainer,
+    /// Users with write access.
+    Collaborator,
+    /// The author of the pull request the command was written on, or a
+    /// collaborator.
+    PullRequestAuthor,
+    /// Active members of `org/team`, typically listed in the bot's config.
+    Team {
+        org: String,
+        team: String,
+    },
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Anyone => write!(f, "anyone"),
+            Role::Owner => write!(f, "owner"),
+            Role::Maintainer => write!(f, "maintainer"),
+            Role::Collaborator => write!(f, "collaborator"),
+            Role::PullRequestAuthor => write!(f, "pr-author"),
+            Role::Team { org, team } => write!(f, "team:{}/{}", org, team),
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    /// Parses the `Display` form, e.g. `collaborator` or `team:org/reviewers`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "anyone" => Ok(Role::Anyone),
+            "owner" => Ok(Role::Owner),
+            "maintainer" => Ok(Role::Maintainer),
+            "collaborator" => Ok(Role::Collaborator),
+            "pr-author" => Ok(Role::PullRequestAuthor),
+            _ => s
+                .strip_prefix("team:")
+                .and_then(|it| it.split_once('/'))
+                .filter(|(org, team)| !org.is_empty() && !team.is_empty())
+                .map(|(org, team)| Role::Team {
+                    org: org.to_string(),
+                    team: team.to_string(),
+                })
+                .ok_or_else(|| format!("unknown role `{}`", s)),
+        }
+    }
+}
+
+/// Checks `role` against the payload's `author_association` first, and asks
+/// the API only when that is not conclusive.
+pub async fn is_allowed(
+    ctx: &Context,
+    role: &Role,
+    command: &Command,
+) -> Result<bool, ClientError> {
+    match role {
+        Role::Anyone => Ok(true),
+        Role::Owner => Ok(command.association == AuthorAssociation::Owner),
+        Role::Maintainer => Ok(match command.association {
+            AuthorAssociation::Owner => true,
+            AuthorAssociation::Member | AuthorAssociation::Collaborator => permission(ctx, command)
+                .await?
+                .is_some_and(|it| it.permission == "admin" || it.role_name == "maintain"),
+            _ => false,
+        }),
+        Role::Collaborator => is_collaborator(ctx, command).await,
+        Role::PullRequestAuthor => {
+            let pull_request_id = match &command.source {
+                CommandSource::PullRequestBody(_) => return Ok(true),
+                CommandSource::Comment {
+                    target: CommentTarget::PullRequest(id),
+                    ..
+                } => *id,
+                _ => return Ok(false),
+            };
+            let pull_request = ctx.github.issue(&ctx.repo, pull_request_id).await?;
+            if pull_request.user.login == command.user {
+                return Ok(true);
+            }
+            is_collaborator(ctx, command).await
+        }
+        Role::Team { org, team } => ctx.github.is_team_member(org, team, &command.user).await,
+    }
+}
+
+/// Whether the user has write access. `COLLABORATOR` is also given to
+/// read-only and triage collaborators, so the association can only rule
+/// users out.
+async fn is_collaborator(ctx: &Context, command: &Command) -> Result<bool, ClientError> {
+    match command.association {
+        AuthorAssociation::Owner => return Ok(true),
+        AuthorAssociation::Member | AuthorAssociation::Collaborator => {}
+        _ => return Ok(false),
+    }
+    Ok(permission(ctx, command)
+        .await?
+        .is_some_and(|it| matches!(it.permission.as_str(), "admin" | "write")))
+}
+
+/// The user's permission on the repository, or `None` when GitHub does not
+/// know them as a collaborator.
+async fn permission(
+    ctx: &Context,
+    command: &Command,
+) -> Result<Option<CollaboratorPermission>, ClientError> {
+    match ctx
+        .github
+        .collaborator_permission(&ctx.repo, &command.user)
+        .await
+    {
+        Ok(permission) => Ok(Some(permission)),
+        Err(ClientError::Status { status: 404, .. }) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Tells the user off and leaves an audit log entry.
+pub async fn deny(ctx: &Context, role: &Role, command: &Command) {
+    tracing::warn!(
+        target: "baipiao::audit",
+        command = command.name.as_str(),
+        user = command.user.as_str(),
+        association = ?command.association,
+        repo = %format_args!("{}/{}", ctx.repo.owner, ctx.repo.name),
+        target = command.source.target_id(),
+        role = %role,
+        "denied command"
+    );
+    let reply = format!(
+        "@{} you are not allowed to do this: `/{}` requires {}.",
+        command.user, command.name, role
+    );
+    if let Err(err) = ctx
+        .github
+        .create_comment(&ctx.repo, command.source.target_id(), &reply)
+        .await
+    {
+        tracing::warn!(%err, "cannot reply to denied command");
+    }
+}
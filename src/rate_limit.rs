@@ -0,0 +1,175 @@
+//! How `GitHub` stays within GitHub's rate limits: writes are queued to
+//! respect the content creation limits, and calls turned away by a limit
+//! are retried after the wait GitHub asks for, when that is safe.
+
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::header::HeaderMap;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+/// Limits for `GitHub::with_rate_limits`; the defaults follow GitHub's
+/// documented limits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimits {
+    /// How often an idempotent call turned away by a rate limit is tried
+    /// again.
+    pub retries: u32,
+    /// The longest wait before giving up with `ClientError::RateLimited`.
+    pub max_wait: Duration,
+    /// The first wait when a secondary limit gives no `Retry-After`,
+    /// doubled on each retry.
+    pub backoff: Duration,
+    /// Writes allowed in any minute and in any hour.
+    pub writes_per_minute: usize,
+    pub writes_per_hour: usize,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            retries: 3,
+            max_wait: Duration::from_secs(180),
+            backoff: Duration::from_secs(60),
+            writes_per_minute: 80,
+            writes_per_hour: 500,
+        }
+    }
+}
+
+/// A response turned away by a rate limit.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Limited {
+    /// One of the limits on bursts, rather than the hourly budget.
+    pub secondary: bool,
+    /// How long GitHub asked to wait, if it said.
+    pub wait: Option<Duration>,
+}
+
+impl Limited {
+    /// Reads `Retry-After` and `X-RateLimit-*` of a 403 or 429; `None` when
+    /// they do not tell a rate limit.
+    pub fn from_headers(status: u16, headers: &HeaderMap) -> Option<Self> {
+        if status != 403 && status != 429 {
+            return None;
+        }
+        let header = |name| headers.get(name).and_then(|it| it.to_str().ok());
+        if let Some(seconds) = header("retry-after").and_then(|it| it.parse().ok()) {
+            return Some(Limited {
+                secondary: true,
+                wait: Some(Duration::from_secs(seconds)),
+            });
+        }
+        if header("x-ratelimit-remaining") == Some("0") {
+            let wait = header("x-ratelimit-reset")
+                .and_then(|it| it.parse().ok())
+                .and_then(|reset| Utc.timestamp_opt(reset, 0).single())
+                .map(|reset| (reset - Utc::now()).to_std().unwrap_or_default());
+            return Some(Limited {
+                secondary: false,
+                wait,
+            });
+        }
+        if status == 429 {
+            return Some(Limited {
+                secondary: true,
+                wait: None,
+            });
+        }
+        None
+    }
+
+    /// How long to wait before `attempt`, counting from 1, with jitter so
+    /// that concurrent runs do not retry in lockstep.
+    pub fn wait(&self, attempt: u32, limits: &RateLimits) -> Duration {
+        let base = self
+            .wait
+            .unwrap_or_else(|| limits.backoff * 2u32.saturating_pow(attempt - 1));
+        base + base.mul_f64(random() / 4.0)
+    }
+
+    /// When it would be worth trying again.
+    pub fn retry_at(&self) -> Option<DateTime<Utc>> {
+        let wait = chrono::Duration::from_std(self.wait?).ok()?;
+        Some(Utc::now() + wait)
+    }
+}
+
+/// A number in `[0, 1)`, different on each call.
+fn random() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u8(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Sends the writes of each token one at a time, and no more of them than
+/// `RateLimits` allows per minute and per hour.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WriteQueue {
+    queues: Arc<std::sync::Mutex<HashMap<Option<u64>, Arc<Queue>>>>,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    /// When each write of the last hour was sent, or is due to be.
+    sent: std::sync::Mutex<VecDeque<Instant>>,
+    /// Held while a write is sent.
+    sending: Arc<Mutex<()>>,
+}
+
+impl WriteQueue {
+    /// Waits for the turn of a write with the token of `installation`, which
+    /// lasts as long as the guard; tokens do not wait for each other. `Err`
+    /// holds the wait when it would be longer than `max_wait`.
+    pub async fn turn(
+        &self,
+        installation: Option<u64>,
+        limits: &RateLimits,
+    ) -> Result<OwnedMutexGuard<()>, Duration> {
+        let queue = self
+            .queues
+            .lock()
+            .unwrap()
+            .entry(installation)
+            .or_default()
+            .clone();
+        let due = {
+            let mut sent = queue.sent.lock().unwrap();
+            let now = Instant::now();
+            let hour = Duration::from_secs(3600);
+            while sent
+                .front()
+                .is_some_and(|it| now.saturating_duration_since(*it) >= hour)
+            {
+                sent.pop_front();
+            }
+            let due = [
+                (Duration::from_secs(60), limits.writes_per_minute),
+                (hour, limits.writes_per_hour),
+            ]
+            .iter()
+            .filter_map(|(window, limit)| {
+                // the write that has to leave the window before the next one
+                let index = sent.len().checked_sub((*limit).max(1))?;
+                Some(sent[index] + *window)
+            })
+            .fold(now, Instant::max);
+            let wait = due - now;
+            if wait > limits.max_wait {
+                return Err(wait);
+            }
+            sent.push_back(due);
+            due
+        };
+        // the slot is taken, so others can queue while this one sleeps
+        let wait = due.saturating_duration_since(Instant::now());
+        if wait > Duration::ZERO {
+            tracing::info!(?wait, "waiting for the content creation limit");
+            tokio::time::sleep(wait).await;
+        }
+        Ok(queue.sending.clone().lock_owned().await)
+    }
+}
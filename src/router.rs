@@ -0,0 +1,135 @@
+use crate::layers::{panic_message, Outcome};
+use crate::scopes::Manifest;
+use crate::subscriptions::Subscriptions;
+use crate::{Bot, Context, Event};
+use async_trait::async_trait;
+use futures::future::join_all;
+use futures::FutureExt;
+use std::panic::AssertUnwindSafe;
+use tracing::Instrument;
+
+/// What became of an event in one of a router's handlers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandlerOutcome {
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+/// Combines several bots into one, e.g. a welcome bot, a labeler and a
+/// command bot.
+///
+/// Each event goes to every handler subscribed to it, in the order they were
+/// added unless `concurrent` is set. A panicking handler does not stop the
+/// others, but fails the event as a whole.
+#[derive(Default)]
+pub struct Router {
+    handlers: Vec<(String, Box<dyn Bot>)>,
+    concurrent: bool,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handler(mut self, name: impl Into<String>, bot: impl Bot + 'static) -> Self {
+        self.handlers.push((name.into(), Box::new(bot)));
+        self
+    }
+
+    /// Runs the handlers of an event at the same time instead of one by one.
+    pub fn concurrent(mut self) -> Self {
+        self.concurrent = true;
+        self
+    }
+
+    pub async fn dispatch(&self, ctx: &Context, event: Event) -> Vec<HandlerOutcome> {
+        let runs = self.handlers.iter().map(|(name, bot)| {
+            let span = tracing::info_span!("handler", handler = name.as_str());
+            Self::run(name, bot.as_ref(), ctx, event.clone()).instrument(span)
+        });
+        if self.concurrent {
+            join_all(runs).await
+        } else {
+            let mut outcomes = Vec::with_capacity(self.handlers.len());
+            for run in runs {
+                outcomes.push(run.await);
+            }
+            outcomes
+        }
+    }
+
+    async fn run(name: &str, bot: &dyn Bot, ctx: &Context, event: Event) -> HandlerOutcome {
+        let outcome = if ctx.ignored_sender && !bot.receive_bot_events() {
+            Outcome::Skipped(format!("sent by bot account {}", ctx.sender.login))
+        } else if !bot.subscriptions().has(event.name()) {
+            Outcome::Skipped(format!("not subscribed to {}", event.name()))
+        } else {
+            match AssertUnwindSafe(bot.handle_event(ctx, event))
+                .catch_unwind()
+                .await
+            {
+                Ok(outcome) => outcome,
+                Err(panic) => Outcome::Failed(panic_message(panic.as_ref())),
+            }
+        };
+        ctx.metrics.increment(
+            "baipiao_handler_outcomes_total",
+            &[("handler", name), ("outcome", outcome.as_str())],
+        );
+        HandlerOutcome {
+            name: name.to_string(),
+            outcome,
+        }
+    }
+}
+
+#[async_trait]
+impl Bot for Router {
+    async fn handle(&self, ctx: &Context, event: Event) {
+        self.handle_event(ctx, event).await;
+    }
+
+    /// `Failed` naming the handlers that failed if any did, `Skipped` if
+    /// none took the event, and `Handled` otherwise.
+    async fn handle_event(&self, ctx: &Context, event: Event) -> Outcome {
+        let name = event.name();
+        let outcomes = self.dispatch(ctx, event).await;
+        let mut failures = Vec::new();
+        for handler in &outcomes {
+            if let Outcome::Failed(message) = &handler.outcome {
+                tracing::error!(handler = handler.name.as_str(), %message, "handler failed");
+                failures.push(format!("handler `{}`: {}", handler.name, message));
+            }
+        }
+        if !failures.is_empty() {
+            Outcome::Failed(failures.join("; "))
+        } else if outcomes.iter().any(|it| it.outcome == Outcome::Handled) {
+            Outcome::Handled
+        } else {
+            Outcome::Skipped(format!("no handler took {}", name))
+        }
+    }
+
+    fn receive_bot_events(&self) -> bool {
+        self.handlers
+            .iter()
+            .any(|(_, bot)| bot.receive_bot_events())
+    }
+
+    fn subscriptions(&self) -> Subscriptions {
+        self.handlers
+            .iter()
+            .fold(Subscriptions::none(), |subscriptions, (_, bot)| {
+                subscriptions.union(&bot.subscriptions())
+            })
+    }
+
+    fn permissions(&self) -> Manifest {
+        self.handlers
+            .iter()
+            .fold(Manifest::new(), |manifest, (_, bot)| {
+                manifest.union(&bot.permissions())
+            })
+    }
+}
@@ -0,0 +1,256 @@
+use crate::subscriptions::Subscriptions;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+/// A permission of the token the bot acts with, as granted to a GitHub App
+/// or in a workflow's `permissions:` block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Scope {
+    Contents,
+    Issues,
+    /// Organization members, which the workflow's own token cannot be
+    /// granted.
+    Members,
+    Metadata,
+    PullRequests,
+}
+
+impl Scope {
+    /// The name in a workflow's `permissions:` block, e.g. `pull-requests`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Contents => "contents",
+            Scope::Issues => "issues",
+            Scope::Members => "members",
+            Scope::Metadata => "metadata",
+            Scope::PullRequests => "pull-requests",
+        }
+    }
+
+    /// Parses workflow names and the API's, e.g. `pull_requests`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "contents" => Some(Scope::Contents),
+            "issues" => Some(Scope::Issues),
+            "members" => Some(Scope::Members),
+            "metadata" => Some(Scope::Metadata),
+            "pull-requests" | "pull_requests" => Some(Scope::PullRequests),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Access::Read => "read",
+            Access::Write => "write",
+        }
+    }
+}
+
+/// What each built-in operation needs, by `GitHubApi` or `Context` method.
+///
+/// Comments, labels and closing work on pull requests too, through the
+/// issues API, which accepts either scope there; `issues` is listed.
+pub const OPERATIONS: &[(&str, Scope, Access)] = &[
+    ("create_comment", Scope::Issues, Access::Write),
+    ("update_comment", Scope::Issues, Access::Write),
+    ("delete_comment", Scope::Issues, Access::Write),
+    ("upsert_comment", Scope::Issues, Access::Write),
+    ("add_labels", Scope::Issues, Access::Write),
+    ("remove_label", Scope::Issues, Access::Write),
+    ("close_issue", Scope::Issues, Access::Write),
+    ("create_review", Scope::PullRequests, Access::Write),
+    ("comments", Scope::Issues, Access::Read),
+    ("issue", Scope::Issues, Access::Read),
+    ("pull_request_files", Scope::PullRequests, Access::Read),
+    ("file", Scope::Contents, Access::Read),
+    ("put_file", Scope::Contents, Access::Write),
+    ("collaborator_permission", Scope::Metadata, Access::Read),
+    ("is_team_member", Scope::Members, Access::Read),
+];
+
+/// The scope and access `operation` needs, e.g. `create_comment`.
+pub fn required(operation: &str) -> Option<(Scope, Access)> {
+    OPERATIONS
+        .iter()
+        .find(|(name, _, _)| *name == operation)
+        .map(|(_, scope, access)| (*scope, *access))
+}
+
+/// The permissions a bot needs, with the operations needing them, see
+/// `Bot::permissions`; or those a token was granted.
+///
+/// ```ignore
+/// fn permissions(&self) -> Manifest {
+///     Manifest::new()
+///         .operation("upsert_comment")
+///         .operation("add_labels")
+///         .operation("file")
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    scopes: BTreeMap<Scope, (Access, BTreeSet<&'static str>)>,
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds what the built-in `operation` needs.
+    ///
+    /// # Panics
+    ///
+    /// If there is no such operation in `OPERATIONS`.
+    pub fn operation(self, operation: &'static str) -> Self {
+        match required(operation) {
+            Some((scope, access)) => self.scope(scope, access, operation),
+            None => panic!("unknown operation `{}`", operation),
+        }
+    }
+
+    /// Adds `access` to `scope`, needed for `reason`, e.g. a request the
+    /// bot makes itself.
+    pub fn scope(mut self, scope: Scope, access: Access, reason: &'static str) -> Self {
+        let entry = self
+            .scopes
+            .entry(scope)
+            .or_insert_with(|| (access, BTreeSet::new()));
+        entry.0 = entry.0.max(access);
+        if !reason.is_empty() {
+            entry.1.insert(reason);
+        }
+        self
+    }
+
+    /// A guess for bots that declare nothing: answering on the issues and
+    /// pull requests they subscribe to, and reading their configuration.
+    pub fn for_subscriptions(subscriptions: &Subscriptions) -> Self {
+        let triggers = subscriptions.triggers();
+        let mut manifest = Manifest::new().operation("file");
+        if triggers.contains_key("issues") || triggers.contains_key("issue_comment") {
+            manifest = manifest.operation("create_comment");
+        }
+        if triggers.contains_key("pull_request") || triggers.contains_key("issue_comment") {
+            manifest = manifest.scope(Scope::PullRequests, Access::Write, "create_comment");
+        }
+        manifest
+    }
+
+    /// What a GitHub App installation token was granted, as listed when it
+    /// was created, e.g. `"pull_requests": "write"`.
+    pub fn from_app(permissions: &HashMap<String, String>) -> Self {
+        permissions
+            .iter()
+            .fold(Manifest::new(), |manifest, (name, access)| {
+                let access = match access.as_str() {
+                    "read" => Access::Read,
+                    "write" | "admin" => Access::Write,
+                    _ => return manifest,
+                };
+                match Scope::from_name(name) {
+                    Some(scope) => manifest.scope(scope, access, ""),
+                    None => manifest,
+                }
+            })
+    }
+
+    /// What a personal access token with the `X-OAuth-Scopes` header
+    /// `scopes` was granted, e.g. `repo, read:org`. Any token reads public
+    /// repositories.
+    pub fn from_oauth_scopes(scopes: &str) -> Self {
+        let mut manifest = Manifest::new()
+            .scope(Scope::Contents, Access::Read, "")
+            .scope(Scope::Issues, Access::Read, "")
+            .scope(Scope::PullRequests, Access::Read, "");
+        for scope in scopes.split(',').map(str::trim) {
+            match scope {
+                "repo" | "public_repo" => {
+                    manifest = manifest
+                        .scope(Scope::Contents, Access::Write, "")
+                        .scope(Scope::Issues, Access::Write, "")
+                        .scope(Scope::PullRequests, Access::Write, "")
+                }
+                "read:org" | "write:org" | "admin:org" => {
+                    manifest = manifest.scope(Scope::Members, Access::Read, "")
+                }
+                _ => {}
+            }
+        }
+        manifest
+    }
+
+    pub fn union(mut self, other: &Manifest) -> Self {
+        for (scope, (access, reasons)) in &other.scopes {
+            let entry = self
+                .scopes
+                .entry(*scope)
+                .or_insert_with(|| (*access, BTreeSet::new()));
+            entry.0 = entry.0.max(*access);
+            entry.1.extend(reasons);
+        }
+        self
+    }
+
+    pub fn access(&self, scope: Scope) -> Option<Access> {
+        self.scopes.get(&scope).map(|(access, _)| *access)
+    }
+
+    pub fn scopes(&self) -> impl Iterator<Item = (Scope, Access)> + '_ {
+        self.scopes
+            .iter()
+            .map(|(scope, (access, _))| (*scope, *access))
+    }
+
+    /// What `granted` lacks of this manifest. `metadata: read` is always
+    /// granted.
+    pub fn missing(&self, granted: &Manifest) -> Option<Missing> {
+        let scopes: Vec<_> = self
+            .scopes
+            .iter()
+            .filter(|(scope, (access, _))| {
+                **scope != Scope::Metadata && granted.access(**scope) < Some(*access)
+            })
+            .map(|(scope, (access, reasons))| (*scope, *access, reasons.iter().copied().collect()))
+            .collect();
+        if scopes.is_empty() {
+            None
+        } else {
+            Some(Missing { scopes })
+        }
+    }
+}
+
+/// Permissions a token lacks, see `Manifest::missing`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Missing {
+    pub scopes: Vec<(Scope, Access, Vec<&'static str>)>,
+}
+
+impl fmt::Display for Missing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the token lacks ")?;
+        for (i, (scope, access, reasons)) in self.scopes.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "`{}: {}`", scope.as_str(), access.as_str())?;
+            if !reasons.is_empty() {
+                write!(f, " (for {})", reasons.join(", "))?;
+            }
+        }
+        write!(
+            f,
+            "; grant it to the token in `BAIPIAO_BOT_TOKEN` or in the app's settings"
+        )
+    }
+}
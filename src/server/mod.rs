@@ -0,0 +1,235 @@
+//! Runs a bot as a long-lived webhook receiver instead of a workflow, e.g.
+//! for a GitHub App:
+//!
+//! ```ignore
+//! #[tokio::main]
+//! async fn main() {
+//!     let dispatcher = Dispatcher::new(Triage)
+//!         .with_github(GitHub::new(Auth::from_env().unwrap()))
+//!         .with_login("baipiao-bot[bot]");
+//!     Server::new(dispatcher)
+//!         .secret(std::env::var("BAIPIAO_WEBHOOK_SECRET").unwrap())
+//!         .serve(([0, 0, 0, 0], 3000).into())
+//!         .await
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! Webhooks are accepted on any path with `POST`, and the dispatcher's
+//! metrics are served on `GET /metrics`.
+//!
+//! GitHub gives up on a delivery after 10 seconds, so a server whose
+//! handlers can be slower should queue events and answer right away:
+//!
+//! ```ignore
+//! Server::new(dispatcher)
+//!     .queue(Queue::new().concurrency(8).spool("/var/lib/baipiao/spool"))
+//!     .shutdown_on(async {
+//!         tokio::signal::ctrl_c().await.ok();
+//!     })
+//!     .serve(([0, 0, 0, 0], 3000).into())
+//!     .await
+//!     .unwrap();
+//! ```
+
+mod queue;
+
+pub use queue::{OrderBy, Queue, QueueError};
+
+use crate::layers::Outcome;
+use crate::metrics::Metrics;
+use crate::{payload, Bot, Dispatcher};
+use futures::future::{BoxFuture, FutureExt};
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, StatusCode};
+use queue::WorkQueue;
+use ring::hmac;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use tracing::Instrument;
+
+pub struct Server<B: Bot> {
+    dispatcher: Arc<Dispatcher<B>>,
+    secret: Option<hmac::Key>,
+    queue: Option<WorkQueue<B>>,
+    // only behind a lock for `Server` to be `Sync`, which serving needs
+    shutdown: Mutex<Option<BoxFuture<'static, ()>>>,
+}
+
+impl<B: Bot + 'static> Server<B> {
+    pub fn new(dispatcher: Dispatcher<B>) -> Self {
+        Server {
+            dispatcher: Arc::new(dispatcher),
+            secret: None,
+            queue: None,
+            shutdown: Mutex::new(None),
+        }
+    }
+
+    /// Rejects deliveries not signed with the app's webhook `secret`.
+    pub fn secret(mut self, secret: impl AsRef<[u8]>) -> Self {
+        self.secret = Some(hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref()));
+        self
+    }
+
+    /// Queues events and answers `202 Accepted` before dispatching them,
+    /// instead of answering once they are dispatched.
+    pub fn queue(mut self, queue: Queue) -> Self {
+        self.queue = Some(WorkQueue::new(self.dispatcher.clone(), queue));
+        self
+    }
+
+    /// Stops accepting connections once `signal` completes, then waits for
+    /// the queued events to be dispatched before `serve` returns.
+    pub fn shutdown_on(mut self, signal: impl Future<Output = ()> + Send + 'static) -> Self {
+        self.shutdown = Mutex::new(Some(signal.boxed()));
+        self
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        self.dispatcher.metrics()
+    }
+
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), hyper::Error> {
+        self.run(hyper::Server::try_bind(&addr)?).await
+    }
+
+    /// Serves on a listener bound beforehand, e.g. to port 0 in tests.
+    pub async fn serve_on(self, listener: TcpListener) -> Result<(), hyper::Error> {
+        self.run(hyper::Server::from_tcp(listener)?).await
+    }
+
+    async fn run(
+        mut self,
+        builder: hyper::server::Builder<AddrIncoming>,
+    ) -> Result<(), hyper::Error> {
+        if let Some(queue) = self.queue.clone() {
+            // a spool larger than the queue must not hold up serving
+            tokio::spawn(async move {
+                match queue.resume().await {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!(count, "resumed spooled events"),
+                    Err(err) => tracing::error!(%err, "cannot read the spool"),
+                }
+            });
+        }
+        let shutdown = self.shutdown.get_mut().unwrap().take();
+        let server = Arc::new(self);
+        let handler = server.clone();
+        let service = make_service_fn(move |_| {
+            let server = handler.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request).await) }
+                }))
+            }
+        });
+        let served = match shutdown {
+            Some(signal) => builder.serve(service).with_graceful_shutdown(signal).await,
+            None => builder.serve(service).await,
+        };
+        if let Some(queue) = &server.queue {
+            queue.drain().await;
+        }
+        served
+    }
+
+    /// Answers one request.
+    pub async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        match (request.method(), request.uri().path()) {
+            (&Method::GET, "/metrics") => Response::builder()
+                .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+                .body(Body::from(self.metrics().render()))
+                .unwrap(),
+            (&Method::POST, _) => self.receive(request).await,
+            _ => reply(StatusCode::NOT_FOUND, "not found"),
+        }
+    }
+
+    async fn receive(&self, request: Request<Body>) -> Response<Body> {
+        let header = |name: &str| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|it| it.to_str().ok())
+                .map(str::to_string)
+        };
+        let event_name = header("x-github-event");
+        let delivery = header("x-github-delivery").unwrap_or_default();
+        let signature = header("x-hub-signature-256");
+        let body = match hyper::body::to_bytes(request.into_body()).await {
+            Ok(body) => body,
+            Err(err) => return reply(StatusCode::BAD_REQUEST, &err.to_string()),
+        };
+        if let Some(secret) = &self.secret {
+            if !verify(secret, &body, signature.as_deref()) {
+                return reply(StatusCode::UNAUTHORIZED, "invalid signature");
+            }
+        }
+        let event_name = match event_name {
+            Some(event_name) => event_name,
+            None => return reply(StatusCode::BAD_REQUEST, "missing X-GitHub-Event"),
+        };
+        let event: serde_json::Value = match serde_json::from_slice(&body) {
+            Ok(event) => event,
+            Err(err) => return reply(StatusCode::BAD_REQUEST, &err.to_string()),
+        };
+        // e.g. `ping` and `installation`, which the dispatcher cannot place
+        if !event["repository"]["full_name"].is_string() {
+            return reply(StatusCode::OK, "skipped, not about a repository");
+        }
+        let payload = payload::wrap(&event_name, event);
+        if let Some(queue) = &self.queue {
+            return match queue.push(delivery, payload) {
+                Ok(()) => reply(StatusCode::ACCEPTED, "queued"),
+                Err(err @ QueueError::Spool(_)) => {
+                    tracing::error!(%err, "cannot queue the event");
+                    reply(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string())
+                }
+                Err(err) => reply(StatusCode::SERVICE_UNAVAILABLE, &err.to_string()),
+            };
+        }
+        let span = tracing::info_span!("webhook", delivery = delivery.as_str());
+        let outcome = self
+            .dispatcher
+            .dispatch_event(payload)
+            .instrument(span)
+            .await;
+        let status = match outcome {
+            Outcome::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::OK,
+        };
+        reply(status, &outcome.to_string())
+    }
+}
+
+fn reply(status: StatusCode, text: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(format!("{}\n", text)))
+        .unwrap()
+}
+
+/// Checks an `X-Hub-Signature-256` header, `sha256=` and the hex HMAC of
+/// the body.
+fn verify(secret: &hmac::Key, body: &[u8], signature: Option<&str>) -> bool {
+    let hex = match signature.and_then(|it| it.strip_prefix("sha256=")) {
+        Some(hex) if hex.len() % 2 == 0 => hex,
+        _ => return false,
+    };
+    let bytes: Option<Vec<u8>> = (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|it| u8::from_str_radix(it, 16).ok())
+        })
+        .collect();
+    match bytes {
+        Some(bytes) => hmac::verify(secret, body, &bytes).is_ok(),
+        None => false,
+    }
+}
@@ -0,0 +1,298 @@
+use crate::{Bot, Dispatcher};
+use futures::FutureExt;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fmt, fs, io};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::Instrument;
+
+/// Which events are dispatched one after another, in the order they were
+/// received; others may run at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderBy {
+    /// Events of the same repository.
+    Repository,
+    /// Events of the same issue or pull request, and those of the same
+    /// repository that are about neither.
+    Issue,
+}
+
+impl OrderBy {
+    fn key(&self, payload: &Value) -> String {
+        let repo = payload["repository"].as_str().unwrap_or_default();
+        let event = &payload["event"];
+        let number = event["issue"]["number"]
+            .as_u64()
+            .or_else(|| event["pull_request"]["number"].as_u64());
+        match (self, number) {
+            (OrderBy::Issue, Some(number)) => format!("{}#{}", repo, number),
+            _ => repo.to_string(),
+        }
+    }
+}
+
+/// How `Server::queue` holds events between receiving and dispatching
+/// them, so that the webhook is answered right away.
+#[derive(Debug, Clone)]
+pub struct Queue {
+    capacity: usize,
+    concurrency: usize,
+    order_by: OrderBy,
+    spool: Option<PathBuf>,
+}
+
+impl Default for Queue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Queue {
+    /// Up to 100 events, 4 dispatched at a time, ordered by issue and kept
+    /// in memory only.
+    pub fn new() -> Self {
+        Queue {
+            capacity: 100,
+            concurrency: 4,
+            order_by: OrderBy::Issue,
+            spool: None,
+        }
+    }
+
+    /// How many events may wait or be dispatched at once; more are turned
+    /// away with `503 Service Unavailable`.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn order_by(mut self, order_by: OrderBy) -> Self {
+        self.order_by = order_by;
+        self
+    }
+
+    /// Writes each event to `dir` until it is dispatched, and dispatches
+    /// those left there by a previous run when the server starts.
+    pub fn spool(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spool = Some(dir.into());
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum QueueError {
+    Full,
+    /// The server is shutting down.
+    Closed,
+    Spool(io::Error),
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueError::Full => write!(f, "the queue is full"),
+            QueueError::Closed => write!(f, "the server is shutting down"),
+            QueueError::Spool(err) => write!(f, "cannot spool the event: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for QueueError {}
+
+struct Job {
+    delivery: String,
+    payload: Value,
+    spooled: Option<PathBuf>,
+    _permit: OwnedSemaphorePermit,
+}
+
+struct Shared<B: Bot> {
+    dispatcher: Arc<Dispatcher<B>>,
+    config: Queue,
+    /// Events waiting behind the one being dispatched, by `OrderBy` key.
+    pending: Mutex<HashMap<String, VecDeque<Job>>>,
+    capacity: Arc<Semaphore>,
+    workers: Semaphore,
+    closed: AtomicBool,
+    sequence: AtomicU64,
+}
+
+/// A running `Queue`: one task per key works through that key's events.
+pub(crate) struct WorkQueue<B: Bot> {
+    shared: Arc<Shared<B>>,
+}
+
+impl<B: Bot> Clone for WorkQueue<B> {
+    fn clone(&self) -> Self {
+        WorkQueue {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<B: Bot + 'static> WorkQueue<B> {
+    pub fn new(dispatcher: Arc<Dispatcher<B>>, config: Queue) -> Self {
+        WorkQueue {
+            shared: Arc::new(Shared {
+                dispatcher,
+                capacity: Arc::new(Semaphore::new(config.capacity)),
+                workers: Semaphore::new(config.concurrency),
+                config,
+                pending: Mutex::new(HashMap::new()),
+                closed: AtomicBool::new(false),
+                sequence: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Queues `payload`, in the `github` context shape, of the webhook
+    /// `delivery`.
+    pub fn push(&self, delivery: String, payload: Value) -> Result<(), QueueError> {
+        if self.shared.closed.load(Ordering::SeqCst) {
+            return Err(QueueError::Closed);
+        }
+        let permit = match self.shared.capacity.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => return Err(QueueError::Full),
+        };
+        let spooled = match &self.shared.config.spool {
+            Some(dir) => Some(
+                self.write_spool(dir, &delivery, &payload)
+                    .map_err(QueueError::Spool)?,
+            ),
+            None => None,
+        };
+        self.schedule(Job {
+            delivery,
+            payload,
+            spooled,
+            _permit: permit,
+        });
+        Ok(())
+    }
+
+    fn write_spool(&self, dir: &Path, delivery: &str, payload: &Value) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        // names sort in the order events were received
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let sequence = self.shared.sequence.fetch_add(1, Ordering::SeqCst);
+        let name = format!("{:020}-{:06}.json", nanos, sequence);
+        let temporary = dir.join(format!(".{}.tmp", name));
+        let spooled = json!({ "delivery": delivery, "payload": payload });
+        fs::write(&temporary, serde_json::to_vec(&spooled)?)?;
+        let path = dir.join(name);
+        fs::rename(&temporary, &path)?;
+        Ok(path)
+    }
+
+    /// Queues the events a previous run left in the spool, returning how
+    /// many there were. Waits for room when there are more of them than the
+    /// queue holds, and moves those that cannot be read aside, as
+    /// `*.json.failed`.
+    pub async fn resume(&self) -> io::Result<usize> {
+        let dir = match &self.shared.config.spool {
+            Some(dir) if dir.exists() => dir,
+            _ => return Ok(0),
+        };
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+            .map(|entry| entry.map(|it| it.path()))
+            .collect::<io::Result<_>>()?;
+        paths.retain(|path| {
+            path.extension().is_some_and(|it| it == "json")
+                && !path.file_name().unwrap().to_string_lossy().starts_with('.')
+        });
+        paths.sort();
+        let mut resumed = 0;
+        for path in paths {
+            let mut spooled = match read_spooled(&path) {
+                Ok(spooled) => spooled,
+                Err(err) => {
+                    tracing::error!(%err, path = %path.display(), "cannot read a spooled event");
+                    if let Err(err) = fs::rename(&path, path.with_extension("json.failed")) {
+                        tracing::error!(%err, path = %path.display(), "cannot move it aside");
+                    }
+                    continue;
+                }
+            };
+            let permit = match self.shared.capacity.clone().acquire_owned().await {
+                Ok(permit) if !self.shared.closed.load(Ordering::SeqCst) => permit,
+                // the rest stays spooled for the next run
+                _ => break,
+            };
+            self.schedule(Job {
+                delivery: spooled["delivery"].as_str().unwrap_or_default().to_string(),
+                payload: spooled["payload"].take(),
+                spooled: Some(path),
+                _permit: permit,
+            });
+            resumed += 1;
+        }
+        Ok(resumed)
+    }
+
+    fn schedule(&self, job: Job) {
+        let key = self.shared.config.order_by.key(&job.payload);
+        let mut pending = self.shared.pending.lock().unwrap();
+        if let Some(waiting) = pending.get_mut(&key) {
+            waiting.push_back(job);
+            return;
+        }
+        pending.insert(key.clone(), VecDeque::new());
+        tokio::spawn(Self::work(self.shared.clone(), key, job));
+    }
+
+    async fn work(shared: Arc<Shared<B>>, key: String, mut job: Job) {
+        loop {
+            {
+                let _worker = shared.workers.acquire().await.unwrap();
+                let span = tracing::info_span!("webhook", delivery = job.delivery.as_str());
+                let dispatch = shared
+                    .dispatcher
+                    .dispatch_event(job.payload.take())
+                    .instrument(span);
+                // a panicking bot must not strand the events behind it
+                if AssertUnwindSafe(dispatch).catch_unwind().await.is_err() {
+                    tracing::error!(key = key.as_str(), "dispatch panicked");
+                }
+            }
+            if let Some(path) = &job.spooled {
+                if let Err(err) = fs::remove_file(path) {
+                    tracing::warn!(%err, path = %path.display(), "cannot remove spooled event");
+                }
+            }
+            let mut pending = shared.pending.lock().unwrap();
+            job = match pending.get_mut(&key).and_then(VecDeque::pop_front) {
+                Some(next) => next,
+                None => {
+                    pending.remove(&key);
+                    return;
+                }
+            };
+        }
+    }
+
+    /// Turns new events away and waits for the queued ones to be
+    /// dispatched.
+    pub async fn drain(&self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        let all = self.shared.config.capacity as u32;
+        let _ = self.shared.capacity.acquire_many(all).await;
+    }
+}
+
+fn read_spooled(path: &Path) -> io::Result<Value> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
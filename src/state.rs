@@ -0,0 +1,388 @@
+use crate::client::{ClientError, Comment};
+use crate::{GitHubApi, Repository};
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::{fmt, fs, io};
+use tokio::sync::Mutex;
+
+#[derive(Debug)]
+pub enum StateError {
+    Client(ClientError),
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// The value was changed since it was read.
+    Conflict {
+        key: String,
+    },
+    /// Keys may only contain ASCII letters, digits, `-`, `_` and `.`.
+    InvalidKey(String),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Client(err) => write!(f, "{}", err),
+            StateError::Io(err) => write!(f, "cannot access state: {}", err),
+            StateError::Json(err) => write!(f, "invalid state: {}", err),
+            StateError::Conflict { key } => write!(f, "state `{}` was changed concurrently", key),
+            StateError::InvalidKey(key) => write!(f, "invalid state key `{}`", key),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<ClientError> for StateError {
+    fn from(err: ClientError) -> Self {
+        StateError::Client(err)
+    }
+}
+
+impl From<io::Error> for StateError {
+    fn from(err: io::Error) -> Self {
+        StateError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for StateError {
+    fn from(err: serde_json::Error) -> Self {
+        StateError::Json(err)
+    }
+}
+
+/// A value with the version it was read at, to be passed back to `put`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Versioned<T> {
+    pub value: T,
+    pub version: String,
+}
+
+/// Remembers JSON values between runs, such as vote counts or who was
+/// already welcomed.
+///
+/// Writes are optimistic: `store` is given the version the value was read
+/// at (`None` when it did not exist) and fails with `StateError::Conflict`
+/// if it changed meanwhile. `StateStoreExt` adds typed access on top.
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    async fn load(&self, key: &str) -> Result<Option<Versioned<Value>>, StateError>;
+
+    /// Writes `value`, returning its new version.
+    async fn store(
+        &self,
+        key: &str,
+        value: Value,
+        expected: Option<&str>,
+    ) -> Result<String, StateError>;
+}
+
+/// Typed access to any `StateStore`.
+#[async_trait]
+pub trait StateStoreExt: StateStore {
+    async fn get<T: DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> Result<Option<Versioned<T>>, StateError> {
+        match self.load(key).await? {
+            Some(Versioned { value, version }) => Ok(Some(Versioned {
+                value: serde_json::from_value(value)?,
+                version,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    async fn put<T: Serialize + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+        expected: Option<&str>,
+    ) -> Result<String, StateError> {
+        self.store(key, serde_json::to_value(value)?, expected)
+            .await
+    }
+
+    /// Reads, changes and writes the value of `key`, starting over when
+    /// another run wrote it in between, up to three times.
+    async fn update<T, F>(&self, key: &str, mut change: F) -> Result<T, StateError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+        F: FnMut(Option<T>) -> T + Send,
+    {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let (value, version) = match self.get(key).await? {
+                Some(Versioned { value, version }) => (Some(value), Some(version)),
+                None => (None, None),
+            };
+            let value = change(value);
+            match self.put(key, &value, version.as_deref()).await {
+                Ok(_) => return Ok(value),
+                Err(StateError::Conflict { .. }) if attempt < 3 => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl<S: StateStore + ?Sized> StateStoreExt for S {}
+
+fn check_key(key: &str) -> Result<(), StateError> {
+    let valid = !key.is_empty()
+        && !key.starts_with('.')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(StateError::InvalidKey(key.to_string()))
+    }
+}
+
+/// Keeps each key as hidden JSON in a comment the bot owns on one issue,
+/// e.g. a pinned "bot state" issue. The version is the comment's last edit
+/// time.
+///
+/// GitHub cannot edit comments conditionally, so the version is checked
+/// right before writing; two runs writing within the same second may still
+/// overwrite each other.
+pub struct CommentStore {
+    github: Arc<dyn GitHubApi>,
+    repo: Repository,
+    issue_id: usize,
+    login: String,
+}
+
+impl CommentStore {
+    /// Only comments by `login`, usually `ctx.login`, are trusted: anyone
+    /// can write a comment that looks like the state.
+    pub fn new(
+        github: Arc<dyn GitHubApi>,
+        repo: Repository,
+        issue_id: usize,
+        login: impl Into<String>,
+    ) -> Self {
+        CommentStore {
+            github,
+            repo,
+            issue_id,
+            login: login.into(),
+        }
+    }
+
+    fn marker(key: &str) -> String {
+        format!("<!-- baipiao-state:{}\n", key)
+    }
+
+    async fn find(&self, key: &str) -> Result<Option<Comment>, StateError> {
+        let marker = Self::marker(key);
+        let comments = self.github.comments(&self.repo, self.issue_id).await?;
+        Ok(comments.into_iter().find(|comment| {
+            comment.body.starts_with(&marker)
+                && comment.user.login.eq_ignore_ascii_case(&self.login)
+        }))
+    }
+
+    fn version(comment: &Comment) -> String {
+        match comment.updated_at {
+            Some(updated_at) => updated_at.to_rfc3339(),
+            None => String::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl StateStore for CommentStore {
+    async fn load(&self, key: &str) -> Result<Option<Versioned<Value>>, StateError> {
+        check_key(key)?;
+        let comment = match self.find(key).await? {
+            Some(comment) => comment,
+            None => return Ok(None),
+        };
+        let json = comment.body[Self::marker(key).len()..]
+            .trim_end()
+            .trim_end_matches("-->");
+        Ok(Some(Versioned {
+            value: serde_json::from_str(json)?,
+            version: Self::version(&comment),
+        }))
+    }
+
+    async fn store(
+        &self,
+        key: &str,
+        value: Value,
+        expected: Option<&str>,
+    ) -> Result<String, StateError> {
+        check_key(key)?;
+        let current = self.find(key).await?;
+        if current.as_ref().map(Self::version).as_deref() != expected {
+            return Err(StateError::Conflict {
+                key: key.to_string(),
+            });
+        }
+        // `>` only occurs inside JSON strings, where it can be escaped, so
+        // the value cannot end the HTML comment early
+        let json = serde_json::to_string(&value)?.replace('>', "\\u003e");
+        let body = format!("{}{}\n-->", Self::marker(key), json);
+        let comment = match current {
+            Some(comment) => {
+                self.github
+                    .update_comment(&self.repo, comment.id, &body)
+                    .await?
+            }
+            None => {
+                self.github
+                    .create_comment(&self.repo, self.issue_id, &body)
+                    .await?
+            }
+        };
+        Ok(Self::version(&comment))
+    }
+}
+
+/// Keeps each key as `<key>.json` on a branch of the repository, which must
+/// exist, e.g. one made with `git switch --orphan baipiao-state`. The
+/// version is the file's blob SHA, so concurrent writes are rejected by
+/// GitHub itself.
+pub struct BranchStore {
+    github: Arc<dyn GitHubApi>,
+    repo: Repository,
+    branch: String,
+}
+
+impl BranchStore {
+    pub fn new(github: Arc<dyn GitHubApi>, repo: Repository, branch: impl Into<String>) -> Self {
+        BranchStore {
+            github,
+            repo,
+            branch: branch.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl StateStore for BranchStore {
+    async fn load(&self, key: &str) -> Result<Option<Versioned<Value>>, StateError> {
+        check_key(key)?;
+        let path = format!("{}.json", key);
+        match self
+            .github
+            .file(&self.repo, &path, Some(&self.branch))
+            .await?
+        {
+            Some(file) => Ok(Some(Versioned {
+                value: serde_json::from_str(&file.content)?,
+                version: file.sha,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    async fn store(
+        &self,
+        key: &str,
+        value: Value,
+        expected: Option<&str>,
+    ) -> Result<String, StateError> {
+        check_key(key)?;
+        let path = format!("{}.json", key);
+        let content = serde_json::to_string_pretty(&value)?;
+        let message = format!("Update {}", key);
+        match self
+            .github
+            .put_file(
+                &self.repo,
+                &self.branch,
+                &path,
+                &content,
+                expected,
+                &message,
+            )
+            .await
+        {
+            Ok(sha) => Ok(sha),
+            // 409 for an outdated SHA, 422 when the file exists but no SHA
+            // was given
+            Err(ClientError::Status {
+                status: 409 | 422, ..
+            }) => Err(StateError::Conflict {
+                key: key.to_string(),
+            }),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    version: u64,
+    value: Value,
+}
+
+/// Keeps each key as `<key>.json` in a local directory, for tests and
+/// long-running servers. Versions are counters, and writes through the same
+/// store are serialized.
+pub struct DirStore {
+    dir: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl DirStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DirStore {
+            dir: dir.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn read(&self, key: &str) -> Result<Option<Stored>, StateError> {
+        match fs::read_to_string(self.dir.join(format!("{}.json", key))) {
+            Ok(text) => Ok(Some(serde_json::from_str(&text)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl StateStore for DirStore {
+    async fn load(&self, key: &str) -> Result<Option<Versioned<Value>>, StateError> {
+        check_key(key)?;
+        Ok(self.read(key)?.map(|stored| Versioned {
+            value: stored.value,
+            version: stored.version.to_string(),
+        }))
+    }
+
+    async fn store(
+        &self,
+        key: &str,
+        value: Value,
+        expected: Option<&str>,
+    ) -> Result<String, StateError> {
+        check_key(key)?;
+        let _guard = self.lock.lock().await;
+        let current = self.read(key)?.map(|stored| stored.version);
+        if current.map(|it| it.to_string()).as_deref() != expected {
+            return Err(StateError::Conflict {
+                key: key.to_string(),
+            });
+        }
+        let stored = Stored {
+            version: current.unwrap_or(0) + 1,
+            value,
+        };
+        fs::create_dir_all(&self.dir)?;
+        // write then rename, so that readers never see half a file
+        let temporary = self.dir.join(format!(".{}.json.tmp", key));
+        fs::write(&temporary, serde_json::to_vec_pretty(&stored)?)?;
+        fs::rename(&temporary, self.dir.join(format!("{}.json", key)))?;
+        Ok(stored.version.to_string())
+    }
+}
@@ -0,0 +1,207 @@
+use crate::scopes::{Manifest, Scope};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Each event a bot can handle, by `Event::name`, with the workflow event
+/// and activity type it comes from.
+pub const EVENTS: &[(&str, &str, &str)] = &[
+    ("issue_created", "issues", "opened"),
+    ("issue_updated", "issues", "edited"),
+    ("issue_closed", "issues", "closed"),
+    ("issue_reopened", "issues", "reopened"),
+    ("pull_request_created", "pull_request", "opened"),
+    ("pull_request_updated", "pull_request", "edited"),
+    ("pull_request_closed", "pull_request", "closed"),
+    ("comment_created", "issue_comment", "created"),
+    ("comment_updated", "issue_comment", "edited"),
+    ("comment_deleted", "issue_comment", "deleted"),
+];
+
+/// Where commands are looked for: new issues, pull requests and comments.
+const COMMAND_EVENTS: &[&str] = &["issue_created", "pull_request_created", "comment_created"];
+
+/// The events a bot handles, see `Bot::subscriptions`, e.g.
+///
+/// ```ignore
+/// fn subscriptions(&self) -> Subscriptions {
+///     Subscriptions::none()
+///         .event("issue_created")
+///         .event("pull_request_closed")
+///         .commands()
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Subscriptions {
+    events: BTreeSet<&'static str>,
+}
+
+impl Subscriptions {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Every event in `EVENTS`.
+    pub fn all() -> Self {
+        Subscriptions {
+            events: EVENTS.iter().map(|(name, _, _)| *name).collect(),
+        }
+    }
+
+    /// Adds the event named `name`, e.g. `issue_created`.
+    ///
+    /// # Panics
+    ///
+    /// If there is no such event in `EVENTS`.
+    pub fn event(mut self, name: &str) -> Self {
+        match EVENTS.iter().find(|(it, _, _)| *it == name) {
+            Some((name, _, _)) => self.events.insert(name),
+            None => panic!("unknown event `{}`", name),
+        };
+        self
+    }
+
+    /// Adds the events commands are read from.
+    pub fn commands(self) -> Self {
+        COMMAND_EVENTS
+            .iter()
+            .fold(self, |subscriptions, name| subscriptions.event(name))
+    }
+
+    pub fn union(mut self, other: &Subscriptions) -> Self {
+        self.events.extend(&other.events);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Whether the event named `name`, e.g. `issue_created`, is subscribed
+    /// to.
+    pub fn has(&self, name: &str) -> bool {
+        self.events.contains(name)
+    }
+
+    /// The subscribed events, by `Event::name`.
+    pub fn events(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.events.iter().copied()
+    }
+
+    /// Whether the workflow event `event_name` with `action` is subscribed
+    /// to, e.g. `("pull_request", "opened")`.
+    pub fn contains(&self, event_name: &str, action: &str) -> bool {
+        EVENTS.iter().any(|(name, event, it)| {
+            *event == event_name && *it == action && self.events.contains(name)
+        })
+    }
+
+    /// The activity types to subscribe to, by workflow event.
+    pub fn triggers(&self) -> BTreeMap<&'static str, Vec<&'static str>> {
+        let mut triggers: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for (name, event, action) in EVENTS {
+            if self.events.contains(name) {
+                triggers.entry(*event).or_default().push(*action);
+            }
+        }
+        triggers
+    }
+}
+
+/// The scopes a workflow's token can be granted; `metadata` always is.
+const WORKFLOW_SCOPES: &[Scope] = &[Scope::Contents, Scope::Issues, Scope::PullRequests];
+
+enum Step {
+    Docker(String),
+    Run(String),
+}
+
+/// A GitHub Actions workflow running the bot on exactly the events it
+/// handles, e.g.
+///
+/// ```ignore
+/// let yaml = WorkflowFile::new()
+///     .docker("ghcr.io/me/my-bot:latest")
+///     .render(&dispatcher.subscriptions(), &dispatcher.permissions());
+/// ```
+///
+/// The bot gets the event as `toJson(github)` in the `JSON` variable, and
+/// its credentials from the `BAIPIAO_BOT_TOKEN` secret, or without it the
+/// workflow's `GITHUB_TOKEN`, which is what the `permissions:` block limits.
+pub struct WorkflowFile {
+    name: String,
+    step: Step,
+}
+
+impl Default for WorkflowFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WorkflowFile {
+    /// Checks out the repository and runs the bot with `cargo run --release`.
+    pub fn new() -> Self {
+        WorkflowFile {
+            name: "baipiao".to_string(),
+            step: Step::Run("cargo run --release".to_string()),
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Runs the bot from a Docker image instead, e.g.
+    /// `baipiaobot/baipiao-bot-rust:latest`.
+    pub fn docker(mut self, image: impl Into<String>) -> Self {
+        self.step = Step::Docker(image.into());
+        self
+    }
+
+    /// Runs `command` in the checked out repository instead.
+    pub fn run(mut self, command: impl Into<String>) -> Self {
+        self.step = Step::Run(command.into());
+        self
+    }
+
+    /// Lists `subscriptions` under `on:`, and `permissions` as far as a
+    /// workflow can grant them.
+    pub fn render(&self, subscriptions: &Subscriptions, permissions: &Manifest) -> String {
+        let mut yaml = String::new();
+        yaml.push_str(
+            "# Generated from the bot's subscriptions, regenerate it when its handlers change.\n",
+        );
+        yaml.push_str(&format!("name: {}\non:\n", self.name));
+        for (event, actions) in subscriptions.triggers() {
+            yaml.push_str(&format!(
+                "  {}:\n    types: [{}]\n",
+                event,
+                actions.join(", ")
+            ));
+        }
+        yaml.push_str("\n# only limits GITHUB_TOKEN, used when BAIPIAO_BOT_TOKEN is not set\n");
+        yaml.push_str("permissions:\n");
+        let granted = permissions
+            .scopes()
+            .filter(|(scope, _)| WORKFLOW_SCOPES.contains(scope));
+        for (scope, access) in granted {
+            yaml.push_str(&format!("  {}: {}\n", scope.as_str(), access.as_str()));
+        }
+        yaml.push_str("\njobs:\n  run-baipiao-bot:\n    runs-on: ubuntu-latest\n    steps:\n");
+        match &self.step {
+            Step::Docker(image) => yaml.push_str(&format!(
+                "      - name: Run the bot\n        uses: docker://{}\n",
+                image
+            )),
+            Step::Run(command) => yaml.push_str(&format!(
+                "      - uses: actions/checkout@v4\n      - name: Run the bot\n        run: {}\n",
+                command
+            )),
+        }
+        yaml.push_str("        env:\n");
+        yaml.push_str("          JSON: ${{ toJson(github) }}\n");
+        yaml.push_str("          BAIPIAO_BOT_TOKEN: ${{ secrets.BAIPIAO_BOT_TOKEN }}\n");
+        yaml.push_str("          GITHUB_TOKEN: ${{ secrets.GITHUB_TOKEN }}\n");
+        yaml
+    }
+}
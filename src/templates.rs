@@ -0,0 +1,414 @@
+use crate::{Context, Event};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// No template with this name.
+    Unknown(String),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            TemplateError::Unknown(name) => write!(f, "no template named `{}`", name),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Var(String),
+    If {
+        path: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Each {
+        path: String,
+        body: Vec<Node>,
+    },
+}
+
+/// A Markdown template, e.g.
+///
+/// ```text
+/// Thanks @{{user}}!
+/// {{#if labels}}Labels: {{#each labels}}`{{this}}` {{/each}}{{else}}No labels.{{/if}}
+/// ```
+///
+/// `{{a.b}}` looks up nested fields; inside `{{#each}}`, `{{this}}` is the
+/// current item and names are looked up in it before the outer variables.
+/// `{{#if}}` is false for missing values, `null`, `false`, `0`, `""` and
+/// `[]`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let mut parser = Parser { source, offset: 0 };
+        let (nodes, end) = parser.nodes()?;
+        match end {
+            None => Ok(Template { nodes }),
+            Some(tag) => Err(parser.error(format!("unexpected `{{{{{}}}}}`", tag))),
+        }
+    }
+
+    pub fn render(&self, vars: &Value) -> String {
+        let mut output = String::new();
+        render(&self.nodes, &[vars], &mut output);
+        output
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = TemplateError;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Template::parse(&source)
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: String) -> TemplateError {
+        TemplateError::Syntax {
+            line: self.source[..self.offset].matches('\n').count() + 1,
+            message,
+        }
+    }
+
+    /// Parses until the end of the source or a closing tag (`else`, `/if`,
+    /// `/each`), which is returned.
+    fn nodes(&mut self) -> Result<(Vec<Node>, Option<&'a str>), TemplateError> {
+        let mut nodes = Vec::new();
+        loop {
+            let rest = &self.source[self.offset..];
+            let start = match rest.find("{{") {
+                Some(start) => start,
+                None => {
+                    if !rest.is_empty() {
+                        nodes.push(Node::Text(rest.to_string()));
+                    }
+                    self.offset = self.source.len();
+                    return Ok((nodes, None));
+                }
+            };
+            if start > 0 {
+                nodes.push(Node::Text(rest[..start].to_string()));
+            }
+            self.offset += start;
+            let end = match rest[start..].find("}}") {
+                Some(end) => end,
+                None => return Err(self.error("unclosed `{{`".to_string())),
+            };
+            let tag = rest[start + 2..start + end].trim();
+            self.offset += end + 2;
+            if let Some(path) = tag.strip_prefix("#if ") {
+                let (then, end) = self.nodes()?;
+                let otherwise = match end {
+                    Some("else") => match self.nodes()? {
+                        (otherwise, Some("/if")) => otherwise,
+                        _ => return Err(self.error("`{{#if}}` is not closed".to_string())),
+                    },
+                    Some("/if") => Vec::new(),
+                    _ => return Err(self.error("`{{#if}}` is not closed".to_string())),
+                };
+                nodes.push(Node::If {
+                    path: path.trim().to_string(),
+                    then,
+                    otherwise,
+                });
+            } else if let Some(path) = tag.strip_prefix("#each ") {
+                match self.nodes()? {
+                    (body, Some("/each")) => nodes.push(Node::Each {
+                        path: path.trim().to_string(),
+                        body,
+                    }),
+                    _ => return Err(self.error("`{{#each}}` is not closed".to_string())),
+                }
+            } else if tag == "else" || tag.starts_with('/') {
+                return Ok((nodes, Some(tag)));
+            } else if tag.is_empty() || tag.starts_with('#') {
+                return Err(self.error(format!("unknown tag `{{{{{}}}}}`", tag)));
+            } else {
+                nodes.push(Node::Var(tag.to_string()));
+            }
+        }
+    }
+}
+
+/// Looks `path` up in the innermost scope that has its first segment.
+fn lookup<'a>(scopes: &[&'a Value], path: &str) -> Option<&'a Value> {
+    if path == "this" {
+        return scopes.last().copied();
+    }
+    let mut segments = path.split('.');
+    let first = segments.next()?;
+    let mut value = scopes.iter().rev().find_map(|scope| scope.get(first))?;
+    for segment in segments {
+        value = value.get(segment)?;
+    }
+    Some(value)
+}
+
+fn is_truthy(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => false,
+        Some(Value::Bool(value)) => *value,
+        Some(Value::Number(number)) => number.as_f64() != Some(0.0),
+        Some(Value::String(string)) => !string.is_empty(),
+        Some(Value::Array(array)) => !array.is_empty(),
+        Some(Value::Object(_)) => true,
+    }
+}
+
+fn write_value(value: &Value, output: &mut String) {
+    match value {
+        Value::Null => {}
+        Value::String(string) => output.push_str(string),
+        Value::Array(array) => {
+            for (i, item) in array.iter().enumerate() {
+                if i != 0 {
+                    output.push_str(", ");
+                }
+                write_value(item, output);
+            }
+        }
+        value => output.push_str(&value.to_string()),
+    }
+}
+
+fn render(nodes: &[Node], scopes: &[&Value], output: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Var(path) => {
+                if let Some(value) = lookup(scopes, path) {
+                    write_value(value, output);
+                }
+            }
+            Node::If {
+                path,
+                then,
+                otherwise,
+            } => {
+                if is_truthy(lookup(scopes, path)) {
+                    render(then, scopes, output);
+                } else {
+                    render(otherwise, scopes, output);
+                }
+            }
+            Node::Each { path, body } => {
+                if let Some(Value::Array(items)) = lookup(scopes, path) {
+                    for item in items {
+                        let mut inner = scopes.to_vec();
+                        inner.push(item);
+                        render(body, &inner, output);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawTemplate {
+    Plain(String),
+    Localized(BTreeMap<String, String>),
+}
+
+#[derive(Deserialize)]
+struct RawTemplates {
+    #[serde(default)]
+    locale: Option<String>,
+    #[serde(default)]
+    users: HashMap<String, String>,
+    #[serde(default)]
+    messages: BTreeMap<String, RawTemplate>,
+}
+
+/// The bot's messages, usually a `templates` table in the repository's
+/// configuration:
+///
+/// ```toml
+/// [templates]
+/// locale = "zh"          # the repository's language
+/// users = { alice = "en" }
+///
+/// [templates.messages]
+/// closed = "Closed, thanks @{{user}}."
+///
+/// [templates.messages.welcome]
+/// en = "Thanks for opening {{title}}, @{{user}}!"
+/// zh = "感谢 @{{user}} 提交 {{title}}！"
+/// ```
+///
+/// A message is picked in the user's language, then the repository's, then
+/// English, falling back to any variant there is.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "RawTemplates")]
+pub struct Templates {
+    pub locale: Option<String>,
+    pub users: HashMap<String, String>,
+    messages: BTreeMap<String, BTreeMap<String, Template>>,
+}
+
+impl TryFrom<RawTemplates> for Templates {
+    type Error = String;
+
+    fn try_from(raw: RawTemplates) -> Result<Self, Self::Error> {
+        let mut messages = BTreeMap::new();
+        for (name, template) in raw.messages {
+            let variants = match template {
+                RawTemplate::Plain(source) => vec![(String::new(), source)],
+                RawTemplate::Localized(variants) => variants.into_iter().collect(),
+            };
+            let mut parsed = BTreeMap::new();
+            for (locale, source) in variants {
+                let template = Template::parse(&source).map_err(|err| {
+                    if locale.is_empty() {
+                        format!("template `{}`, {}", name, err)
+                    } else {
+                        format!("template `{}` ({}), {}", name, locale, err)
+                    }
+                })?;
+                parsed.insert(locale, template);
+            }
+            messages.insert(name, parsed);
+        }
+        Ok(Templates {
+            locale: raw.locale,
+            users: raw.users,
+            messages,
+        })
+    }
+}
+
+impl Templates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the `locale` variant of `name`; an empty locale is the variant
+    /// used when no language matches.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        locale: impl Into<String>,
+        template: Template,
+    ) {
+        self.messages
+            .entry(name.into())
+            .or_default()
+            .insert(locale.into(), template);
+    }
+
+    /// The language `user` reads: their own setting, or the repository's.
+    pub fn locale_for(&self, user: &str) -> Option<&str> {
+        self.users
+            .get(user)
+            .or(self.locale.as_ref())
+            .map(String::as_str)
+    }
+
+    /// Renders `name` in the language of `user`.
+    pub fn render(&self, name: &str, user: &str, vars: &Value) -> Result<String, TemplateError> {
+        self.render_locale(name, self.locale_for(user), vars)
+    }
+
+    pub fn render_locale(
+        &self,
+        name: &str,
+        locale: Option<&str>,
+        vars: &Value,
+    ) -> Result<String, TemplateError> {
+        let variants = self
+            .messages
+            .get(name)
+            .ok_or_else(|| TemplateError::Unknown(name.to_string()))?;
+        let language = locale.map(|it| it.split(['-', '_']).next().unwrap_or(it));
+        let template = locale
+            .into_iter()
+            .chain(language)
+            .chain(self.locale.as_deref())
+            .chain(["", "en"])
+            .find_map(|locale| variants.get(locale))
+            .or_else(|| variants.values().next())
+            .ok_or_else(|| TemplateError::Unknown(name.to_string()))?;
+        Ok(template.render(vars))
+    }
+}
+
+/// Variables describing `event`: `user` (the sender), `bot`, `repo`,
+/// `owner`, `run_url`, and where present `number`, `title`, `author`,
+/// `labels`, `base` and `head`.
+pub fn event_vars(ctx: &Context, event: &Event) -> Value {
+    let mut vars = json!({
+        "user": ctx.sender.login,
+        "bot": ctx.login.as_deref().unwrap_or_default(),
+        "repo": format!("{}/{}", ctx.repo.owner, ctx.repo.name),
+        "owner": ctx.repo.owner,
+        "run_url": format!(
+            "https://github.com/{}/{}/actions/runs/{}",
+            ctx.repo.owner, ctx.repo.name, ctx.running_info.run_id
+        ),
+    });
+    let fields = match event {
+        Event::IssueCreated(event) => json!({
+            "number": event.id,
+            "title": event.title,
+            "author": event.user,
+            "labels": event.labels,
+        }),
+        Event::IssueReopened(event) => json!({
+            "number": event.id,
+            "title": event.title,
+            "author": event.user,
+            "labels": event.labels,
+        }),
+        Event::PullRequestCreated(event) => json!({
+            "number": event.id,
+            "title": event.title,
+            "author": event.user,
+            "labels": event.labels,
+            "base": event.to_ref,
+            "head": event.from_ref,
+        }),
+        Event::IssueUpdated(event) => json!({ "number": event.id, "author": event.user }),
+        Event::PullRequestUpdated(event) => json!({ "number": event.id, "author": event.user }),
+        Event::IssueClosed(id) | Event::PullRequestClosed(id) => json!({ "number": id }),
+        Event::CommentCreated(event) => json!({
+            "number": event.target.id(),
+            "author": event.user,
+        }),
+        Event::CommentUpdated(event) => json!({
+            "number": event.target.id(),
+            "author": event.user,
+        }),
+        Event::CommentDeleted(_) => json!({}),
+    };
+    if let (Some(vars), Value::Object(fields)) = (vars.as_object_mut(), fields) {
+        vars.extend(fields);
+    }
+    vars
+}
@@ -0,0 +1,317 @@
+//! Builders for the payloads a workflow hands to the bot, e.g.
+//! `events::issue_opened().title("x").by("alice").build()`.
+
+use crate::{AuthorAssociation, Event};
+use serde_json::{json, Value};
+
+/// An event in the shape of `toJson(github)`. Unless set otherwise, it
+/// happens on `octocat/hello-world` #1, by `octocat`, who owns the
+/// repository.
+#[derive(Debug, Clone)]
+pub struct EventBuilder {
+    event_name: &'static str,
+    action: &'static str,
+    repo: String,
+    number: usize,
+    title: String,
+    body: String,
+    author: String,
+    sender: String,
+    sender_is_bot: bool,
+    association: AuthorAssociation,
+    labels: Vec<String>,
+    base: String,
+    head: String,
+    on_pull_request: bool,
+    comment_id: usize,
+    comment_body: String,
+    changes: Option<(&'static str, String)>,
+    installation_id: Option<u64>,
+    run_id: usize,
+    sha: String,
+}
+
+fn builder(event_name: &'static str, action: &'static str) -> EventBuilder {
+    EventBuilder {
+        event_name,
+        action,
+        repo: "octocat/hello-world".to_string(),
+        number: 1,
+        title: "Something is broken".to_string(),
+        body: String::new(),
+        author: "octocat".to_string(),
+        sender: "octocat".to_string(),
+        sender_is_bot: false,
+        association: AuthorAssociation::Owner,
+        labels: Vec::new(),
+        base: "main".to_string(),
+        head: "patch-1".to_string(),
+        on_pull_request: false,
+        comment_id: 1001,
+        comment_body: String::new(),
+        changes: None,
+        installation_id: None,
+        run_id: 1,
+        sha: "0000000000000000000000000000000000000000".to_string(),
+    }
+}
+
+pub fn issue_opened() -> EventBuilder {
+    builder("issues", "opened")
+}
+
+/// Set what changed with `changed_title` or `changed_body`.
+pub fn issue_edited() -> EventBuilder {
+    builder("issues", "edited")
+}
+
+pub fn issue_closed() -> EventBuilder {
+    builder("issues", "closed")
+}
+
+pub fn issue_reopened() -> EventBuilder {
+    builder("issues", "reopened")
+}
+
+pub fn pull_request_opened() -> EventBuilder {
+    builder("pull_request", "opened")
+}
+
+pub fn pull_request_edited() -> EventBuilder {
+    builder("pull_request", "edited")
+}
+
+pub fn pull_request_closed() -> EventBuilder {
+    builder("pull_request", "closed")
+}
+
+/// A comment on an issue, or on a pull request with `on_pull_request`.
+pub fn comment_created() -> EventBuilder {
+    builder("issue_comment", "created")
+}
+
+pub fn comment_edited() -> EventBuilder {
+    builder("issue_comment", "edited")
+}
+
+pub fn comment_deleted() -> EventBuilder {
+    builder("issue_comment", "deleted")
+}
+
+impl EventBuilder {
+    /// `owner/name`.
+    pub fn repo(mut self, repo: &str) -> Self {
+        self.repo = repo.to_string();
+        self
+    }
+
+    /// The issue or pull request number.
+    pub fn number(mut self, number: usize) -> Self {
+        self.number = number;
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    /// The body of the issue or pull request, or the text of the comment for
+    /// comment events.
+    pub fn body(mut self, body: &str) -> Self {
+        if self.event_name == "issue_comment" {
+            self.comment_body = body.to_string();
+        } else {
+            self.body = body.to_string();
+        }
+        self
+    }
+
+    /// Written and sent by `login`.
+    pub fn by(mut self, login: &str) -> Self {
+        self.author = login.to_string();
+        self.sender = login.to_string();
+        self
+    }
+
+    /// Sent by `login`, e.g. someone closing an issue opened by someone
+    /// else.
+    pub fn sender(mut self, login: &str) -> Self {
+        self.sender = login.to_string();
+        self
+    }
+
+    /// Sent by a bot account; `login` should end in `[bot]`.
+    pub fn by_bot(mut self, login: &str) -> Self {
+        self = self.by(login);
+        self.sender_is_bot = true;
+        self
+    }
+
+    pub fn association(mut self, association: AuthorAssociation) -> Self {
+        self.association = association;
+        self
+    }
+
+    pub fn label(mut self, label: &str) -> Self {
+        self.labels.push(label.to_string());
+        self
+    }
+
+    pub fn base(mut self, branch: &str) -> Self {
+        self.base = branch.to_string();
+        self
+    }
+
+    pub fn head(mut self, branch: &str) -> Self {
+        self.head = branch.to_string();
+        self
+    }
+
+    pub fn on_pull_request(mut self) -> Self {
+        self.on_pull_request = true;
+        self
+    }
+
+    pub fn comment_id(mut self, id: usize) -> Self {
+        self.comment_id = id;
+        self
+    }
+
+    pub fn changed_title(mut self, from: &str) -> Self {
+        self.changes = Some(("title", from.to_string()));
+        self
+    }
+
+    /// The previous body, or the previous text of an edited comment.
+    pub fn changed_body(mut self, from: &str) -> Self {
+        self.changes = Some(("body", from.to_string()));
+        self
+    }
+
+    pub fn installation(mut self, id: u64) -> Self {
+        self.installation_id = Some(id);
+        self
+    }
+
+    pub fn run_id(mut self, id: usize) -> Self {
+        self.run_id = id;
+        self
+    }
+
+    pub fn sha(mut self, sha: &str) -> Self {
+        self.sha = sha.to_string();
+        self
+    }
+
+    fn user(login: &str, is_bot: bool) -> Value {
+        json!({ "login": login, "type": if is_bot { "Bot" } else { "User" } })
+    }
+
+    fn labels(&self) -> Value {
+        self.labels
+            .iter()
+            .map(|name| json!({ "name": name }))
+            .collect()
+    }
+
+    /// The issue, or the pull request's issue for comments.
+    fn issue(&self) -> Value {
+        let mut issue = json!({
+            "number": self.number,
+            "title": self.title,
+            "body": self.body,
+            "user": Self::user(&self.author, false),
+            "author_association": self.association.as_str(),
+            "labels": self.labels(),
+            "state": if self.action == "closed" { "closed" } else { "open" },
+        });
+        if self.on_pull_request {
+            let (owner, name) = self.repo.split_once('/').unwrap_or_default();
+            issue["pull_request"] = json!({
+                "url": format!(
+                    "https://api.github.com/repos/{}/{}/pulls/{}",
+                    owner, name, self.number
+                ),
+            });
+        }
+        issue
+    }
+
+    fn pull_request(&self) -> Value {
+        let (owner, name) = self.repo.split_once('/').unwrap_or_default();
+        json!({
+            "number": self.number,
+            "title": self.title,
+            "body": self.body,
+            "user": Self::user(&self.author, false),
+            "author_association": self.association.as_str(),
+            "labels": self.labels(),
+            "state": if self.action == "closed" { "closed" } else { "open" },
+            "base": { "ref": self.base, "repo": { "name": name, "owner": { "login": owner } } },
+            "head": {
+                "ref": self.head,
+                "user": { "login": self.author },
+                "repo": { "name": name, "owner": { "login": self.author } },
+            },
+        })
+    }
+
+    pub fn build(&self) -> Value {
+        let (owner, name) = self.repo.split_once('/').unwrap_or_default();
+        let mut event = json!({
+            "action": self.action,
+            "repository": {
+                "name": name,
+                "full_name": self.repo,
+                "owner": { "login": owner },
+            },
+            "sender": Self::user(&self.sender, self.sender_is_bot),
+        });
+        match self.event_name {
+            "pull_request" => {
+                event["number"] = json!(self.number);
+                event["pull_request"] = self.pull_request();
+            }
+            "issue_comment" => {
+                event["issue"] = self.issue();
+                event["comment"] = json!({
+                    "id": self.comment_id,
+                    "body": self.comment_body,
+                    "user": Self::user(&self.author, self.sender_is_bot),
+                    "author_association": self.association.as_str(),
+                });
+            }
+            _ => event["issue"] = self.issue(),
+        }
+        if let Some((field, from)) = &self.changes {
+            let mut changes = serde_json::Map::new();
+            changes.insert(field.to_string(), json!({ "from": from }));
+            event["changes"] = Value::Object(changes);
+        }
+        if let Some(id) = self.installation_id {
+            event["installation"] = json!({ "id": id });
+        }
+        let is_pull_request = self.event_name == "pull_request";
+        json!({
+            "event_name": self.event_name,
+            "event": event,
+            "repository": self.repo,
+            "repository_owner": owner,
+            "run_id": self.run_id.to_string(),
+            "run_number": "1",
+            "sha": self.sha,
+            "ref": if is_pull_request {
+                format!("refs/pull/{}/merge", self.number)
+            } else {
+                "refs/heads/main".to_string()
+            },
+            "head_ref": if is_pull_request { self.head.as_str() } else { "" },
+            "base_ref": if is_pull_request { self.base.as_str() } else { "" },
+        })
+    }
+
+    pub fn event(&self) -> Event {
+        Event::from_payload(&self.build())
+    }
+}
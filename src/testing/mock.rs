@@ -0,0 +1,576 @@
+use crate::action_log::{Action, ActionLog};
+use crate::client::{ClientError, CollaboratorPermission, Comment, File, Issue, ReviewEvent, User};
+use crate::scopes::Manifest;
+use crate::{GitHubApi, Repository};
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// An in-memory GitHub for testing bots.
+///
+/// It serves canned issues, permissions, team memberships and files, keeps
+/// the comments, labels and files written to it, and records every call.
+/// Clones share their state, so a test can keep one and hand another to
+/// the dispatcher.
+#[derive(Debug, Clone)]
+pub struct MockGitHub {
+    state: Arc<Mutex<State>>,
+    log: ActionLog,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    login: String,
+    writes: i64,
+    calls: Vec<&'static str>,
+    failures: HashMap<&'static str, u16>,
+    comments: Vec<(usize, Comment)>,
+    issues: HashMap<usize, Issue>,
+    labels: HashMap<usize, Vec<String>>,
+    closed: Vec<usize>,
+    pull_request_files: HashMap<usize, Vec<String>>,
+    files: HashMap<(Option<String>, String), File>,
+    permissions: HashMap<String, String>,
+    team_members: Vec<(String, String, String)>,
+    granted: Option<Manifest>,
+}
+
+impl State {
+    /// Every write gets its own timestamp and SHA.
+    fn tick(&mut self) -> i64 {
+        self.writes += 1;
+        self.writes
+    }
+
+    fn comment(&mut self, issue_id: usize, user: &str, body: &str) -> Comment {
+        let tick = self.tick();
+        let comment = Comment {
+            id: tick as usize + 1000,
+            body: body.to_string(),
+            user: User {
+                login: user.to_string(),
+            },
+            updated_at: Utc.timestamp_opt(tick, 0).single(),
+        };
+        self.comments.push((issue_id, comment.clone()));
+        comment
+    }
+}
+
+impl Default for MockGitHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockGitHub {
+    /// Comments written through the mock are by `baipiao-bot`.
+    pub fn new() -> Self {
+        MockGitHub {
+            state: Arc::new(Mutex::new(State {
+                login: "baipiao-bot".to_string(),
+                ..State::default()
+            })),
+            log: ActionLog::new(),
+        }
+    }
+
+    /// Who the comments written through the mock are by.
+    pub fn login(self, login: &str) -> Self {
+        self.state.lock().unwrap().login = login.to_string();
+        self
+    }
+
+    pub fn api(&self) -> Arc<dyn GitHubApi> {
+        Arc::new(self.clone())
+    }
+
+    /// Makes every call of `operation`, e.g. `create_comment`, fail with
+    /// `status`.
+    pub fn fail(&self, operation: &'static str, status: u16) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .insert(operation, status);
+    }
+
+    pub fn add_issue(&self, number: usize, title: &str, author: &str) {
+        let issue = Issue {
+            number,
+            title: title.to_string(),
+            body: None,
+            user: User {
+                login: author.to_string(),
+            },
+        };
+        self.state.lock().unwrap().issues.insert(number, issue);
+    }
+
+    /// A comment that was there before the test.
+    pub fn add_comment(&self, issue_id: usize, user: &str, body: &str) -> Comment {
+        self.state.lock().unwrap().comment(issue_id, user, body)
+    }
+
+    /// `permission` is `admin`, `maintain`, `write`, `triage` or `read`.
+    pub fn set_permission(&self, user: &str, permission: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .permissions
+            .insert(user.to_string(), permission.to_string());
+    }
+
+    pub fn add_team_member(&self, org: &str, team: &str, user: &str) {
+        self.state.lock().unwrap().team_members.push((
+            org.to_string(),
+            team.to_string(),
+            user.to_string(),
+        ));
+    }
+
+    /// What the token may do, for `Dispatcher::with_permission_check`;
+    /// unknown unless set.
+    pub fn grant(&self, permissions: Manifest) {
+        self.state.lock().unwrap().granted = Some(permissions);
+    }
+
+    pub fn set_pull_request_files(&self, pull_request_id: usize, files: &[&str]) {
+        self.state.lock().unwrap().pull_request_files.insert(
+            pull_request_id,
+            files.iter().map(|it| it.to_string()).collect(),
+        );
+    }
+
+    /// A file at `git_ref`, or at any ref with `None`.
+    pub fn set_file(&self, git_ref: Option<&str>, path: &str, content: &str) {
+        let mut state = self.state.lock().unwrap();
+        let sha = format!("sha{}", state.tick());
+        state.files.insert(
+            (git_ref.map(str::to_string), path.to_string()),
+            File {
+                content: content.to_string(),
+                sha,
+            },
+        );
+    }
+
+    /// The operations called so far, e.g. `["comments", "create_comment"]`.
+    pub fn calls(&self) -> Vec<&'static str> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// The writes made so far.
+    pub fn actions(&self) -> Vec<Action> {
+        self.log.actions()
+    }
+
+    pub fn comments_on(&self, issue_id: usize) -> Vec<Comment> {
+        let state = self.state.lock().unwrap();
+        state
+            .comments
+            .iter()
+            .filter(|(id, _)| *id == issue_id)
+            .map(|(_, comment)| comment.clone())
+            .collect()
+    }
+
+    pub fn labels_on(&self, issue_id: usize) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.labels.get(&issue_id).cloned().unwrap_or_default()
+    }
+
+    pub fn is_closed(&self, issue_id: usize) -> bool {
+        self.state.lock().unwrap().closed.contains(&issue_id)
+    }
+
+    pub fn file_on(&self, git_ref: &str, path: &str) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        let key = (Some(git_ref.to_string()), path.to_string());
+        state.files.get(&key).map(|file| file.content.clone())
+    }
+
+    /// Records the call, failing it if asked to.
+    fn call(&self, operation: &'static str) -> Result<(), ClientError> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(operation);
+        match state.failures.get(operation) {
+            Some(status) => Err(ClientError::Status {
+                status: *status,
+                message: format!("{} failed", operation),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn issue_of(&self, comment_id: usize) -> Option<usize> {
+        let state = self.state.lock().unwrap();
+        state
+            .comments
+            .iter()
+            .find(|(_, comment)| comment.id == comment_id)
+            .map(|(issue_id, _)| *issue_id)
+    }
+
+    fn describe(&self) -> String {
+        let actions = self.actions();
+        if actions.is_empty() {
+            return "no changes were made".to_string();
+        }
+        let actions: Vec<String> = actions
+            .iter()
+            .map(|action| match action.body() {
+                Some(body) => format!("{}: {:?}", action, body),
+                None => action.to_string(),
+            })
+            .collect();
+        format!("the changes were:\n  {}", actions.join("\n  "))
+    }
+
+    /// Asserts that a comment containing `contains` was written on issue or
+    /// pull request `target`, or an earlier one was edited to contain it.
+    #[track_caller]
+    pub fn assert_commented(&self, target: usize, contains: &str) {
+        let found = self.actions().iter().any(|action| match action {
+            Action::CreateComment { issue_id, body } => {
+                *issue_id == target && body.contains(contains)
+            }
+            Action::UpdateComment { comment_id, body } => {
+                self.issue_of(*comment_id) == Some(target) && body.contains(contains)
+            }
+            _ => false,
+        });
+        assert!(
+            found,
+            "expected a comment on #{} containing {:?}, but {}",
+            target,
+            contains,
+            self.describe()
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_not_commented(&self, target: usize) {
+        let found = self.actions().iter().any(|action| match action {
+            Action::CreateComment { issue_id, .. } => *issue_id == target,
+            Action::UpdateComment { comment_id, .. } => self.issue_of(*comment_id) == Some(target),
+            _ => false,
+        });
+        assert!(
+            !found,
+            "expected no comment on #{}, but {}",
+            target,
+            self.describe()
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_labelled(&self, target: usize, label: &str) {
+        let found = self.actions().iter().any(|action| match action {
+            Action::AddLabels { issue_id, labels } => {
+                *issue_id == target && labels.iter().any(|it| it == label)
+            }
+            _ => false,
+        });
+        assert!(
+            found,
+            "expected #{} to be labelled `{}`, but {}",
+            target,
+            label,
+            self.describe()
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_closed(&self, target: usize) {
+        assert!(
+            self.is_closed(target),
+            "expected #{} to be closed, but {}",
+            target,
+            self.describe()
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_reviewed(&self, target: usize, review: ReviewEvent) {
+        let found = self.actions().iter().any(|action| {
+            matches!(action, Action::CreateReview { pull_request_id, review: it, .. }
+                if *pull_request_id == target && it == review.as_str())
+        });
+        assert!(
+            found,
+            "expected #{} to be reviewed with {}, but {}",
+            target,
+            review.as_str(),
+            self.describe()
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_no_changes(&self) {
+        assert!(
+            self.actions().is_empty(),
+            "expected no changes, but {}",
+            self.describe()
+        );
+    }
+}
+
+fn not_found<T>(what: String) -> Result<T, ClientError> {
+    Err(ClientError::Status {
+        status: 404,
+        message: format!("{} not found", what),
+    })
+}
+
+#[async_trait]
+impl GitHubApi for MockGitHub {
+    async fn create_comment(
+        &self,
+        _repo: &Repository,
+        issue_id: usize,
+        body: &str,
+    ) -> Result<Comment, ClientError> {
+        self.call("create_comment")?;
+        let comment = {
+            let mut state = self.state.lock().unwrap();
+            let login = state.login.clone();
+            state.comment(issue_id, &login, body)
+        };
+        self.log.push(Action::CreateComment {
+            issue_id,
+            body: body.to_string(),
+        });
+        Ok(comment)
+    }
+
+    async fn update_comment(
+        &self,
+        _repo: &Repository,
+        comment_id: usize,
+        body: &str,
+    ) -> Result<Comment, ClientError> {
+        self.call("update_comment")?;
+        let comment = {
+            let mut state = self.state.lock().unwrap();
+            let tick = state.tick();
+            let found = state
+                .comments
+                .iter_mut()
+                .find(|(_, comment)| comment.id == comment_id);
+            match found {
+                Some((_, comment)) => {
+                    comment.body = body.to_string();
+                    comment.updated_at = Utc.timestamp_opt(tick, 0).single();
+                    comment.clone()
+                }
+                None => return not_found(format!("comment {}", comment_id)),
+            }
+        };
+        self.log.push(Action::UpdateComment {
+            comment_id,
+            body: body.to_string(),
+        });
+        Ok(comment)
+    }
+
+    async fn delete_comment(
+        &self,
+        _repo: &Repository,
+        comment_id: usize,
+    ) -> Result<(), ClientError> {
+        self.call("delete_comment")?;
+        self.state
+            .lock()
+            .unwrap()
+            .comments
+            .retain(|(_, comment)| comment.id != comment_id);
+        self.log.push(Action::DeleteComment { comment_id });
+        Ok(())
+    }
+
+    async fn add_labels(
+        &self,
+        _repo: &Repository,
+        issue_id: usize,
+        labels: &[String],
+    ) -> Result<(), ClientError> {
+        self.call("add_labels")?;
+        self.state
+            .lock()
+            .unwrap()
+            .labels
+            .entry(issue_id)
+            .or_default()
+            .extend_from_slice(labels);
+        self.log.push(Action::AddLabels {
+            issue_id,
+            labels: labels.to_vec(),
+        });
+        Ok(())
+    }
+
+    async fn remove_label(
+        &self,
+        _repo: &Repository,
+        issue_id: usize,
+        label: &str,
+    ) -> Result<(), ClientError> {
+        self.call("remove_label")?;
+        if let Some(labels) = self.state.lock().unwrap().labels.get_mut(&issue_id) {
+            labels.retain(|it| it != label);
+        }
+        self.log.push(Action::RemoveLabel {
+            issue_id,
+            label: label.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn close_issue(&self, _repo: &Repository, issue_id: usize) -> Result<(), ClientError> {
+        self.call("close_issue")?;
+        self.state.lock().unwrap().closed.push(issue_id);
+        self.log.push(Action::CloseIssue { issue_id });
+        Ok(())
+    }
+
+    async fn create_review(
+        &self,
+        _repo: &Repository,
+        pull_request_id: usize,
+        review: ReviewEvent,
+        body: &str,
+    ) -> Result<(), ClientError> {
+        self.call("create_review")?;
+        self.log.push(Action::CreateReview {
+            pull_request_id,
+            review: review.as_str().to_string(),
+            body: body.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn comments(
+        &self,
+        _repo: &Repository,
+        issue_id: usize,
+    ) -> Result<Vec<Comment>, ClientError> {
+        self.call("comments")?;
+        Ok(self.comments_on(issue_id))
+    }
+
+    async fn issue(&self, _repo: &Repository, issue_id: usize) -> Result<Issue, ClientError> {
+        self.call("issue")?;
+        let issue = self.state.lock().unwrap().issues.get(&issue_id).cloned();
+        match issue {
+            Some(issue) => Ok(issue),
+            None => not_found(format!("issue {}", issue_id)),
+        }
+    }
+
+    async fn pull_request_files(
+        &self,
+        _repo: &Repository,
+        pull_request_id: usize,
+    ) -> Result<Vec<String>, ClientError> {
+        self.call("pull_request_files")?;
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .pull_request_files
+            .get(&pull_request_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn file(
+        &self,
+        _repo: &Repository,
+        path: &str,
+        git_ref: Option<&str>,
+    ) -> Result<Option<File>, ClientError> {
+        self.call("file")?;
+        let state = self.state.lock().unwrap();
+        let exact = (git_ref.map(str::to_string), path.to_string());
+        let any = (None, path.to_string());
+        Ok(state
+            .files
+            .get(&exact)
+            .or_else(|| state.files.get(&any))
+            .cloned())
+    }
+
+    async fn put_file(
+        &self,
+        _repo: &Repository,
+        branch: &str,
+        path: &str,
+        content: &str,
+        sha: Option<&str>,
+        _message: &str,
+    ) -> Result<String, ClientError> {
+        self.call("put_file")?;
+        let sha = {
+            let mut state = self.state.lock().unwrap();
+            let key = (Some(branch.to_string()), path.to_string());
+            if state.files.get(&key).map(|file| file.sha.as_str()) != sha {
+                return Err(ClientError::Status {
+                    status: 409,
+                    message: format!("{} does not match", path),
+                });
+            }
+            let new_sha = format!("sha{}", state.tick());
+            state.files.insert(
+                key,
+                File {
+                    content: content.to_string(),
+                    sha: new_sha.clone(),
+                },
+            );
+            new_sha
+        };
+        self.log.push(Action::PutFile {
+            branch: branch.to_string(),
+            path: path.to_string(),
+        });
+        Ok(sha)
+    }
+
+    /// Users without a permission set have none.
+    async fn collaborator_permission(
+        &self,
+        _repo: &Repository,
+        user: &str,
+    ) -> Result<CollaboratorPermission, ClientError> {
+        self.call("collaborator_permission")?;
+        let state = self.state.lock().unwrap();
+        let role = state.permissions.get(user).map_or("none", String::as_str);
+        // `permission` only knows the coarse levels
+        let permission = match role {
+            "maintain" => "write",
+            "triage" => "read",
+            role => role,
+        };
+        Ok(CollaboratorPermission {
+            permission: permission.to_string(),
+            role_name: role.to_string(),
+        })
+    }
+
+    async fn is_team_member(&self, org: &str, team: &str, user: &str) -> Result<bool, ClientError> {
+        self.call("is_team_member")?;
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .team_members
+            .iter()
+            .any(|(o, t, u)| o == org && t == team && u.eq_ignore_ascii_case(user)))
+    }
+
+    async fn granted_permissions(
+        &self,
+        _repo: &Repository,
+    ) -> Result<Option<Manifest>, ClientError> {
+        self.call("granted_permissions")?;
+        Ok(self.state.lock().unwrap().granted.clone())
+    }
+}
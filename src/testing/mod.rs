@@ -0,0 +1,25 @@
+//! Helpers for unit-testing bots, enabled by the `testing` feature.
+//!
+//! ```ignore
+//! let github = MockGitHub::new();
+//! testing::dispatcher(MyBot, &github)
+//!     .dispatch_event(events::issue_opened().title("x").by("alice").build())
+//!     .await;
+//! github.assert_commented(1, "Thanks @alice");
+//! ```
+
+pub mod events;
+mod mock;
+
+pub use mock::MockGitHub;
+
+use crate::{Bot, Dispatcher};
+
+/// A dispatcher talking to `github`, acting as `baipiao-bot`, without
+/// writing step summaries even inside GitHub Actions.
+pub fn dispatcher<B: Bot>(bot: B, github: &MockGitHub) -> Dispatcher<B> {
+    Dispatcher::new(bot)
+        .with_api(github.api())
+        .with_login("baipiao-bot")
+        .with_step_summary(false)
+}
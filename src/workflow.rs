@@ -0,0 +1,226 @@
+use crate::action_log::Action;
+use crate::layers::Outcome;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, process};
+
+/// A GitHub Actions workflow command, e.g. `::error file=a.rs,line=3::bad`,
+/// understood when printed to stdout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkflowCommand {
+    name: String,
+    properties: Vec<(String, String)>,
+    message: String,
+}
+
+impl WorkflowCommand {
+    pub fn new(name: impl Into<String>, message: impl Into<String>) -> Self {
+        WorkflowCommand {
+            name: name.into(),
+            properties: Vec::new(),
+            message: message.into(),
+        }
+    }
+
+    pub fn property(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.properties.push((name.into(), value.to_string()));
+        self
+    }
+
+    pub fn emit(&self) {
+        println!("{}", self);
+    }
+}
+
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+impl fmt::Display for WorkflowCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "::{}", self.name)?;
+        for (i, (name, value)) in self.properties.iter().enumerate() {
+            let separator = if i == 0 { " " } else { "," };
+            write!(f, "{}{}={}", separator, name, escape_property(value))?;
+        }
+        write!(f, "::{}", escape_data(&self.message))
+    }
+}
+
+/// Where an error, warning or notice points to, shown inline in pull
+/// request diffs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Annotation {
+    pub title: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub end_line: Option<usize>,
+    pub col: Option<usize>,
+    pub end_column: Option<usize>,
+}
+
+impl Annotation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    pub fn lines(mut self, line: usize, end_line: usize) -> Self {
+        self.line = Some(line);
+        self.end_line = Some(end_line);
+        self
+    }
+
+    pub fn columns(mut self, col: usize, end_column: usize) -> Self {
+        self.col = Some(col);
+        self.end_column = Some(end_column);
+        self
+    }
+
+    pub fn command(&self, level: &str, message: &str) -> WorkflowCommand {
+        let mut command = WorkflowCommand::new(level, message);
+        if let Some(title) = &self.title {
+            command = command.property("title", title);
+        }
+        if let Some(file) = &self.file {
+            command = command.property("file", file);
+        }
+        let numbers = [
+            ("line", self.line),
+            ("endLine", self.end_line),
+            ("col", self.col),
+            ("endColumn", self.end_column),
+        ];
+        for (name, value) in numbers.iter() {
+            if let Some(value) = value {
+                command = command.property(*name, value);
+            }
+        }
+        command
+    }
+
+    pub fn error(&self, message: &str) {
+        self.command("error", message).emit()
+    }
+
+    pub fn warning(&self, message: &str) {
+        self.command("warning", message).emit()
+    }
+
+    pub fn notice(&self, message: &str) {
+        self.command("notice", message).emit()
+    }
+}
+
+pub fn error(message: &str) {
+    Annotation::new().error(message)
+}
+
+pub fn warning(message: &str) {
+    Annotation::new().warning(message)
+}
+
+pub fn notice(message: &str) {
+    Annotation::new().notice(message)
+}
+
+pub fn debug(message: &str) {
+    WorkflowCommand::new("debug", message).emit()
+}
+
+/// Hides `secret` in the rest of the log.
+pub fn add_mask(secret: &str) {
+    WorkflowCommand::new("add-mask", secret).emit()
+}
+
+/// Folds the log lines printed until it is dropped, e.g.
+/// `let _group = workflow::group("Fetching labels");`.
+pub struct Group(());
+
+pub fn group(title: &str) -> Group {
+    WorkflowCommand::new("group", title).emit();
+    Group(())
+}
+
+impl Drop for Group {
+    fn drop(&mut self) {
+        WorkflowCommand::new("endgroup", "").emit()
+    }
+}
+
+/// Appends to the file named by the environment variable `var`; does
+/// nothing outside of GitHub Actions.
+fn append(var: &str, text: &str) -> io::Result<()> {
+    let path = match env::var_os(var) {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(text.as_bytes())
+}
+
+/// Sets the step output `name`, for later steps to read as
+/// `steps.<id>.outputs.<name>`.
+pub fn set_output(name: &str, value: &str) -> io::Result<()> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.subsec_nanos())
+        .unwrap_or_default();
+    let mut delimiter = format!("ghadelimiter_{}_{}", process::id(), nanos);
+    while value.contains(&delimiter) {
+        delimiter.push('_');
+    }
+    append(
+        "GITHUB_OUTPUT",
+        &format!("{}<<{}\n{}\n{}\n", name, delimiter, value, delimiter),
+    )
+}
+
+/// Adds Markdown to the job summary shown on the run's page.
+pub fn append_step_summary(markdown: &str) -> io::Result<()> {
+    let mut markdown = markdown.to_string();
+    if !markdown.ends_with('\n') {
+        markdown.push('\n');
+    }
+    append("GITHUB_STEP_SUMMARY", &markdown)
+}
+
+/// What the dispatcher writes to the job summary after an event.
+pub fn step_summary(event: &str, target: &str, outcome: &Outcome, actions: &[Action]) -> String {
+    let mut summary = format!(
+        "### baipiao-bot: `{}` on {}\n\n**Outcome:** {}\n\n",
+        event, target, outcome
+    );
+    if actions.is_empty() {
+        summary.push_str("No changes were made.\n");
+    }
+    for action in actions {
+        summary.push_str(&format!("- {}\n", action));
+    }
+    summary
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use baipiao_bot_rust::auth::{AppAuth, Auth};
+use baipiao_bot_rust::{GitHub, GitHubApi, Repository};
+use chrono::{Duration, Utc};
+use common::{Response, StubServer};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::json;
+use std::env;
+use std::sync::Arc;
+
+const PRIVATE_KEY: &[u8] = include_bytes!("fixtures/app-key.pem");
+const PUBLIC_KEY: &[u8] = include_bytes!("fixtures/app-key.pub.pem");
+
+fn token_response(token: &str, expires_in: Duration) -> Response {
+    Response::json(
+        201,
+        json!({ "token": token, "expires_at": (Utc::now() + expires_in).to_rfc3339() }),
+    )
+}
+
+#[test]
+fn jwt_is_signed_for_the_app() {
+    let app = AppAuth::new(1234, PRIVATE_KEY).unwrap();
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.set_issuer(&["1234"]);
+    let decoded = jsonwebtoken::decode::<serde_json::Value>(
+        &app.jwt().unwrap(),
+        &DecodingKey::from_rsa_pem(PUBLIC_KEY).unwrap(),
+        &validation,
+    )
+    .unwrap();
+    assert!(decoded.claims["iat"].as_i64().unwrap() < Utc::now().timestamp());
+}
+
+#[tokio::test]
+async fn installation_token_is_cached() {
+    let server = StubServer::start(|_| token_response("ghs_cached", Duration::hours(1)));
+    let app = AppAuth::new(1234, PRIVATE_KEY)
+        .unwrap()
+        .with_base_url(&server.url);
+
+    assert_eq!(app.installation_token(42).await.unwrap(), "ghs_cached");
+    assert_eq!(app.installation_token(42).await.unwrap(), "ghs_cached");
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/app/installations/42/access_tokens");
+    assert!(requests[0]
+        .header("authorization")
+        .unwrap()
+        .starts_with("Bearer "));
+}
+
+#[tokio::test]
+async fn installation_token_is_refreshed_before_expiry() {
+    let server = StubServer::start(|_| token_response("ghs_short", Duration::minutes(2)));
+    let app = AppAuth::new(1234, PRIVATE_KEY)
+        .unwrap()
+        .with_base_url(&server.url);
+
+    app.installation_token(42).await.unwrap();
+    app.installation_token(42).await.unwrap();
+
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn client_uses_installation_token() {
+    let server = StubServer::start(|request| match request.path.as_str() {
+        "/app/installations/7/access_tokens" => token_response("ghs_seven", Duration::hours(1)),
+        _ => Response::json(
+            201,
+            json!({ "id": 1, "body": "hello", "user": { "login": "baipiao-bot[bot]" } }),
+        ),
+    });
+    let app = AppAuth::new(1234, PRIVATE_KEY)
+        .unwrap()
+        .with_base_url(&server.url);
+    let github = GitHub::new(Auth::App(Arc::new(app)))
+        .with_base_url(&server.url)
+        .installation(Some(7));
+    let repo = Repository {
+        owner: "baipiao-bot".to_string(),
+        name: "baipiao-bot-rust".to_string(),
+    };
+
+    let comment = github.create_comment(&repo, 3, "hello").await.unwrap();
+
+    assert_eq!(comment.user.login, "baipiao-bot[bot]");
+    let requests = server.requests();
+    assert_eq!(
+        requests[1].path,
+        "/repos/baipiao-bot/baipiao-bot-rust/issues/3/comments"
+    );
+    assert_eq!(requests[1].header("authorization"), Some("token ghs_seven"));
+}
+
+#[tokio::test]
+async fn tokens_fall_back_to_the_workflow_token() {
+    env::remove_var("BAIPIAO_APP_ID");
+    // a secret that does not exist reaches the bot as an empty string
+    env::set_var("BAIPIAO_BOT_TOKEN", "");
+    env::set_var("GITHUB_TOKEN", "ghs_workflow");
+    let auth = Auth::from_env().unwrap();
+    assert_eq!(
+        auth.token(None).await.unwrap().as_deref(),
+        Some("ghs_workflow")
+    );
+
+    env::set_var("BAIPIAO_BOT_TOKEN", "ghp_bot");
+    let auth = Auth::from_env().unwrap();
+    assert_eq!(auth.token(None).await.unwrap().as_deref(), Some("ghp_bot"));
+}
@@ -0,0 +1,36 @@
+use baipiao_bot_rust::bots::{is_ignored, BotPolicy};
+use baipiao_bot_rust::Sender;
+
+fn sender(login: &str, is_bot: bool) -> Sender {
+    Sender {
+        login: login.to_string(),
+        is_bot,
+    }
+}
+
+#[test]
+fn ignores_itself_whatever_the_policy() {
+    let own = sender("baipiao-bot", false);
+    assert!(is_ignored(&own, Some("baipiao-bot"), &BotPolicy::Allow));
+    assert!(!is_ignored(&own, None, &BotPolicy::Ignore));
+}
+
+#[test]
+fn applies_policy_to_other_bots() {
+    let dependabot = sender("dependabot[bot]", true);
+    let human = sender("alice", false);
+
+    assert!(is_ignored(&dependabot, None, &BotPolicy::Ignore));
+    assert!(!is_ignored(&human, None, &BotPolicy::Ignore));
+    assert!(!is_ignored(&dependabot, None, &BotPolicy::Allow));
+    assert!(!is_ignored(
+        &dependabot,
+        None,
+        &BotPolicy::AllowList(vec!["dependabot[bot]".into()])
+    ));
+    assert!(is_ignored(
+        &sender("renovate[bot]", true),
+        None,
+        &BotPolicy::AllowList(vec!["dependabot[bot]".into()])
+    ));
+}
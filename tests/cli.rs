@@ -0,0 +1,142 @@
+use async_trait::async_trait;
+use baipiao_bot_rust::cli::Cli;
+use baipiao_bot_rust::testing::MockGitHub;
+use baipiao_bot_rust::{Bot, Context, IssueCreatedEvent};
+use std::env;
+use std::fs;
+
+struct Welcome;
+
+#[async_trait]
+impl Bot for Welcome {
+    async fn on_issue_created(&self, ctx: &Context, event: IssueCreatedEvent) {
+        ctx.github
+            .create_comment(&ctx.repo, event.id, "welcome")
+            .await
+            .unwrap();
+    }
+}
+
+async fn run(cli: &Cli, args: &[&str]) -> (i32, String, String) {
+    let args: Vec<String> = args.iter().map(|it| it.to_string()).collect();
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = cli.run_with(&args, &mut out, &mut err).await;
+    (
+        code,
+        String::from_utf8(out).unwrap(),
+        String::from_utf8(err).unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn parse() {
+    let cli = Cli::new();
+    let (code, out, _) = run(
+        &cli,
+        &["parse", "tests/fixtures/events/synthetic/issue_comment.edited.json"],
+    )
+    .await;
+    assert_eq!(code, 0);
+    assert!(out.starts_with("CommentUpdated("));
+    assert!(out.contains("from: \"/label needs-repo\""));
+
+    let path = env::temp_dir().join(format!("baipiao-cli-{}.json", std::process::id()));
+    fs::write(
+        &path,
+        r#"{ "event_name": "issues", "event": { "action": "opened" } }"#,
+    )
+    .unwrap();
+    let (code, _, err) = run(&cli, &["parse", path.to_str().unwrap()]).await;
+    fs::remove_file(&path).unwrap();
+    assert_eq!(code, 1);
+    assert_eq!(err, "error: expected a number at `/event/issue/number`\n");
+
+    let (code, _, err) = run(&cli, &["parse"]).await;
+    assert_eq!(code, 2);
+    assert!(err.starts_with("error: missing <file>\n\nusage:"));
+}
+
+#[tokio::test]
+async fn replay_is_a_dry_run() {
+    let github = MockGitHub::new();
+    let cli = Cli::new()
+        .bot("welcome", Welcome)
+        .bot("other", Welcome)
+        .api(github.api());
+    let fixture = "tests/fixtures/events/synthetic/issues.opened.json";
+
+    let (code, out, _) = run(&cli, &["replay", fixture, "--bot", "welcome"]).await;
+    assert_eq!(code, 0);
+    assert!(
+        out.starts_with("Dry run of `issues.opened` on baipiao-bot/baipiao-bot-rust#3 "),
+        "{}",
+        out
+    );
+    assert!(
+        out.ends_with("would have:\n1. commented on #3\n   > welcome\nwelcome: handled\n"),
+        "{}",
+        out
+    );
+    github.assert_no_changes();
+
+    let (code, out, _) = run(&cli, &["replay", fixture, "--bot", "welcome", "--json"]).await;
+    assert_eq!(code, 0);
+    let plan: serde_json::Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(plan["event"], "issues.opened");
+    assert_eq!(plan["outcome"], "handled");
+    assert_eq!(
+        plan["actions"],
+        serde_json::json!([{ "action": "create_comment", "issue_id": 3, "body": "welcome" }])
+    );
+    github.assert_no_changes();
+
+    let (code, _, err) = run(&cli, &["replay", fixture]).await;
+    assert_eq!(code, 1);
+    assert_eq!(
+        err,
+        "error: pick a bot with --bot, one of `welcome`, `other`\n"
+    );
+}
+
+#[tokio::test]
+async fn offline_replays_read_from_memory() {
+    let cli = Cli::new().bot("welcome", Welcome).login("baipiao-bot");
+    let fixture = "tests/fixtures/events/synthetic/issues.opened.json";
+
+    let (code, out, _) = run(&cli, &["replay", fixture, "--offline"]).await;
+    assert_eq!(code, 0);
+    assert!(out.ends_with("welcome: handled\n"), "{}", out);
+}
+
+#[tokio::test]
+async fn fixtures() {
+    let cli = Cli::new();
+    let (code, out, _) = run(&cli, &["fixtures", "list"]).await;
+    assert_eq!(code, 0);
+    assert!(out.contains("issues.opened                           issue_created\n"));
+    assert!(out.contains("unsupported event `pull_request.synchronize`"));
+
+    let (code, out, _) = run(&cli, &["fixtures", "show", "issues.closed"]).await;
+    assert_eq!(code, 0);
+    assert_eq!(
+        out,
+        fs::read_to_string("tests/fixtures/events/synthetic/issues.closed.json").unwrap()
+    );
+}
+
+#[tokio::test]
+async fn validate_config() {
+    let cli = Cli::new();
+    let (code, out, _) = run(&cli, &["validate-config", "examples/baipiao.toml"]).await;
+    assert_eq!(
+        (code, out.as_str()),
+        (0, "examples/baipiao.toml is valid\n")
+    );
+
+    let path = env::temp_dir().join(format!("baipiao-cli-{}.yml", std::process::id()));
+    fs::write(&path, "templates:\n  messages:\n    hi: \"{{#if x}}\"\n").unwrap();
+    let (code, _, err) = run(&cli, &["validate-config", path.to_str().unwrap()]).await;
+    fs::remove_file(&path).unwrap();
+    assert_eq!(code, 1);
+    assert!(err.contains("template `hi`"), "{}", err);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use baipiao_bot_rust::auth::Auth;
+use baipiao_bot_rust::{GitHub, GitHubApi, Repository};
+use common::{Response, StubServer};
+use serde_json::json;
+
+fn repo() -> Repository {
+    Repository {
+        owner: "baipiao-bot".to_string(),
+        name: "baipiao-bot-rust".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn path_segments_and_queries_are_encoded() {
+    let server = StubServer::start(|request| {
+        if request.path.contains("/contents/") {
+            Response::json(200, json!({ "content": "aGk=", "sha": "abc" }))
+        } else {
+            Response::json(200, json!([]))
+        }
+    });
+    let github = GitHub::new(Auth::None).with_base_url(&server.url);
+
+    for label in &["good first issue", "a/b", "c#"] {
+        github.remove_label(&repo(), 1, label).await.unwrap();
+    }
+    let file = github
+        .file(&repo(), "docs/a b.md", Some("feature/x&y"))
+        .await
+        .unwrap();
+    assert_eq!(file.unwrap().content, "hi");
+
+    let paths: Vec<String> = server.requests().into_iter().map(|it| it.path).collect();
+    let issue = "/repos/baipiao-bot/baipiao-bot-rust/issues/1/labels";
+    assert_eq!(
+        paths,
+        [
+            format!("{}/good%20first%20issue", issue),
+            format!("{}/a%2Fb", issue),
+            format!("{}/c%23", issue),
+            "/repos/baipiao-bot/baipiao-bot-rust/contents/docs/a%20b.md?ref=feature%2Fx%26y"
+                .to_string(),
+        ]
+    );
+}
@@ -0,0 +1,93 @@
+use baipiao_bot_rust::commands::{
+    ArgKind, ArgValue, CommandErrorKind, CommandParser, CommandSource, CommandSpec,
+};
+use baipiao_bot_rust::AuthorAssociation;
+
+fn parser() -> CommandParser {
+    CommandParser::new(vec![
+        CommandSpec::new("label").arg("names", ArgKind::List),
+        CommandSpec::new("assign").arg("user", ArgKind::User),
+        CommandSpec::new("retest"),
+        CommandSpec::new("retry").optional_arg("times", ArgKind::Number),
+    ])
+    .mention("baipiao-bot")
+}
+
+#[test]
+fn parses_multiple_commands() {
+    let text = "Thanks!\n/label bug \"good first issue\"\n@baipiao-bot retest\n/assign @alice";
+    let commands: Vec<_> = parser()
+        .parse(
+            text,
+            "bob",
+            AuthorAssociation::None,
+            &CommandSource::IssueBody(1),
+        )
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(commands.len(), 3);
+    assert_eq!(commands[0].list("names"), ["bug", "good first issue"]);
+    assert_eq!(commands[1].name, "retest");
+    assert_eq!(
+        commands[2].arg("user"),
+        Some(&ArgValue::User("alice".into()))
+    );
+    assert_eq!(commands[2].user, "bob");
+}
+
+#[test]
+fn ignores_code_quotes_and_unknown_commands() {
+    let text = "> /label quoted\n```\n/label fenced\n```\n/usr/bin/env\n@someone retest";
+    assert!(parser()
+        .parse(
+            text,
+            "bob",
+            AuthorAssociation::None,
+            &CommandSource::IssueBody(1)
+        )
+        .is_empty());
+}
+
+#[test]
+fn ignores_indented_code() {
+    let text =
+        "Run:\n\n    /label indented\n\t/retest tabbed\n\n```\n```\n    /retest after a fence\n\
+                Steps\n    /label continued";
+    let names: Vec<_> = parser()
+        .parse(
+            text,
+            "bob",
+            AuthorAssociation::None,
+            &CommandSource::IssueBody(1),
+        )
+        .into_iter()
+        .map(|it| it.unwrap().name)
+        .collect();
+    // an indented line right after text is not code
+    assert_eq!(names, ["label"]);
+}
+
+#[test]
+fn reports_invalid_arguments() {
+    let results = parser().parse(
+        "/assign\n/retry twice\n/retest now",
+        "bob",
+        AuthorAssociation::None,
+        &CommandSource::PullRequestBody(2),
+    );
+    let kinds: Vec<_> = results.into_iter().map(|it| it.unwrap_err().kind).collect();
+
+    assert_eq!(
+        kinds,
+        [
+            CommandErrorKind::MissingArgument("user".into()),
+            CommandErrorKind::InvalidNumber {
+                arg: "times".into(),
+                value: "twice".into()
+            },
+            CommandErrorKind::UnexpectedArgument("now".into()),
+        ]
+    );
+}
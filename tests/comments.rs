@@ -0,0 +1,53 @@
+mod common;
+
+use baipiao_bot_rust::client::ClientError;
+use baipiao_bot_rust::testing::MockGitHub;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn upsert_edits_the_previous_comment() {
+    let api = Arc::new(MockGitHub::default());
+    let mut ctx = common::context();
+    ctx.github = api.clone();
+    ctx.login = Some("baipiao-bot".to_string());
+    // someone quoting the marker must not be edited
+    api.add_comment(1, "alice", "<!-- baipiao:size -->\nfake");
+
+    let first = ctx.upsert_comment("size", "size: S").await.unwrap();
+    let second = ctx.upsert_comment("size", "size: L").await.unwrap();
+    ctx.upsert_comment("ci", "passed").await.unwrap();
+
+    assert_eq!(first.id, second.id);
+    let comments = api.comments_on(1);
+    assert_eq!(comments.len(), 3);
+    assert_eq!(comments[1].body, "<!-- baipiao:size -->\nsize: L");
+    assert_eq!(comments[2].body, "<!-- baipiao:ci -->\npassed");
+
+    assert!(ctx.delete_comment("size").await.unwrap());
+    assert!(!ctx.delete_comment("size").await.unwrap());
+    assert_eq!(api.comments_on(1).len(), 2);
+}
+
+#[tokio::test]
+async fn needs_an_issue() {
+    let mut ctx = common::context();
+    ctx.github = Arc::new(MockGitHub::default());
+    ctx.issue = None;
+    assert!(matches!(
+        ctx.upsert_comment("size", "S").await,
+        Err(ClientError::NoIssue)
+    ));
+}
+
+#[tokio::test]
+async fn needs_the_bot_login() {
+    let api = Arc::new(MockGitHub::default());
+    let mut ctx = common::context();
+    ctx.github = api.clone();
+    api.add_comment(1, "alice", "<!-- baipiao:size -->\nfake");
+    assert!(matches!(
+        ctx.upsert_comment("size", "S").await,
+        Err(ClientError::NoLogin)
+    ));
+    assert_eq!(api.comments_on(1)[0].body, "<!-- baipiao:size -->\nfake");
+}
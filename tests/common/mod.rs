@@ -0,0 +1,212 @@
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Response {
+            status,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// A minimal HTTP/1.1 server standing in for api.github.com.
+pub struct StubServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl StubServer {
+    pub fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&Request) -> Response + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let request = read_request(&mut BufReader::new(&mut stream));
+                let response = handler(&request);
+                recorded.lock().unwrap().push(request);
+                let mut head = format!("HTTP/1.1 {} Stub\r\n", response.status);
+                for (name, value) in &response.headers {
+                    head.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                head.push_str(&format!(
+                    "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    response.body.len()
+                ));
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.write_all(response.body.as_bytes()).unwrap();
+            }
+        });
+        StubServer { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> Request {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap().to_string();
+    let path = parts.next().unwrap().to_string();
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_at(line.find(':').unwrap());
+        headers.push((name.to_string(), value[1..].trim().to_string()));
+    }
+    let length = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .map(|(_, value)| value.parse().unwrap())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    Request {
+        method,
+        path,
+        headers,
+        body: String::from_utf8(body).unwrap(),
+    }
+}
+
+/// The `github` context of an Actions run triggered by a newly opened issue.
+pub fn issue_opened(user: &str, body: &str) -> serde_json::Value {
+    serde_json::json!({
+        "event_name": "issues",
+        "repository": "baipiao-bot/baipiao-bot-rust",
+        "run_id": "1",
+        "run_number": "1",
+        "event": {
+            "action": "opened",
+            "issue": {
+                "number": 1,
+                "title": "It does not work",
+                "body": body,
+                "user": { "login": user },
+                "author_association": "NONE"
+            },
+            "repository": { "owner": { "login": "baipiao-bot" } },
+            "sender": { "login": user, "type": "User" }
+        }
+    })
+}
+
+pub fn context() -> baipiao_bot_rust::Context {
+    baipiao_bot_rust::Context {
+        repo: baipiao_bot_rust::Repository {
+            owner: "baipiao-bot".to_string(),
+            name: "baipiao-bot-rust".to_string(),
+        },
+        running_info: baipiao_bot_rust::RunningInfo {
+            run_id: 1,
+            run_number: 1,
+            installation_id: None,
+            sha: None,
+        },
+        sender: baipiao_bot_rust::Sender {
+            login: "alice".to_string(),
+            is_bot: false,
+        },
+        login: None,
+        ignored_sender: false,
+        issue: Some(1),
+        github: std::sync::Arc::new(baipiao_bot_rust::GitHub::new(
+            baipiao_bot_rust::auth::Auth::None,
+        )),
+        config: None,
+        trace: Default::default(),
+        metrics: Default::default(),
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Lines(Arc<Mutex<Vec<u8>>>);
+
+impl Lines {
+    pub fn json(&self) -> Vec<serde_json::Value> {
+        String::from_utf8(self.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+impl Write for Lines {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for Lines {
+    type Writer = Lines;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Collects what is logged while the guard lives, as `logging::init` would
+/// print it with `LogFormat::Json`.
+pub fn capture() -> (Lines, tracing::subscriber::DefaultGuard) {
+    let lines = Lines::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(lines.clone())
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .finish();
+    (lines, tracing::subscriber::set_default(subscriber))
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use baipiao_bot_rust::auth::Auth;
+use baipiao_bot_rust::config::{self, Config, ConfigError, ConfigLoader, Format};
+use baipiao_bot_rust::layers::Outcome;
+use baipiao_bot_rust::testing::events;
+use baipiao_bot_rust::{Bot, Context, Dispatcher, Event, GitHub};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::{Response, StubServer};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::{env, fs};
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+struct Settings {
+    welcome: String,
+    labels: Vec<String>,
+}
+
+impl Config for Settings {
+    fn validate(&self) -> Result<(), String> {
+        if self.labels.iter().any(|label| label.is_empty()) {
+            return Err("labels must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+struct Welcome(Arc<Mutex<Option<String>>>);
+
+#[async_trait::async_trait]
+impl Bot for Welcome {
+    async fn handle(&self, ctx: &Context, _event: Event) {
+        *self.0.lock().unwrap() = ctx.config::<Settings>().map(|it| it.welcome.clone());
+    }
+}
+
+#[test]
+fn parse_formats() {
+    let toml: Settings = config::parse(
+        "welcome = \"hi\"\nlabels = [\"bug\"]",
+        Format::Toml,
+        "baipiao.toml",
+    )
+    .unwrap();
+    let yaml: Settings =
+        config::parse("welcome: hi\nlabels: [bug]", Format::Yaml, "baipiao.yml").unwrap();
+    assert_eq!(toml, yaml);
+
+    let error = config::parse::<Settings>("labels = [\"\"]", Format::Toml, "baipiao.toml");
+    assert!(matches!(error, Err(ConfigError::Invalid { .. })));
+    let error = config::parse::<Settings>("labels = 1", Format::Toml, "baipiao.toml");
+    assert!(matches!(error, Err(ConfigError::Parse { .. })));
+}
+
+#[tokio::test]
+async fn loads_from_workspace() {
+    let workspace = env::temp_dir().join(format!("baipiao-config-{}", std::process::id()));
+    fs::create_dir_all(workspace.join(".git")).unwrap();
+    fs::create_dir_all(workspace.join(".github")).unwrap();
+    fs::write(workspace.join(".github/baipiao.yml"), "welcome: hello").unwrap();
+
+    let seen = Arc::new(Mutex::new(None));
+    let dispatcher = Dispatcher::new(Welcome(seen.clone()))
+        .with_config_loader(ConfigLoader::new::<Settings>().workspace(Some(workspace.clone())));
+    dispatcher
+        .dispatch_event(common::issue_opened("alice", ""))
+        .await;
+    assert_eq!(seen.lock().unwrap().as_deref(), Some("hello"));
+    fs::remove_dir_all(workspace).unwrap();
+}
+
+#[tokio::test]
+async fn loads_from_api() {
+    let server = StubServer::start(|request| {
+        if request
+            .path
+            .starts_with("/repos/baipiao-bot/baipiao-bot-rust/contents/.github/baipiao.toml")
+        {
+            let content = STANDARD.encode("welcome = \"from the api\"");
+            Response::json(200, json!({ "content": content, "sha": "abc" }))
+        } else {
+            Response::json(404, json!({ "message": "Not Found" }))
+        }
+    });
+    let seen = Arc::new(Mutex::new(None));
+    let dispatcher = Dispatcher::new(Welcome(seen.clone()))
+        .with_github(GitHub::new(Auth::None).with_base_url(&server.url))
+        .with_config_loader(ConfigLoader::new::<Settings>().workspace(None));
+    let mut payload = common::issue_opened("alice", "");
+    payload["sha"] = json!("abc123");
+    dispatcher.dispatch_event(payload).await;
+    assert_eq!(seen.lock().unwrap().as_deref(), Some("from the api"));
+    assert_eq!(
+        server.requests()[0].path,
+        "/repos/baipiao-bot/baipiao-bot-rust/contents/.github/baipiao.toml?ref=abc123"
+    );
+}
+
+#[tokio::test]
+async fn invalid_config_fails_dispatch() {
+    let server = StubServer::start(|_| {
+        let content = STANDARD.encode("labels = [\"\"]");
+        Response::json(200, json!({ "content": content, "sha": "abc" }))
+    });
+    let seen = Arc::new(Mutex::new(None));
+    let dispatcher = Dispatcher::new(Welcome(seen.clone()))
+        .with_github(GitHub::new(Auth::None).with_base_url(&server.url))
+        .with_config_loader(ConfigLoader::new::<Settings>().workspace(None));
+    let outcome = dispatcher
+        .dispatch_event(common::issue_opened("alice", ""))
+        .await;
+    assert!(matches!(outcome, Outcome::Failed(_)));
+    assert_eq!(*seen.lock().unwrap(), None);
+}
+
+#[tokio::test]
+async fn pull_requests_read_config_from_their_base() {
+    let server = StubServer::start(|_| {
+        let content = STANDARD.encode("welcome = \"from the base\"");
+        Response::json(200, json!({ "content": content, "sha": "abc" }))
+    });
+    let seen = Arc::new(Mutex::new(None));
+    let dispatcher = Dispatcher::new(Welcome(seen.clone()))
+        .with_github(GitHub::new(Auth::None).with_base_url(&server.url))
+        .with_config_loader(ConfigLoader::new::<Settings>().workspace(None));
+    let mut payload = events::pull_request_opened().sha("merge123").build();
+    payload["event"]["pull_request"]["base"]["sha"] = json!("base123");
+    dispatcher.dispatch_event(payload).await;
+    assert_eq!(seen.lock().unwrap().as_deref(), Some("from the base"));
+    assert!(server.requests()[0].path.ends_with("?ref=base123"));
+}
+
+#[tokio::test]
+async fn skipped_events_do_not_load_config() {
+    let server = StubServer::start(|_| {
+        let content = STANDARD.encode("labels = [\"\"]");
+        Response::json(200, json!({ "content": content, "sha": "abc" }))
+    });
+    let dispatcher = Dispatcher::new(Welcome(Arc::default()))
+        .with_github(GitHub::new(Auth::None).with_base_url(&server.url))
+        .with_config_loader(ConfigLoader::new::<Settings>().workspace(None));
+    let mut payload = events::pull_request_opened().build();
+    payload["event"]["action"] = json!("synchronize");
+    let outcome = dispatcher.dispatch_event(payload).await;
+    assert!(matches!(outcome, Outcome::Skipped(_)));
+    assert!(server.requests().is_empty());
+}
@@ -0,0 +1,262 @@
+use baipiao_bot_rust::cli;
+use baipiao_bot_rust::payload::{self, ParseError};
+use baipiao_bot_rust::testing::{self, MockGitHub};
+use baipiao_bot_rust::{Bot, CommentTarget, Event, UpdatedPart};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Every synthetic fixture with the event it parses to, or `None` when it is
+/// unsupported and skipped; see `tests/fixtures/events/README.md`.
+const FIXTURES: &[(&str, Option<&str>)] = &[
+    ("issue_comment.created", Some("comment_created")),
+    ("issue_comment.created.bot", Some("comment_created")),
+    (
+        "issue_comment.created.pull-request",
+        Some("comment_created"),
+    ),
+    ("issue_comment.deleted", Some("comment_deleted")),
+    ("issue_comment.edited", Some("comment_updated")),
+    ("issues.closed", Some("issue_closed")),
+    ("issues.edited.body", Some("issue_updated")),
+    ("issues.edited.title", Some("issue_updated")),
+    ("issues.opened", Some("issue_created")),
+    ("issues.opened.empty-body", Some("issue_created")),
+    ("issues.reopened", Some("issue_reopened")),
+    ("pull_request.closed.merged", Some("pull_request_closed")),
+    ("pull_request.edited.base", Some("pull_request_updated")),
+    ("pull_request.edited.title", Some("pull_request_updated")),
+    ("pull_request.opened", Some("pull_request_created")),
+    ("pull_request.opened.fork", Some("pull_request_created")),
+    ("pull_request.reopened", None),
+    ("pull_request.synchronize", None),
+];
+
+/// Every recorded payload, likewise.
+const RECORDED: &[(&str, Option<&str>)] = &[
+    ("issue_comment.created", Some("comment_created")),
+    ("issues.opened", Some("issue_created")),
+    ("pull_request.opened", Some("pull_request_created")),
+    ("push", None),
+];
+
+fn read(dir: &str, name: &str) -> Value {
+    let path = Path::new("tests/fixtures/events")
+        .join(dir)
+        .join(format!("{}.json", name));
+    serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap()
+}
+
+fn fixture(name: &str) -> Value {
+    read("synthetic", name)
+}
+
+/// A recorded Events API record as the `github` context of a run.
+fn recorded(name: &str) -> Value {
+    let record = read("recorded", name);
+    let event_name = match record["type"].as_str().unwrap() {
+        "IssuesEvent" => "issues",
+        "IssueCommentEvent" => "issue_comment",
+        "PullRequestEvent" => "pull_request",
+        "PushEvent" => "push",
+        other => panic!("{}: unknown type {}", name, other),
+    };
+    let mut payload = payload::wrap(event_name, record["payload"].clone());
+    payload["repository"] = record["repo"]["name"].clone();
+    payload
+}
+
+fn names(dir: &str) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(Path::new("tests/fixtures/events").join(dir))
+        .unwrap()
+        .filter_map(|entry| {
+            let name = entry.unwrap().file_name().into_string().unwrap();
+            name.strip_suffix(".json").map(str::to_string)
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+fn parse(name: &str) -> Event {
+    Event::parse(&fixture(name)).unwrap_or_else(|err| panic!("{}: {}", name, err))
+}
+
+#[test]
+fn every_fixture_is_listed() {
+    let listed: Vec<&str> = FIXTURES.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names("synthetic"), listed);
+    let recorded: Vec<&str> = RECORDED.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names("recorded"), recorded);
+    let bundled: Vec<&str> = cli::fixtures::FIXTURES
+        .iter()
+        .map(|(name, _)| *name)
+        .collect();
+    assert_eq!(bundled, listed);
+}
+
+#[test]
+fn every_fixture_parses() {
+    for (name, expected) in FIXTURES {
+        match (Event::parse(&fixture(name)), expected) {
+            (Ok(event), Some(expected)) => assert_eq!(event.name(), *expected, "{}", name),
+            (Err(ParseError::Unsupported { .. }), None) => {}
+            (result, _) => panic!("{}: unexpected {:?}", name, result),
+        }
+    }
+}
+
+#[test]
+fn every_recorded_payload_parses() {
+    for (name, expected) in RECORDED {
+        match (Event::parse(&recorded(name)), expected) {
+            (Ok(event), Some(expected)) => assert_eq!(event.name(), *expected, "{}", name),
+            (Err(ParseError::Unsupported { .. }), None) => {}
+            (result, _) => panic!("{}: unexpected {:?}", name, result),
+        }
+    }
+}
+
+#[test]
+fn recorded_fields() {
+    match Event::parse(&recorded("issues.opened")).unwrap() {
+        Event::IssueCreated(event) => {
+            assert_eq!((event.id, event.title.as_str()), (6, "Test"));
+            assert_eq!(event.user, "wayofthepie");
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+    match Event::parse(&recorded("issue_comment.created")).unwrap() {
+        Event::CommentCreated(event) => {
+            assert_eq!(event.id, 760203693);
+            assert!(matches!(event.target, CommentTarget::Issue(_)));
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+    match Event::parse(&recorded("pull_request.opened")).unwrap() {
+        Event::PullRequestCreated(event) => {
+            assert_eq!((event.id, event.title.as_str()), (8, "Add test file"));
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+}
+
+#[test]
+fn fields() {
+    match parse("issues.opened.empty-body") {
+        Event::IssueCreated(event) => {
+            assert_eq!(event.id, 4);
+            assert_eq!(event.body, "");
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+    match parse("issues.edited.title") {
+        Event::IssueUpdated(event) => match event.updated_part {
+            UpdatedPart::Title { from, to } => {
+                assert_eq!(from, "Echo example panics");
+                assert_eq!(to, "Echo example panics on edited issues");
+            }
+            part => panic!("unexpected {:?}", part),
+        },
+        event => panic!("unexpected {:?}", event),
+    }
+    match parse("pull_request.opened.fork") {
+        Event::PullRequestCreated(event) => {
+            assert_eq!(event.id, 6);
+            assert_eq!(event.from_repo.owner, "alice-dev");
+            assert_eq!(event.from_ref, "patch-1");
+            assert_eq!(event.to_ref, "main");
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+    match parse("pull_request.edited.title") {
+        Event::PullRequestUpdated(event) => {
+            assert_eq!(event.id, 5);
+            assert_eq!(event.user, "alice-dev");
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+    assert!(matches!(
+        parse("pull_request.closed.merged"),
+        Event::PullRequestClosed(5)
+    ));
+    match parse("issue_comment.created.pull-request") {
+        Event::CommentCreated(event) => {
+            assert!(matches!(event.target, CommentTarget::PullRequest(5)))
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+    match parse("issue_comment.edited") {
+        Event::CommentUpdated(event) => {
+            assert_eq!(event.from, "/label needs-repo");
+            assert_eq!(event.to, "/label needs-repro");
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+}
+
+#[test]
+fn errors_point_at_the_field() {
+    let mut payload = fixture("pull_request.opened");
+    payload["event"]["pull_request"]
+        .as_object_mut()
+        .unwrap()
+        .remove("number");
+    assert_eq!(
+        Event::parse(&payload).unwrap_err().to_string(),
+        "expected a number at `/event/pull_request/number`"
+    );
+    assert_eq!(
+        Event::parse(&fixture("pull_request.synchronize"))
+            .unwrap_err()
+            .to_string(),
+        "unsupported event `pull_request.synchronize`"
+    );
+}
+
+struct Nothing;
+
+impl Bot for Nothing {}
+
+#[tokio::test]
+async fn every_fixture_dispatches() {
+    for (name, expected) in FIXTURES {
+        let github = MockGitHub::new();
+        let outcome = testing::dispatcher(Nothing, &github)
+            .dispatch_event(fixture(name))
+            .await;
+        let skipped = outcome.to_string().starts_with("skipped");
+        // the bot's own comments are skipped by the default bot policy
+        assert_eq!(
+            skipped,
+            expected.is_none() || name.ends_with(".bot"),
+            "{}: {}",
+            name,
+            outcome
+        );
+    }
+}
+
+#[tokio::test]
+async fn malformed_contexts_fail() {
+    let github = MockGitHub::new();
+    let dispatcher = testing::dispatcher(Nothing, &github);
+    for (field, value, message) in &[
+        ("run_id", Value::Null, "expected a string at `/run_id`"),
+        (
+            "run_number",
+            Value::from("first"),
+            "expected a number in a string at `/run_number`",
+        ),
+        (
+            "repository",
+            Value::from("hello-world"),
+            "expected `owner/name` at `/repository`",
+        ),
+    ] {
+        let mut payload = fixture("issues.opened");
+        payload[*field] = value.clone();
+        let outcome = dispatcher.dispatch_event(payload).await;
+        assert_eq!(outcome.to_string(), format!("failed, {}", message));
+    }
+}
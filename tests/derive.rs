@@ -0,0 +1,86 @@
+use baipiao_bot_rust::commands::{BotCommand, CommandErrorKind, CommandParser, CommandSource};
+use baipiao_bot_rust::permissions::Role;
+use baipiao_bot_rust::AuthorAssociation;
+
+#[derive(Debug, PartialEq, BotCommand)]
+enum Cmd {
+    /// Adds labels
+    Label { names: Vec<String> },
+    /// Closes the issue
+    #[command(requires = "collaborator")]
+    Close,
+    /// Assigns someone
+    Assign {
+        #[command(user)]
+        user: String,
+    },
+    #[command(rename = "lgtm")]
+    LooksGood { times: Option<u8> },
+}
+
+fn parse(text: &str) -> Vec<Result<Cmd, CommandErrorKind>> {
+    CommandParser::new(Cmd::specs())
+        .parse(
+            text,
+            "alice",
+            AuthorAssociation::None,
+            &CommandSource::IssueBody(1),
+        )
+        .into_iter()
+        .map(|it| {
+            it.and_then(|command| Cmd::from_command(&command))
+                .map_err(|error| error.kind)
+        })
+        .collect()
+}
+
+#[test]
+fn parses_into_variants() {
+    assert_eq!(
+        parse("/label bug help-wanted\n/close\n/assign @bob\n/lgtm"),
+        [
+            Ok(Cmd::Label {
+                names: vec!["bug".into(), "help-wanted".into()]
+            }),
+            Ok(Cmd::Close),
+            Ok(Cmd::Assign { user: "bob".into() }),
+            Ok(Cmd::LooksGood { times: None }),
+        ]
+    );
+}
+
+#[test]
+fn reports_validation_errors() {
+    assert_eq!(
+        parse("/label\n/lgtm 1000"),
+        [
+            Err(CommandErrorKind::MissingArgument("names".into())),
+            Err(CommandErrorKind::InvalidNumber {
+                arg: "times".into(),
+                value: "1000".into()
+            }),
+        ]
+    );
+}
+
+#[test]
+fn generates_help() {
+    assert_eq!(
+        Cmd::help(),
+        "| Command | Description |\n\
+         | --- | --- |\n\
+         | `/label <names...>` | Adds labels |\n\
+         | `/close` | Closes the issue |\n\
+         | `/assign <@user>` | Assigns someone |\n\
+         | `/lgtm [times]` |  |\n"
+    );
+}
+
+#[test]
+fn declares_required_roles() {
+    let roles: Vec<_> = Cmd::specs().into_iter().map(|spec| spec.role).collect();
+    assert_eq!(
+        roles,
+        [Role::Anyone, Role::Collaborator, Role::Anyone, Role::Anyone]
+    );
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use async_trait::async_trait;
+use baipiao_bot_rust::action_log::{Action, ActionLog, PlanFormat};
+use baipiao_bot_rust::layers::Outcome;
+use baipiao_bot_rust::testing::MockGitHub;
+use baipiao_bot_rust::{Bot, Context, Dispatcher, IssueCreatedEvent};
+use std::sync::Arc;
+
+struct Triage;
+
+#[async_trait]
+impl Bot for Triage {
+    async fn on_issue_created(&self, ctx: &Context, event: IssueCreatedEvent) {
+        ctx.upsert_comment("triage", "Thanks!\nWe'll look.")
+            .await
+            .unwrap();
+        ctx.github
+            .add_labels(&ctx.repo, event.id, &["needs-triage".to_string()])
+            .await
+            .unwrap();
+        ctx.github.close_issue(&ctx.repo, event.id).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn writes_are_not_sent() {
+    let github = Arc::new(MockGitHub::default());
+    let log = ActionLog::new();
+    let mut ctx = common::context();
+    ctx.github = Arc::new(log.dry_run(github.clone()));
+    ctx.login = Some("baipiao-bot".to_string());
+    let event = match baipiao_bot_rust::Event::from_payload(&common::issue_opened("bob", "")) {
+        baipiao_bot_rust::Event::IssueCreated(event) => event,
+        _ => unreachable!(),
+    };
+    Triage.on_issue_created(&ctx, event).await;
+
+    assert!(github.comments_on(1).is_empty());
+    assert!(github.labels_on(1).is_empty());
+    assert!(!github.is_closed(1));
+    assert_eq!(
+        log.actions(),
+        vec![
+            Action::CreateComment {
+                issue_id: 1,
+                body: "<!-- baipiao:triage -->\nThanks!\nWe'll look.".to_string(),
+            },
+            Action::AddLabels {
+                issue_id: 1,
+                labels: vec!["needs-triage".to_string()],
+            },
+            Action::CloseIssue { issue_id: 1 },
+        ]
+    );
+    assert_eq!(
+        log.plan(),
+        "1. commented on #1\n   > <!-- baipiao:triage -->\n   > Thanks!\n   > We'll look.\n\
+         2. labelled #1 with `needs-triage`\n\
+         3. closed #1\n"
+    );
+    assert_eq!(
+        log.to_json()[2],
+        serde_json::json!({ "action": "close_issue", "issue_id": 1 })
+    );
+}
+
+#[tokio::test]
+async fn reads_are_served_by_the_mock() {
+    let github = Arc::new(MockGitHub::default());
+    github.add_comment(1, "baipiao-bot", "<!-- baipiao:triage -->\nold");
+    let log = ActionLog::new();
+    let mut ctx = common::context();
+    ctx.github = Arc::new(log.dry_run(github.clone()));
+    ctx.login = Some("baipiao-bot".to_string());
+    ctx.upsert_comment("triage", "new").await.unwrap();
+    assert!(matches!(log.actions()[0], Action::UpdateComment { .. }));
+    assert_eq!(
+        github.comments_on(1)[0].body,
+        "<!-- baipiao:triage -->\nold"
+    );
+}
+
+#[tokio::test]
+async fn dispatcher_dry_run() {
+    let github = Arc::new(MockGitHub::default());
+    let report = Dispatcher::new(Triage)
+        .with_api(github.clone())
+        .with_login("baipiao-bot")
+        .with_dry_run(true)
+        .with_step_summary(false)
+        .dispatch(common::issue_opened("bob", ""))
+        .await;
+    assert_eq!(report.outcome, Outcome::Handled);
+    assert_eq!(report.actions.len(), 3);
+    assert!(github.comments_on(1).is_empty());
+
+    let text = report.render(PlanFormat::Text);
+    assert!(
+        text.starts_with(
+            "Dry run of `issues.opened` on baipiao-bot/baipiao-bot-rust#1 (handled), would have:\n\
+             1. commented on #1\n"
+        ),
+        "{}",
+        text
+    );
+    let json: serde_json::Value = serde_json::from_str(&report.render(PlanFormat::Json)).unwrap();
+    assert_eq!(json["dry_run"], true);
+    assert_eq!(
+        json["actions"][2],
+        serde_json::json!({ "action": "close_issue", "issue_id": 1 })
+    );
+}
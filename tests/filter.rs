@@ -0,0 +1,162 @@
+mod common;
+
+use async_trait::async_trait;
+use baipiao_bot_rust::commands::{Command, CommandSpec};
+use baipiao_bot_rust::filter::Filter;
+use baipiao_bot_rust::testing::{self, events, MockGitHub};
+use baipiao_bot_rust::{
+    AuthorAssociation, Bot, Context, Event, GitHub, PullRequestCreatedEvent,
+    PullRequestUpdatedEvent, Repository, UpdatedPart,
+};
+use common::{Response, StubServer};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+fn pull_request(to_ref: &str, title: &str, labels: &[&str]) -> Event {
+    Event::PullRequestCreated(PullRequestCreatedEvent {
+        id: 7,
+        title: title.to_string(),
+        body: String::new(),
+        user: "alice".to_string(),
+        author_association: AuthorAssociation::Contributor,
+        labels: labels.iter().map(|it| it.to_string()).collect(),
+        from_repo: Repository {
+            owner: "alice".to_string(),
+            name: "baipiao-bot-rust".to_string(),
+        },
+        from_ref: "fix".to_string(),
+        to_ref: to_ref.to_string(),
+    })
+}
+
+#[tokio::test]
+async fn combinators() {
+    let ctx = common::context();
+    let filter = Filter::event("pull_request_created")
+        & Filter::base_branch("main")
+        & !Filter::title("^(WIP|Draft)").unwrap()
+        & (Filter::label("bug") | Filter::author("bob"));
+
+    assert!(
+        filter
+            .matches(&ctx, &pull_request("main", "Fix it", &["bug"]))
+            .await
+    );
+    assert!(
+        !filter
+            .matches(&ctx, &pull_request("dev", "Fix it", &["bug"]))
+            .await
+    );
+    assert!(
+        !filter
+            .matches(&ctx, &pull_request("main", "WIP: fix", &["bug"]))
+            .await
+    );
+    assert!(
+        !filter
+            .matches(&ctx, &pull_request("main", "Fix it", &[]))
+            .await
+    );
+    assert!(!filter.matches(&ctx, &Event::IssueClosed(1)).await);
+
+    let event = pull_request("main", "Fix it", &[]);
+    assert!(!Filter::collaborators().matches(&ctx, &event).await);
+    assert!(Filter::author("Alice").matches(&ctx, &event).await);
+}
+
+#[tokio::test]
+async fn filters_only_see_what_the_event_carries() {
+    let ctx = common::context();
+    let retitled = Event::PullRequestUpdated(PullRequestUpdatedEvent {
+        id: 7,
+        updated_part: UpdatedPart::Title {
+            from: "Fix it".to_string(),
+            to: "WIP: fix it".to_string(),
+        },
+        user: "alice".to_string(),
+    });
+    assert!(
+        Filter::title("^WIP")
+            .unwrap()
+            .matches(&ctx, &retitled)
+            .await
+    );
+    // edits do not carry the branches
+    assert!(!Filter::base_branch("main").matches(&ctx, &retitled).await);
+}
+
+#[tokio::test]
+async fn paths_are_fetched() {
+    let server = StubServer::start(|_| {
+        Response::json(
+            200,
+            json!([{ "filename": "README.md" }, { "filename": "src/lib.rs" }]),
+        )
+    });
+    let mut ctx = common::context();
+    ctx.github =
+        Arc::new(GitHub::new(baipiao_bot_rust::auth::Auth::None).with_base_url(&server.url));
+    let event = pull_request("main", "Fix it", &[]);
+
+    assert!(
+        Filter::path("src/**/*.rs")
+            .unwrap()
+            .matches(&ctx, &event)
+            .await
+    );
+    assert!(!Filter::path("docs/*").unwrap().matches(&ctx, &event).await);
+    assert_eq!(
+        server.requests()[0].path,
+        "/repos/baipiao-bot/baipiao-bot-rust/pulls/7/files?per_page=100&page=1"
+    );
+}
+
+struct Recorder(Arc<Mutex<Vec<usize>>>);
+
+#[async_trait]
+impl Bot for Recorder {
+    async fn on_issue_closed(&self, _ctx: &Context, issue_id: usize) {
+        self.0.lock().unwrap().push(issue_id);
+    }
+}
+
+#[tokio::test]
+async fn filtered_bot() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let bot = Recorder(log.clone()).when(Filter::author("bob"));
+    let mut ctx = common::context();
+    bot.handle(&ctx, Event::IssueClosed(1)).await;
+    ctx.sender.login = "bob".to_string();
+    bot.handle(&ctx, Event::IssueClosed(2)).await;
+    assert_eq!(*log.lock().unwrap(), vec![2]);
+}
+
+/// Logs the commands it runs.
+struct Greeter(Arc<Mutex<Vec<String>>>);
+
+#[async_trait]
+impl Bot for Greeter {
+    fn commands(&self) -> Vec<CommandSpec> {
+        vec![CommandSpec::new("hello")]
+    }
+
+    async fn on_command(&self, _ctx: &Context, command: Command) {
+        self.0.lock().unwrap().push(command.user);
+    }
+}
+
+#[tokio::test]
+async fn filtered_bots_keep_their_commands() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let bot = Greeter(log.clone()).when(Filter::author("bob"));
+    let names: Vec<_> = bot.commands().into_iter().map(|it| it.name).collect();
+    assert_eq!(names, ["hello"]);
+
+    let github = MockGitHub::new();
+    let dispatcher = testing::dispatcher(bot, &github);
+    for user in &["alice", "bob"] {
+        let event = events::comment_created().body("/hello").by(user).build();
+        dispatcher.dispatch_event(event).await;
+    }
+    assert_eq!(*log.lock().unwrap(), ["bob"]);
+}
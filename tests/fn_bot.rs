@@ -0,0 +1,33 @@
+mod common;
+
+use baipiao_bot_rust::commands::CommandSpec;
+use baipiao_bot_rust::Dispatcher;
+use std::sync::{Arc, Mutex};
+
+#[tokio::test]
+async fn closures_receive_events_and_commands() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let (on_issue, on_command) = (log.clone(), log.clone());
+    let dispatcher = Dispatcher::builder()
+        .on_issue_created(move |ctx, event| {
+            let log = on_issue.clone();
+            async move {
+                log.lock()
+                    .unwrap()
+                    .push(format!("{} opened #{}", ctx.sender.login, event.id));
+            }
+        })
+        .on_command(vec![CommandSpec::new("retest")], move |_ctx, command| {
+            let log = on_command.clone();
+            async move {
+                log.lock().unwrap().push(format!("/{}", command.name));
+            }
+        })
+        .build();
+
+    dispatcher
+        .dispatch_event(common::issue_opened("alice", "/retest"))
+        .await;
+
+    assert_eq!(*log.lock().unwrap(), ["alice opened #1", "/retest"]);
+}
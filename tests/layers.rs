@@ -0,0 +1,63 @@
+mod common;
+
+use async_trait::async_trait;
+use baipiao_bot_rust::layers::{CatchPanic, Layer, Next, Outcome, Retry};
+use baipiao_bot_rust::{Bot, Context, Dispatcher, Event, IssueCreatedEvent};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct FlakyBot {
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Bot for FlakyBot {
+    async fn on_issue_created(&self, _ctx: &Context, _event: IssueCreatedEvent) {
+        if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            panic!("flaky");
+        }
+    }
+}
+
+struct SkipAll;
+
+#[async_trait]
+impl Layer for SkipAll {
+    async fn call(&self, _ctx: &mut Context, event: Event, _next: Next<'_>) -> Outcome {
+        Outcome::Skipped(event.name().to_string())
+    }
+}
+
+#[tokio::test]
+async fn retries_after_caught_panic() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let dispatcher = Dispatcher::new(FlakyBot {
+        calls: calls.clone(),
+    })
+    .layer(Retry { attempts: 2 })
+    .layer(CatchPanic);
+
+    let outcome = dispatcher
+        .dispatch_event(common::issue_opened("alice", "hi"))
+        .await;
+
+    assert_eq!(outcome, Outcome::Handled);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn layers_can_short_circuit() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let dispatcher = Dispatcher::new(FlakyBot {
+        calls: calls.clone(),
+    })
+    .layer(SkipAll)
+    .layer(CatchPanic);
+
+    let outcome = dispatcher
+        .dispatch_event(common::issue_opened("alice", "hi"))
+        .await;
+
+    assert_eq!(outcome, Outcome::Skipped("issue_created".to_string()));
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
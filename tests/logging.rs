@@ -0,0 +1,102 @@
+mod common;
+
+use async_trait::async_trait;
+use baipiao_bot_rust::auth::Auth;
+use baipiao_bot_rust::layers::Logging;
+use baipiao_bot_rust::router::Router;
+use baipiao_bot_rust::testing::{self, events, MockGitHub};
+use baipiao_bot_rust::{Bot, Context, GitHub, GitHubApi, IssueCreatedEvent, Repository};
+use common::{capture, Response, StubServer};
+use serde_json::json;
+
+struct Triage;
+
+#[async_trait]
+impl Bot for Triage {
+    async fn on_issue_created(&self, ctx: &Context, event: IssueCreatedEvent) {
+        ctx.record("label", "bug");
+        ctx.record("votes", 3);
+        ctx.github
+            .add_labels(&ctx.repo, event.id, &["bug".to_string()])
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn events_get_a_span() {
+    let (lines, _guard) = capture();
+    let github = MockGitHub::new();
+    testing::dispatcher(Router::new().handler("triage", Triage), &github)
+        .dispatch_event(events::issue_opened().number(2).build())
+        .await;
+
+    let lines = lines.json();
+    let dispatched = lines
+        .iter()
+        .find(|line| line["fields"]["message"] == "event dispatched")
+        .unwrap();
+    let span = &dispatched["span"];
+    assert_eq!(span["name"], "event");
+    assert_eq!(span["event"], "issues");
+    assert_eq!(span["action"], "opened");
+    assert_eq!(span["repo"], "octocat/hello-world");
+    assert_eq!(span["target"], 2);
+    assert_eq!(span["fields"], "label=bug votes=3");
+    assert_eq!(span["outcome"], "handled");
+
+    let handler = lines
+        .iter()
+        .find(|line| line["span"]["name"] == "handler")
+        .unwrap();
+    assert_eq!(handler["span"]["handler"], "triage");
+    assert_eq!(handler["spans"][0]["name"], "event");
+}
+
+#[tokio::test]
+async fn api_calls_get_a_span() {
+    let (lines, _guard) = capture();
+    let server = StubServer::start(|_| {
+        Response::json(
+            201,
+            json!({ "id": 7, "body": "hi", "user": { "login": "baipiao-bot" } }),
+        )
+    });
+    let github = GitHub::new(Auth::None).with_base_url(&server.url);
+    let repo = Repository {
+        owner: "baipiao-bot".to_string(),
+        name: "baipiao-bot-rust".to_string(),
+    };
+    github.create_comment(&repo, 2, "hi").await.unwrap();
+
+    let call = lines
+        .json()
+        .into_iter()
+        .find(|line| line["span"]["name"] == "github_api")
+        .unwrap();
+    assert_eq!(call["span"]["method"], "POST");
+    assert_eq!(
+        call["span"]["path"],
+        "/repos/baipiao-bot/baipiao-bot-rust/issues/2/comments"
+    );
+    assert_eq!(call["span"]["status"], 201);
+}
+
+#[tokio::test]
+async fn logging_layer_logs_inside_the_event_span() {
+    let (lines, _guard) = capture();
+    let github = MockGitHub::new();
+    testing::dispatcher(Triage, &github)
+        .layer(Logging)
+        .dispatch_event(events::issue_opened().number(2).build())
+        .await;
+
+    let finished = lines
+        .json()
+        .into_iter()
+        .find(|line| line["fields"]["message"] == "finished")
+        .unwrap();
+    assert_eq!(finished["fields"]["event"], "issue_created");
+    assert_eq!(finished["fields"]["outcome"], "handled");
+    assert_eq!(finished["span"]["name"], "event");
+}
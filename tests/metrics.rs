@@ -0,0 +1,189 @@
+mod common;
+
+use async_trait::async_trait;
+use baipiao_bot_rust::auth::Auth;
+use baipiao_bot_rust::metrics::Metrics;
+use baipiao_bot_rust::router::Router;
+use baipiao_bot_rust::server::Server;
+use baipiao_bot_rust::testing::{self, events, MockGitHub};
+use baipiao_bot_rust::{Bot, Context, Dispatcher, GitHub, GitHubApi, IssueCreatedEvent};
+use common::{Response, StubServer};
+use ring::hmac;
+use serde_json::json;
+use std::net::TcpListener;
+use std::time::Duration;
+
+struct Flaky;
+
+#[async_trait]
+impl Bot for Flaky {
+    async fn on_issue_created(&self, _ctx: &Context, event: IssueCreatedEvent) {
+        if event.title.contains("crash") {
+            panic!("cannot triage");
+        }
+    }
+}
+
+#[test]
+fn renders_the_text_format() {
+    let metrics = Metrics::new();
+    metrics.increment(
+        "baipiao_api_calls_total",
+        &[("method", "GET"), ("status", "200")],
+    );
+    metrics.increment(
+        "baipiao_api_calls_total",
+        &[("method", "GET"), ("status", "200")],
+    );
+    metrics.set(
+        "baipiao_rate_limit_remaining",
+        &[("resource", "core")],
+        4999.0,
+    );
+    metrics.observe(
+        "baipiao_dispatch_duration_seconds",
+        &[("event", "issues")],
+        Duration::from_millis(300),
+    );
+    let text = metrics.render();
+    assert!(text.starts_with(
+        "# HELP baipiao_dispatch_duration_seconds Time taken to dispatch an event, by event.\n\
+         # TYPE baipiao_dispatch_duration_seconds histogram\n\
+         baipiao_dispatch_duration_seconds_bucket{event=\"issues\",le=\"0.05\"} 0\n"
+    ));
+    assert!(
+        text.contains("baipiao_dispatch_duration_seconds_bucket{event=\"issues\",le=\"0.5\"} 1\n")
+    );
+    assert!(
+        text.contains("baipiao_dispatch_duration_seconds_bucket{event=\"issues\",le=\"+Inf\"} 1\n")
+    );
+    assert!(text.contains("baipiao_dispatch_duration_seconds_count{event=\"issues\"} 1\n"));
+    assert!(text.contains(
+        "# TYPE baipiao_api_calls_total counter\n\
+         baipiao_api_calls_total{method=\"GET\",status=\"200\"} 2\n"
+    ));
+    assert!(text.contains("baipiao_rate_limit_remaining{resource=\"core\"} 4999\n"));
+    assert!(!text.contains("baipiao_events_received_total"));
+}
+
+#[tokio::test]
+async fn dispatcher_records_events_and_outcomes() {
+    let github = MockGitHub::new();
+    let dispatcher = testing::dispatcher(Router::new().handler("flaky", Flaky), &github);
+    for title in &["crash on start", "typo"] {
+        dispatcher
+            .dispatch_event(events::issue_opened().title(title).build())
+            .await;
+    }
+    dispatcher
+        .dispatch_event(events::issue_closed().build())
+        .await;
+
+    let metrics = dispatcher.metrics();
+    let received = |action| {
+        metrics.counter(
+            "baipiao_events_received_total",
+            &[("event", "issues"), ("action", action)],
+        )
+    };
+    assert_eq!(received("opened"), 2);
+    assert_eq!(received("closed"), 1);
+    let dispatches = |outcome| {
+        metrics.counter(
+            "baipiao_dispatches_total",
+            &[("event", "issues"), ("outcome", outcome)],
+        )
+    };
+    // a failed handler fails the event
+    assert_eq!(dispatches("handled"), 2);
+    assert_eq!(dispatches("failed"), 1);
+    let handler = |outcome| {
+        metrics.counter(
+            "baipiao_handler_outcomes_total",
+            &[("handler", "flaky"), ("outcome", outcome)],
+        )
+    };
+    assert_eq!(handler("handled"), 2);
+    assert_eq!(handler("failed"), 1);
+    assert!(metrics
+        .render()
+        .contains("baipiao_dispatch_duration_seconds_count{event=\"issues\"} 3\n"));
+}
+
+#[tokio::test]
+async fn client_records_calls_and_rate_limits() {
+    let server = StubServer::start(|_| {
+        Response::json(
+            200,
+            json!({ "permission": "write", "user": { "login": "alice" } }),
+        )
+        .header("X-RateLimit-Remaining", "4321")
+        .header("X-RateLimit-Resource", "core")
+    });
+    let metrics = Metrics::new();
+    let github = GitHub::new(Auth::None)
+        .with_base_url(&server.url)
+        .with_metrics(metrics.clone());
+    github
+        .collaborator_permission(&common::context().repo, "alice")
+        .await
+        .unwrap();
+    assert_eq!(
+        metrics.counter(
+            "baipiao_api_calls_total",
+            &[("method", "GET"), ("status", "200")]
+        ),
+        1
+    );
+    assert_eq!(
+        metrics.gauge("baipiao_rate_limit_remaining", &[("resource", "core")]),
+        Some(4321.0)
+    );
+}
+
+#[tokio::test]
+async fn server_receives_webhooks_and_serves_metrics() {
+    let github = MockGitHub::new();
+    let dispatcher = Dispatcher::new(Flaky)
+        .with_api(github.api())
+        .with_login("baipiao-bot")
+        .with_step_summary(false);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(Server::new(dispatcher).secret("s3cret").serve_on(listener));
+
+    let body = events::issue_opened().number(2).build()["event"].to_string();
+    let key = hmac::Key::new(hmac::HMAC_SHA256, b"s3cret");
+    let signature: String = hmac::sign(&key, body.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let http = reqwest::Client::new();
+    let deliver = |signature: String| {
+        http.post(format!("{}/", url))
+            .header("X-GitHub-Event", "issues")
+            .header("X-GitHub-Delivery", "72d3162e")
+            .header("X-Hub-Signature-256", format!("sha256={}", signature))
+            .body(body.clone())
+            .send()
+    };
+    let response = deliver(signature).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "handled\n");
+    let forged = deliver("00".repeat(32)).await.unwrap();
+    assert_eq!(forged.status(), 401);
+
+    let metrics = http
+        .get(format!("{}/metrics", url))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(
+        metrics.contains("baipiao_events_received_total{event=\"issues\",action=\"opened\"} 1\n")
+    );
+    assert!(metrics.contains("baipiao_dispatches_total{event=\"issues\",outcome=\"handled\"} 1\n"));
+}
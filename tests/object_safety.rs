@@ -0,0 +1,64 @@
+mod common;
+
+use async_trait::async_trait;
+use baipiao_bot_rust::layers::{Layer, Outcome};
+use baipiao_bot_rust::router::Router;
+use baipiao_bot_rust::{Bot, Context, Dispatcher, Event, IssueCreatedEvent};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Counter(AtomicUsize);
+
+#[async_trait]
+impl Bot for Counter {
+    async fn on_issue_created(&self, _ctx: &Context, _event: IssueCreatedEvent) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn public_types_are_object_safe_and_thread_safe() {
+    let _: Option<&dyn Bot> = None;
+    let _: Option<&dyn Layer> = None;
+    assert_send_sync::<Box<dyn Bot>>();
+    assert_send_sync::<Arc<dyn Bot>>();
+    assert_send_sync::<Context>();
+    assert_send_sync::<Event>();
+    assert_send_sync::<Dispatcher<Arc<dyn Bot>>>();
+}
+
+/// Picks a bot from configuration at runtime.
+fn bot_named(name: &str, counter: Arc<Counter>) -> Arc<dyn Bot> {
+    match name {
+        "counter" => counter,
+        _ => Arc::new(Router::new()),
+    }
+}
+
+#[tokio::test]
+async fn dispatcher_accepts_shared_bots() {
+    let counter = Arc::new(Counter(AtomicUsize::new(0)));
+    let dispatcher = Dispatcher::new(bot_named("counter", counter.clone()));
+
+    let outcome = dispatcher
+        .dispatch_event(common::issue_opened("alice", "hi"))
+        .await;
+
+    assert_eq!(outcome, Outcome::Handled);
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn router_accepts_boxed_bots() {
+    let counter = Arc::new(Counter(AtomicUsize::new(0)));
+    let boxed: Box<dyn Bot> = Box::new(counter.clone());
+    let dispatcher = Dispatcher::new(Router::new().handler("boxed", boxed));
+
+    dispatcher
+        .dispatch_event(common::issue_opened("alice", "hi"))
+        .await;
+
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use async_trait::async_trait;
+use baipiao_bot_rust::commands::{Command, CommandSpec};
+use baipiao_bot_rust::permissions::Role;
+use baipiao_bot_rust::testing::{self, events, MockGitHub};
+use baipiao_bot_rust::AuthorAssociation::{self, Collaborator, Contributor, Member, Owner};
+use baipiao_bot_rust::{Bot, Context};
+use std::sync::{Arc, Mutex};
+
+/// Logs the commands it is allowed to run.
+#[derive(Clone, Default)]
+struct Gate(Arc<Mutex<Vec<String>>>);
+
+#[async_trait]
+impl Bot for Gate {
+    fn commands(&self) -> Vec<CommandSpec> {
+        vec![
+            CommandSpec::new("release").requires(Role::Owner),
+            CommandSpec::new("lock").requires(Role::Maintainer),
+            CommandSpec::new("label").requires(Role::Collaborator),
+            CommandSpec::new("rebase").requires(Role::PullRequestAuthor),
+            CommandSpec::new("approve").requires("team:baipiao-bot/reviewers".parse().unwrap()),
+        ]
+    }
+
+    async fn on_command(&self, _ctx: &Context, command: Command) {
+        self.0.lock().unwrap().push(command.name);
+    }
+}
+
+/// Whether `user`, related to the repository by `association`, may run
+/// `command` in a comment on issue #1, or on pull request #1 if
+/// `on_pull_request`.
+async fn allowed(
+    github: &MockGitHub,
+    command: &str,
+    user: &str,
+    association: AuthorAssociation,
+    on_pull_request: bool,
+) -> bool {
+    let gate = Gate::default();
+    let mut event = events::comment_created()
+        .body(&format!("/{}", command))
+        .by(user)
+        .association(association);
+    if on_pull_request {
+        event = event.on_pull_request();
+    }
+    testing::dispatcher(gate.clone(), github)
+        .dispatch_event(event.build())
+        .await;
+    let ran = gate.0.lock().unwrap().clone();
+    ran == [command]
+}
+
+#[test]
+fn roles_round_trip() {
+    for role in &[
+        "anyone",
+        "owner",
+        "maintainer",
+        "collaborator",
+        "pr-author",
+        "team:baipiao-bot/reviewers",
+    ] {
+        assert_eq!(role.parse::<Role>().unwrap().to_string(), *role);
+    }
+    assert!("team:reviewers".parse::<Role>().is_err());
+    assert!("admin".parse::<Role>().is_err());
+}
+
+#[tokio::test]
+async fn owners_are_told_by_the_association() {
+    let github = MockGitHub::new();
+    assert!(allowed(&github, "release", "octocat", Owner, false).await);
+    // organization members are not owners, whatever their permission
+    github.set_permission("alice", "admin");
+    assert!(!allowed(&github, "release", "alice", Member, false).await);
+}
+
+#[tokio::test]
+async fn maintainers_need_the_maintain_or_admin_permission() {
+    let github = MockGitHub::new();
+    github.set_permission("alice", "maintain");
+    github.set_permission("bob", "write");
+    assert!(allowed(&github, "lock", "alice", Member, false).await);
+    assert!(!allowed(&github, "lock", "bob", Collaborator, false).await);
+}
+
+#[tokio::test]
+async fn outsiders_are_denied_without_asking() {
+    let github = MockGitHub::new();
+    assert!(!allowed(&github, "lock", "eve", Contributor, false).await);
+    assert!(!allowed(&github, "label", "eve", AuthorAssociation::None, false).await);
+    assert!(!github.calls().contains(&"collaborator_permission"));
+    assert_eq!(github.comments_on(1).len(), 2);
+
+    // e.g. a collaborator removed since the event was sent
+    let github = MockGitHub::new();
+    github.fail("collaborator_permission", 404);
+    assert!(!allowed(&github, "lock", "mallory", Collaborator, false).await);
+    github.assert_commented(
+        1,
+        "@mallory you are not allowed to do this: `/lock` requires maintainer.",
+    );
+}
+
+#[tokio::test]
+async fn collaborators_need_write_access() {
+    let github = MockGitHub::new();
+    github.set_permission("alice", "write");
+    github.set_permission("bob", "read");
+    github.set_permission("carol", "triage");
+    assert!(allowed(&github, "label", "alice", Member, false).await);
+    // read-only and triage collaborators share the association
+    assert!(!allowed(&github, "label", "bob", Collaborator, false).await);
+    assert!(!allowed(&github, "label", "carol", Collaborator, false).await);
+
+    let github = MockGitHub::new();
+    assert!(!allowed(&github, "label", "dave", Contributor, false).await);
+    assert!(!github.calls().contains(&"collaborator_permission"));
+}
+
+#[tokio::test]
+async fn pull_request_authors_may_run_commands_on_their_pull_requests() {
+    let github = MockGitHub::new();
+    github.add_issue(1, "Fix typo", "alice");
+    github.set_permission("carol", "write");
+    assert!(allowed(&github, "rebase", "alice", Contributor, true).await);
+    assert!(allowed(&github, "rebase", "carol", Collaborator, true).await);
+    assert!(!allowed(&github, "rebase", "bob", Contributor, true).await);
+    assert!(!allowed(&github, "rebase", "alice", Contributor, false).await);
+}
+
+#[tokio::test]
+async fn team_members_are_looked_up() {
+    let github = MockGitHub::new();
+    github.add_team_member("baipiao-bot", "reviewers", "alice");
+    assert!(allowed(&github, "approve", "alice", AuthorAssociation::None, false).await);
+    assert!(!allowed(&github, "approve", "bob", Member, false).await);
+}
+
+#[tokio::test]
+async fn denied_commands_are_answered_and_audited() {
+    let (lines, _guard) = common::capture();
+    let github = MockGitHub::new();
+    github.set_permission("bob", "read");
+    assert!(!allowed(&github, "label", "bob", Collaborator, false).await);
+
+    github.assert_commented(
+        1,
+        "@bob you are not allowed to do this: `/label` requires collaborator.",
+    );
+    let audit = lines
+        .json()
+        .into_iter()
+        .find(|line| line["target"] == "baipiao::audit")
+        .expect("an audit entry");
+    assert_eq!(audit["fields"]["message"], "denied command");
+    assert_eq!(audit["fields"]["user"], "bob");
+    assert_eq!(audit["fields"]["role"], "collaborator");
+}
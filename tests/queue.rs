@@ -0,0 +1,170 @@
+use async_trait::async_trait;
+use baipiao_bot_rust::payload;
+use baipiao_bot_rust::server::{Queue, Server};
+use baipiao_bot_rust::testing::{self, events, MockGitHub};
+use baipiao_bot_rust::{Bot, Context, IssueCreatedEvent};
+use serde_json::json;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Logs the title of each opened issue once handled; titles containing
+/// "slow" take a while, and with `hang` those containing "hang" never end.
+#[derive(Clone, Default)]
+struct Recorder {
+    log: Arc<Mutex<Vec<String>>>,
+    hang: bool,
+}
+
+impl Recorder {
+    fn log(&self) -> Vec<String> {
+        self.log.lock().unwrap().clone()
+    }
+
+    async fn wait_for(&self, count: usize) -> Vec<String> {
+        for _ in 0..200 {
+            if self.log.lock().unwrap().len() >= count {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        self.log()
+    }
+}
+
+#[async_trait]
+impl Bot for Recorder {
+    async fn on_issue_created(&self, _ctx: &Context, event: IssueCreatedEvent) {
+        if self.hang && event.title.contains("hang") {
+            futures::future::pending::<()>().await;
+        }
+        if event.title.contains("slow") {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+        }
+        self.log.lock().unwrap().push(event.title);
+    }
+}
+
+fn start(server: Server<Recorder>) -> (String, tokio::task::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let handle = tokio::spawn(async move { server.serve_on(listener).await.unwrap() });
+    (url, handle)
+}
+
+fn server(bot: &Recorder, queue: Queue) -> Server<Recorder> {
+    let github = MockGitHub::new();
+    Server::new(testing::dispatcher(bot.clone(), &github)).queue(queue)
+}
+
+async fn deliver(url: &str, number: usize, title: &str) -> (u16, String) {
+    let body = events::issue_opened().number(number).title(title).build()["event"].to_string();
+    let response = reqwest::Client::new()
+        .post(url)
+        .header("X-GitHub-Event", "issues")
+        .header("X-GitHub-Delivery", title)
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    (response.status().as_u16(), response.text().await.unwrap())
+}
+
+fn spool(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("baipiao-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn spooled(dir: &Path) -> usize {
+    std::fs::read_dir(dir).map_or(0, |it| it.count())
+}
+
+#[tokio::test]
+async fn events_of_an_issue_are_dispatched_in_order() {
+    let bot = Recorder::default();
+    let (url, _) = start(server(&bot, Queue::new()));
+    assert_eq!(deliver(&url, 1, "1 slow").await, (202, "queued\n".into()));
+    deliver(&url, 1, "1 fast").await;
+    deliver(&url, 2, "2 fast").await;
+    // the other issue does not wait for the slow event
+    assert_eq!(bot.wait_for(3).await, ["2 fast", "1 slow", "1 fast"]);
+}
+
+#[tokio::test]
+async fn a_full_queue_turns_events_away() {
+    let bot = Recorder {
+        hang: true,
+        ..Recorder::default()
+    };
+    let (url, _) = start(server(&bot, Queue::new().capacity(1)));
+    assert_eq!(deliver(&url, 1, "hang").await.0, 202);
+    assert_eq!(
+        deliver(&url, 2, "fast").await,
+        (503, "the queue is full\n".into())
+    );
+}
+
+#[tokio::test]
+async fn shutting_down_drains_the_queue() {
+    let bot = Recorder::default();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = server(&bot, Queue::new()).shutdown_on(async {
+        stopped.await.ok();
+    });
+    let (url, handle) = start(server);
+    deliver(&url, 1, "1 slow").await;
+    deliver(&url, 2, "2 slow").await;
+    stop.send(()).unwrap();
+    handle.await.unwrap();
+    assert_eq!(bot.log().len(), 2);
+}
+
+#[tokio::test]
+async fn spooled_events_survive_a_restart() {
+    let dir = spool("restart");
+    let hanging = Recorder {
+        hang: true,
+        ..Recorder::default()
+    };
+    let (url, handle) = start(server(&hanging, Queue::new().spool(&dir)));
+    assert_eq!(deliver(&url, 1, "hang").await.0, 202);
+    assert_eq!(spooled(&dir), 1);
+    handle.abort();
+
+    let bot = Recorder::default();
+    start(server(&bot, Queue::new().spool(&dir)));
+    assert_eq!(bot.wait_for(1).await, ["hang"]);
+    for _ in 0..100 {
+        if spooled(&dir) == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(spooled(&dir), 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_large_or_damaged_spool_does_not_hold_up_startup() {
+    let dir = spool("backlog");
+    std::fs::create_dir_all(&dir).unwrap();
+    for (i, title) in ["1 slow", "2 slow", "3 slow"].iter().enumerate() {
+        let event = events::issue_opened().number(i + 1).title(title).build()["event"].clone();
+        let spooled = json!({ "delivery": title, "payload": payload::wrap("issues", event) });
+        std::fs::write(dir.join(format!("{}.json", i + 1)), spooled.to_string()).unwrap();
+    }
+    std::fs::write(dir.join("0.json"), "{ truncated").unwrap();
+
+    let bot = Recorder::default();
+    let (url, _) = start(server(&bot, Queue::new().capacity(1).spool(&dir)));
+    let metrics = reqwest::get(format!("{}metrics", url)).await.unwrap();
+    assert_eq!(metrics.status(), 200);
+    assert!(bot.log().is_empty());
+
+    assert_eq!(bot.wait_for(3).await, ["1 slow", "2 slow", "3 slow"]);
+    assert!(dir.join("0.json.failed").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
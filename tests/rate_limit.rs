@@ -0,0 +1,176 @@
+mod common;
+
+use baipiao_bot_rust::auth::Auth;
+use baipiao_bot_rust::client::ClientError;
+use baipiao_bot_rust::rate_limit::RateLimits;
+use baipiao_bot_rust::{GitHub, GitHubApi, Repository};
+use chrono::Utc;
+use common::{Response, StubServer};
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+fn repo() -> Repository {
+    Repository {
+        owner: "baipiao-bot".to_string(),
+        name: "baipiao-bot-rust".to_string(),
+    }
+}
+
+fn comment() -> Response {
+    Response::json(
+        201,
+        json!({ "id": 7, "body": "hi", "user": { "login": "baipiao-bot" } }),
+    )
+}
+
+fn issue() -> Response {
+    Response::json(
+        200,
+        json!({ "number": 2, "title": "crash", "body": null, "user": { "login": "alice" } }),
+    )
+}
+
+fn client(server: &StubServer) -> GitHub {
+    GitHub::new(Auth::None)
+        .with_base_url(&server.url)
+        .with_rate_limits(RateLimits {
+            backoff: Duration::from_millis(10),
+            max_wait: Duration::from_secs(5),
+            ..RateLimits::default()
+        })
+}
+
+#[tokio::test]
+async fn reads_are_retried_after_retry_after() {
+    let calls = AtomicUsize::new(0);
+    let server = StubServer::start(move |_| {
+        if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            Response::json(429, json!({ "message": "slow down" })).header("Retry-After", "0")
+        } else {
+            issue()
+        }
+    });
+    let issue = client(&server).issue(&repo(), 2).await.unwrap();
+    assert_eq!(issue.number, 2);
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn secondary_limits_back_off_until_retries_run_out() {
+    let server = StubServer::start(|_| {
+        Response::json(
+            403,
+            json!({ "message": "You have exceeded a secondary rate limit. Please wait a few minutes before you try again." }),
+        )
+    });
+    let err = client(&server)
+        .with_rate_limits(RateLimits {
+            retries: 2,
+            backoff: Duration::from_millis(10),
+            ..RateLimits::default()
+        })
+        .issue(&repo(), 2)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ClientError::RateLimited {
+            secondary: true,
+            retry_at: None
+        }
+    ));
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn an_exhausted_budget_is_not_waited_out() {
+    let reset = (Utc::now().timestamp() + 3600).to_string();
+    let server = StubServer::start(move |_| {
+        Response::json(403, json!({ "message": "API rate limit exceeded" }))
+            .header("X-RateLimit-Remaining", "0")
+            .header("X-RateLimit-Reset", &reset)
+    });
+    let err = client(&server).issue(&repo(), 2).await.unwrap_err();
+    match &err {
+        ClientError::RateLimited {
+            secondary: false,
+            retry_at: Some(retry_at),
+        } => assert!(*retry_at > Utc::now() + chrono::Duration::minutes(59)),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(err
+        .to_string()
+        .starts_with("the rate limit of GitHub was hit, retry after "));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn writes_are_not_retried() {
+    let server = StubServer::start(|_| {
+        Response::json(429, json!({ "message": "slow down" })).header("Retry-After", "0")
+    });
+    let err = client(&server)
+        .create_comment(&repo(), 2, "hi")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ClientError::RateLimited {
+            secondary: true,
+            ..
+        }
+    ));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn writes_are_queued_within_the_content_limits() {
+    let server = StubServer::start(|_| comment());
+    let github = client(&server).with_rate_limits(RateLimits {
+        writes_per_minute: 2,
+        max_wait: Duration::from_secs(5),
+        ..RateLimits::default()
+    });
+    // clones for the same installation share the queue
+    let same = github.installation(None);
+    github.create_comment(&repo(), 2, "one").await.unwrap();
+    same.create_comment(&repo(), 2, "two").await.unwrap();
+    let err = github
+        .create_comment(&repo(), 2, "three")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ClientError::RateLimited { .. }));
+    assert_eq!(server.requests().len(), 2);
+    // reads do not count
+    let server = StubServer::start(|_| issue());
+    let github = github.with_base_url(&server.url);
+    github.issue(&repo(), 2).await.unwrap();
+}
+
+#[tokio::test]
+async fn installations_do_not_wait_for_each_other() {
+    let server = StubServer::start(|_| comment());
+    let github = client(&server).with_rate_limits(RateLimits {
+        writes_per_minute: 1,
+        max_wait: Duration::from_secs(120),
+        ..RateLimits::default()
+    });
+    let first = github.installation(Some(1));
+    first.create_comment(&repo(), 2, "one").await.unwrap();
+    // waits out the minute
+    let throttled = tokio::spawn(async move { first.create_comment(&repo(), 2, "two").await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let second = github.installation(Some(2));
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        second.create_comment(&repo(), 2, "three"),
+    )
+    .await
+    .expect("not held up by the other installation")
+    .unwrap();
+    assert!(!throttled.is_finished());
+    throttled.abort();
+    assert_eq!(server.requests().len(), 2);
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use async_trait::async_trait;
+use baipiao_bot_rust::layers::Outcome;
+use baipiao_bot_rust::router::{HandlerOutcome, Router};
+use baipiao_bot_rust::subscriptions::Subscriptions;
+use baipiao_bot_rust::testing::{self, events, MockGitHub};
+use baipiao_bot_rust::{Bot, Context, Event};
+use std::sync::{Arc, Mutex};
+
+struct Recorder {
+    name: &'static str,
+    log: Arc<Mutex<Vec<&'static str>>>,
+    receive_bot_events: bool,
+}
+
+#[async_trait]
+impl Bot for Recorder {
+    async fn on_issue_closed(&self, _ctx: &Context, _issue_id: usize) {
+        self.log.lock().unwrap().push(self.name);
+    }
+
+    fn receive_bot_events(&self) -> bool {
+        self.receive_bot_events
+    }
+}
+
+/// Only wants new issues, but would log any other event too.
+struct Greeter(Arc<Mutex<Vec<&'static str>>>);
+
+#[async_trait]
+impl Bot for Greeter {
+    async fn handle(&self, _ctx: &Context, _event: Event) {
+        self.0.lock().unwrap().push("greeter");
+    }
+
+    fn subscriptions(&self) -> Subscriptions {
+        Subscriptions::none().event("issue_created")
+    }
+}
+
+struct Panicking;
+
+#[async_trait]
+impl Bot for Panicking {
+    async fn on_issue_closed(&self, _ctx: &Context, _issue_id: usize) {
+        panic!("boom");
+    }
+}
+
+fn router(log: &Arc<Mutex<Vec<&'static str>>>) -> Router {
+    Router::new()
+        .handler(
+            "welcome",
+            Recorder {
+                name: "welcome",
+                log: log.clone(),
+                receive_bot_events: false,
+            },
+        )
+        .handler("broken", Panicking)
+        .handler(
+            "labeler",
+            Recorder {
+                name: "labeler",
+                log: log.clone(),
+                receive_bot_events: true,
+            },
+        )
+}
+
+fn outcome(name: &str, outcome: Outcome) -> HandlerOutcome {
+    HandlerOutcome {
+        name: name.to_string(),
+        outcome,
+    }
+}
+
+#[tokio::test]
+async fn isolates_failing_handlers() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let outcomes = router(&log)
+        .dispatch(&common::context(), Event::IssueClosed(1))
+        .await;
+
+    assert_eq!(
+        outcomes,
+        [
+            outcome("welcome", Outcome::Handled),
+            outcome("broken", Outcome::Failed("boom".to_string())),
+            outcome("labeler", Outcome::Handled),
+        ]
+    );
+    assert_eq!(*log.lock().unwrap(), ["welcome", "labeler"]);
+}
+
+#[tokio::test]
+async fn only_opted_in_handlers_see_bot_events() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut ctx = common::context();
+    ctx.ignored_sender = true;
+
+    let outcomes = router(&log)
+        .concurrent()
+        .dispatch(&ctx, Event::IssueClosed(1))
+        .await;
+
+    assert!(matches!(outcomes[0].outcome, Outcome::Skipped(_)));
+    assert_eq!(outcomes[2], outcome("labeler", Outcome::Handled));
+    assert_eq!(*log.lock().unwrap(), ["labeler"]);
+}
+
+#[tokio::test]
+async fn handlers_only_see_their_subscriptions() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let router = router(&log).handler("greeter", Greeter(log.clone()));
+    assert!(router.subscriptions().has("issue_created"));
+
+    let outcomes = router
+        .dispatch(&common::context(), Event::IssueClosed(1))
+        .await;
+
+    assert_eq!(
+        outcomes[3],
+        outcome(
+            "greeter",
+            Outcome::Skipped("not subscribed to issue_closed".to_string())
+        )
+    );
+    assert_eq!(*log.lock().unwrap(), ["welcome", "labeler"]);
+}
+
+#[tokio::test]
+async fn dispatcher_reports_failed_handlers() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let github = MockGitHub::new();
+    let outcome = testing::dispatcher(router(&log), &github)
+        .dispatch_event(events::issue_closed().build())
+        .await;
+    assert_eq!(
+        outcome,
+        Outcome::Failed("handler `broken`: boom".to_string())
+    );
+    assert_eq!(*log.lock().unwrap(), ["welcome", "labeler"]);
+}
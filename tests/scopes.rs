@@ -0,0 +1,208 @@
+mod common;
+
+use async_trait::async_trait;
+use baipiao_bot_rust::auth::Auth;
+use baipiao_bot_rust::client::ClientError;
+use baipiao_bot_rust::layers::Outcome;
+use baipiao_bot_rust::router::Router;
+use baipiao_bot_rust::scopes::{Access, Manifest, Scope};
+use baipiao_bot_rust::subscriptions::Subscriptions;
+use baipiao_bot_rust::testing::{self, events, MockGitHub};
+use baipiao_bot_rust::{Bot, Context, GitHub, GitHubApi, IssueCreatedEvent, Repository};
+use common::{Response, StubServer};
+use serde_json::json;
+use std::collections::HashMap;
+
+struct Labeller;
+
+#[async_trait]
+impl Bot for Labeller {
+    async fn on_issue_created(&self, ctx: &Context, event: IssueCreatedEvent) {
+        ctx.github
+            .add_labels(&ctx.repo, event.id, &["triage".to_string()])
+            .await
+            .unwrap();
+    }
+
+    fn subscriptions(&self) -> Subscriptions {
+        Subscriptions::none().event("issue_created")
+    }
+
+    fn permissions(&self) -> Manifest {
+        Manifest::new().operation("add_labels")
+    }
+}
+
+struct Reviewer;
+
+#[async_trait]
+impl Bot for Reviewer {
+    fn subscriptions(&self) -> Subscriptions {
+        Subscriptions::none().event("pull_request_created")
+    }
+
+    fn permissions(&self) -> Manifest {
+        Manifest::new()
+            .operation("create_review")
+            .operation("pull_request_files")
+            .operation("file")
+    }
+}
+
+fn repo() -> Repository {
+    Repository {
+        owner: "baipiao-bot".to_string(),
+        name: "baipiao-bot-rust".to_string(),
+    }
+}
+
+#[test]
+fn manifests_aggregate() {
+    let router = Router::new()
+        .handler("labeller", Labeller)
+        .handler("reviewer", Reviewer);
+    let manifest = router.permissions();
+    assert_eq!(
+        manifest.scopes().collect::<Vec<_>>(),
+        vec![
+            (Scope::Contents, Access::Read),
+            (Scope::Issues, Access::Write),
+            (Scope::PullRequests, Access::Write),
+        ]
+    );
+
+    let guessed = Manifest::for_subscriptions(&Subscriptions::none().event("issue_closed"));
+    assert_eq!(guessed.access(Scope::Issues), Some(Access::Write));
+    assert_eq!(guessed.access(Scope::PullRequests), None);
+}
+
+#[test]
+#[should_panic(expected = "unknown operation `merge`")]
+fn unknown_operations_panic() {
+    Manifest::new().operation("merge");
+}
+
+#[test]
+fn missing_names_the_operations() {
+    let needed = Labeller.permissions().union(&Reviewer.permissions());
+    let granted = Manifest::new()
+        .scope(Scope::Contents, Access::Write, "")
+        .scope(Scope::Issues, Access::Read, "");
+    assert_eq!(
+        needed.missing(&granted).unwrap().to_string(),
+        "the token lacks `issues: write` (for add_labels), \
+         `pull-requests: write` (for create_review, pull_request_files); \
+         grant it to the token in `BAIPIAO_BOT_TOKEN` or in the app's settings"
+    );
+    assert_eq!(needed.missing(&needed), None);
+}
+
+#[test]
+fn grants_are_parsed() {
+    let app: HashMap<String, String> = [("pull_requests", "write"), ("checks", "write")]
+        .iter()
+        .map(|(scope, access)| (scope.to_string(), access.to_string()))
+        .collect();
+    assert_eq!(
+        Manifest::from_app(&app).scopes().collect::<Vec<_>>(),
+        vec![(Scope::PullRequests, Access::Write)]
+    );
+
+    let classic = Manifest::from_oauth_scopes("read:org, repo");
+    assert_eq!(classic.access(Scope::Issues), Some(Access::Write));
+    assert_eq!(classic.access(Scope::Members), Some(Access::Read));
+    let bare = Manifest::from_oauth_scopes("");
+    assert_eq!(bare.access(Scope::Issues), Some(Access::Read));
+}
+
+#[tokio::test]
+async fn dispatcher_fails_early() {
+    let github = MockGitHub::new();
+    github.grant(Manifest::new().scope(Scope::Issues, Access::Read, ""));
+    let outcome = testing::dispatcher(Labeller, &github)
+        .with_permission_check(true)
+        .dispatch_event(events::issue_opened().number(2).build())
+        .await;
+    assert_eq!(
+        outcome,
+        Outcome::Failed(
+            "the token lacks `issues: write` (for add_labels); \
+             grant it to the token in `BAIPIAO_BOT_TOKEN` or in the app's settings"
+                .to_string()
+        )
+    );
+    assert_eq!(github.calls(), vec!["granted_permissions"]);
+    github.assert_no_changes();
+
+    // nothing is known of a workflow's own token
+    let (lines, _guard) = common::capture();
+    let github = MockGitHub::new();
+    testing::dispatcher(Labeller, &github)
+        .with_permission_check(true)
+        .dispatch_event(events::issue_opened().number(2).build())
+        .await;
+    github.assert_labelled(2, "triage");
+    assert!(lines.json().iter().any(|line| line["fields"]["message"]
+        == "cannot tell what the token was granted, its permissions are not checked"));
+}
+
+#[tokio::test]
+async fn grants_are_asked_once_per_installation() {
+    let github = MockGitHub::new();
+    github.grant(Manifest::new().scope(Scope::Issues, Access::Write, ""));
+    let dispatcher = testing::dispatcher(Labeller, &github).with_permission_check(true);
+    let mut skipped = events::issue_opened().build();
+    skipped["event"]["action"] = json!("pinned");
+    dispatcher.dispatch_event(skipped).await;
+    assert!(github.calls().is_empty());
+
+    for installation in &[1, 1, 2] {
+        let event = events::issue_opened().number(2).installation(*installation);
+        dispatcher.dispatch_event(event.build()).await;
+    }
+    let asked = github
+        .calls()
+        .into_iter()
+        .filter(|it| *it == "granted_permissions");
+    assert_eq!(asked.count(), 2);
+}
+
+#[tokio::test]
+async fn personal_tokens_list_their_scopes() {
+    let server = StubServer::start(|_| {
+        Response::json(200, json!({ "id": 1 })).header("X-OAuth-Scopes", "public_repo")
+    });
+    let github = GitHub::new(Auth::Token("ghp_x".to_string())).with_base_url(&server.url);
+    let granted = github.granted_permissions(&repo()).await.unwrap().unwrap();
+    assert_eq!(granted.access(Scope::PullRequests), Some(Access::Write));
+    assert_eq!(
+        server.requests()[0].path,
+        "/repos/baipiao-bot/baipiao-bot-rust"
+    );
+
+    let server = StubServer::start(|_| Response::json(200, json!({ "id": 1 })));
+    let github = GitHub::new(Auth::Token("ghs_x".to_string())).with_base_url(&server.url);
+    assert_eq!(github.granted_permissions(&repo()).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn forbidden_names_the_accepted_permissions() {
+    let server = StubServer::start(|_| {
+        Response::json(
+            403,
+            json!({ "message": "Resource not accessible by integration" }),
+        )
+        .header(
+            "X-Accepted-GitHub-Permissions",
+            "issues=write; pull_requests=write",
+        )
+    });
+    let github = GitHub::new(Auth::Token("ghs_x".to_string())).with_base_url(&server.url);
+    match github.close_issue(&repo(), 2).await {
+        Err(ClientError::Status { status, message }) => {
+            assert_eq!(status, 403);
+            assert!(message.ends_with("(needs issues=write; pull_requests=write)"));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use baipiao_bot_rust::state::{
+    BranchStore, CommentStore, DirStore, StateError, StateStore, StateStoreExt,
+};
+use baipiao_bot_rust::testing::MockGitHub;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::{env, fs};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+struct Votes {
+    count: u32,
+    note: String,
+}
+
+/// Every backend behaves the same way.
+async fn round_trip(store: &dyn StateStore) {
+    assert_eq!(store.get::<Votes>("votes").await.unwrap(), None);
+
+    let votes = Votes {
+        count: 1,
+        note: "a --> b".to_string(),
+    };
+    let version = store.put("votes", &votes, None).await.unwrap();
+    let read = store.get::<Votes>("votes").await.unwrap().unwrap();
+    assert_eq!(read.value, votes);
+    assert_eq!(read.version, version);
+
+    // a stale version is rejected
+    store.put("votes", &votes, Some(&version)).await.unwrap();
+    assert!(matches!(
+        store.put("votes", &votes, Some(&version)).await,
+        Err(StateError::Conflict { .. })
+    ));
+    assert!(matches!(
+        store.put("votes", &votes, None).await,
+        Err(StateError::Conflict { .. })
+    ));
+
+    let updated = store
+        .update("votes", |votes: Option<Votes>| {
+            let mut votes = votes.unwrap_or_default();
+            votes.count += 1;
+            votes
+        })
+        .await
+        .unwrap();
+    assert_eq!(updated.count, 2);
+
+    let welcomed: BTreeMap<String, bool> = store
+        .update("welcomed", |welcomed: Option<BTreeMap<String, bool>>| {
+            let mut welcomed = welcomed.unwrap_or_default();
+            welcomed.insert("alice".to_string(), true);
+            welcomed
+        })
+        .await
+        .unwrap();
+    assert_eq!(welcomed.len(), 1);
+
+    assert!(matches!(
+        store.get::<Votes>("../escape").await,
+        Err(StateError::InvalidKey(_))
+    ));
+}
+
+#[tokio::test]
+async fn comment_store() {
+    let github = Arc::new(MockGitHub::default());
+    let repo = common::context().repo;
+    let store = CommentStore::new(github.clone(), repo, 42, "baipiao-bot");
+    round_trip(&store).await;
+
+    let comments = github.comments_on(42);
+    assert_eq!(comments.len(), 2);
+    assert!(comments[0].body.starts_with("<!-- baipiao-state:votes\n"));
+    assert!(!comments[0].body.contains("a --> b"));
+}
+
+#[tokio::test]
+async fn branch_store() {
+    let github = Arc::new(MockGitHub::default());
+    let repo = common::context().repo;
+    round_trip(&BranchStore::new(github.clone(), repo, "baipiao-state")).await;
+    assert!(github.file_on("baipiao-state", "votes.json").is_some());
+}
+
+#[tokio::test]
+async fn dir_store() {
+    let dir = env::temp_dir().join(format!("baipiao-state-{}", std::process::id()));
+    round_trip(&DirStore::new(&dir)).await;
+    assert!(dir.join("welcomed.json").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,121 @@
+use async_trait::async_trait;
+use baipiao_bot_rust::cli::Cli;
+use baipiao_bot_rust::commands::CommandSpec;
+use baipiao_bot_rust::layers::Outcome;
+use baipiao_bot_rust::router::Router;
+use baipiao_bot_rust::subscriptions::{Subscriptions, WorkflowFile};
+use baipiao_bot_rust::testing::{self, events, MockGitHub};
+use baipiao_bot_rust::{Bot, Dispatcher};
+
+struct Welcome;
+
+#[async_trait]
+impl Bot for Welcome {
+    fn subscriptions(&self) -> Subscriptions {
+        Subscriptions::none().event("issue_created")
+    }
+}
+
+struct Closer;
+
+#[async_trait]
+impl Bot for Closer {
+    fn subscriptions(&self) -> Subscriptions {
+        Subscriptions::none()
+            .event("pull_request_closed")
+            .event("issue_closed")
+    }
+}
+
+#[test]
+fn fn_bots_subscribe_to_their_closures() {
+    let dispatcher = Dispatcher::builder()
+        .on_issue_closed(|_, _| async {})
+        .on_command(vec![CommandSpec::new("ping")], |_, _| async {})
+        .build();
+    let subscriptions = dispatcher.subscriptions();
+    let events: Vec<&str> = subscriptions.events().collect();
+    assert_eq!(
+        events,
+        vec![
+            "comment_created",
+            "issue_closed",
+            "issue_created",
+            "pull_request_created"
+        ]
+    );
+    assert!(subscriptions.contains("pull_request", "opened"));
+    assert!(!subscriptions.contains("pull_request", "reopened"));
+}
+
+#[test]
+fn workflow() {
+    let router = Router::new()
+        .handler("welcome", Welcome)
+        .handler("closer", Closer);
+    let yaml = WorkflowFile::new()
+        .name("bot")
+        .render(&router.subscriptions(), &router.permissions());
+    assert_eq!(
+        yaml,
+        "\
+# Generated from the bot's subscriptions, regenerate it when its handlers change.
+name: bot
+on:
+  issues:
+    types: [opened, closed]
+  pull_request:
+    types: [closed]
+
+# only limits GITHUB_TOKEN, used when BAIPIAO_BOT_TOKEN is not set
+permissions:
+  contents: read
+  issues: write
+  pull-requests: write
+
+jobs:
+  run-baipiao-bot:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - name: Run the bot
+        run: cargo run --release
+        env:
+          JSON: ${{ toJson(github) }}
+          BAIPIAO_BOT_TOKEN: ${{ secrets.BAIPIAO_BOT_TOKEN }}
+          GITHUB_TOKEN: ${{ secrets.GITHUB_TOKEN }}
+"
+    );
+    let parsed: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(parsed["permissions"]["issues"], "write");
+}
+
+#[tokio::test]
+async fn unsubscribed_events_are_skipped() {
+    let github = MockGitHub::new();
+    let outcome = testing::dispatcher(Welcome, &github)
+        .dispatch_event(events::issue_closed().build())
+        .await;
+    assert_eq!(
+        outcome,
+        Outcome::Skipped("not subscribed to issue_closed".to_string())
+    );
+    let outcome = testing::dispatcher(Welcome, &github)
+        .dispatch_event(events::issue_opened().build())
+        .await;
+    assert_eq!(outcome, Outcome::Handled);
+}
+
+#[tokio::test]
+async fn cli_prints_the_workflow() {
+    let cli = Cli::new().bot("welcome", Welcome).bot("closer", Closer);
+    let args: Vec<String> = ["workflow", "--bot", "welcome", "--docker", "me/bot"]
+        .iter()
+        .map(|it| it.to_string())
+        .collect();
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    assert_eq!(cli.run_with(&args, &mut out, &mut err).await, 0);
+    let yaml = String::from_utf8(out).unwrap();
+    assert!(yaml.contains("on:\n  issues:\n    types: [opened]\n\n"));
+    assert!(yaml.contains("uses: docker://me/bot\n"));
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use baipiao_bot_rust::templates::{event_vars, Template, TemplateError, Templates};
+use baipiao_bot_rust::Event;
+use serde::Deserialize;
+use serde_json::json;
+
+#[test]
+fn variables_conditionals_and_loops() {
+    let template = Template::parse(
+        "Hi @{{user}}{{#if labels}}, labelled {{#each labels}}`{{this}}` {{/each}}\
+         {{else}}, no labels{{/if}} in {{pr.base}}",
+    )
+    .unwrap();
+    let vars = json!({ "user": "alice", "labels": ["bug", "ui"], "pr": { "base": "main" } });
+    assert_eq!(
+        template.render(&vars),
+        "Hi @alice, labelled `bug` `ui`  in main"
+    );
+    let vars = json!({ "user": "bob", "labels": [] });
+    assert_eq!(template.render(&vars), "Hi @bob, no labels in ");
+}
+
+#[test]
+fn syntax_errors_have_lines() {
+    assert_eq!(
+        Template::parse("ok\n{{#if x}}\nnever closed"),
+        Err(TemplateError::Syntax {
+            line: 3,
+            message: "`{{#if}}` is not closed".to_string()
+        })
+    );
+    assert!(Template::parse("{{/each}}").is_err());
+    assert!(Template::parse("{{user").is_err());
+}
+
+#[derive(Deserialize)]
+struct Settings {
+    templates: Templates,
+}
+
+#[test]
+fn localized_variants() {
+    let settings: Settings = toml::from_str(
+        r#"
+        [templates]
+        locale = "zh"
+        users = { alice = "en-US" }
+
+        [templates.messages]
+        bye = "Bye {{user}}"
+
+        [templates.messages.welcome]
+        en = "Welcome {{user}}"
+        zh = "欢迎 {{user}}"
+        "#,
+    )
+    .unwrap();
+    let templates = settings.templates;
+    let vars = json!({ "user": "x" });
+    assert_eq!(
+        templates.render("welcome", "alice", &vars).unwrap(),
+        "Welcome x"
+    );
+    assert_eq!(templates.render("welcome", "bob", &vars).unwrap(), "欢迎 x");
+    assert_eq!(
+        templates
+            .render_locale("welcome", Some("fr"), &vars)
+            .unwrap(),
+        "欢迎 x"
+    );
+    assert_eq!(templates.render("bye", "alice", &vars).unwrap(), "Bye x");
+    assert_eq!(
+        templates.render("missing", "alice", &vars),
+        Err(TemplateError::Unknown("missing".to_string()))
+    );
+
+    let error = toml::from_str::<Settings>("[templates.messages]\nbad = \"{{#each x}}\"")
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("template `bad`, line 1"));
+}
+
+#[test]
+fn vars_from_events() {
+    let ctx = common::context();
+    let event = Event::from_payload(&common::issue_opened("bob", "body"));
+    let vars = event_vars(&ctx, &event);
+    assert_eq!(vars["user"], "alice");
+    assert_eq!(vars["author"], "bob");
+    assert_eq!(vars["title"], "It does not work");
+    assert_eq!(
+        vars["run_url"],
+        "https://github.com/baipiao-bot/baipiao-bot-rust/actions/runs/1"
+    );
+}
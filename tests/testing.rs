@@ -0,0 +1,141 @@
+use async_trait::async_trait;
+use baipiao_bot_rust::client::ReviewEvent;
+use baipiao_bot_rust::testing::{self, events, MockGitHub};
+use baipiao_bot_rust::{
+    AuthorAssociation, Bot, CommentCreatedEvent, CommentTarget, Context, Event, IssueCreatedEvent,
+    PullRequestCreatedEvent,
+};
+
+struct Triage;
+
+#[async_trait]
+impl Bot for Triage {
+    async fn on_issue_created(&self, ctx: &Context, event: IssueCreatedEvent) {
+        let body = format!("Thanks @{}!", event.user);
+        ctx.github
+            .create_comment(&ctx.repo, event.id, &body)
+            .await
+            .unwrap();
+        if event.title.contains("crash") {
+            ctx.github
+                .add_labels(&ctx.repo, event.id, &["bug".to_string()])
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn on_pull_request_created(&self, ctx: &Context, event: PullRequestCreatedEvent) {
+        if event.to_ref == "main" {
+            ctx.github
+                .create_review(&ctx.repo, event.id, ReviewEvent::Approve, "LGTM")
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn on_comment_created(&self, ctx: &Context, event: CommentCreatedEvent) {
+        if event.body == "/close" {
+            if let Err(err) = ctx.github.close_issue(&ctx.repo, event.target.id()).await {
+                let body = format!("Cannot close: {}", err);
+                let _ = ctx
+                    .github
+                    .create_comment(&ctx.repo, event.target.id(), &body)
+                    .await;
+            }
+        }
+    }
+}
+
+#[test]
+fn builders_parse() {
+    let event = events::pull_request_opened()
+        .repo("alice/app")
+        .number(7)
+        .title("Fix it")
+        .by("alice")
+        .association(AuthorAssociation::FirstTimeContributor)
+        .label("bug")
+        .base("dev")
+        .build();
+    assert_eq!(event["event"]["pull_request"]["base"]["ref"], "dev");
+    match Event::from_payload(&event) {
+        Event::PullRequestCreated(event) => {
+            assert_eq!(event.id, 7);
+            assert_eq!(event.user, "alice");
+            assert_eq!(
+                event.author_association,
+                AuthorAssociation::FirstTimeContributor
+            );
+            assert_eq!(event.labels, vec!["bug"]);
+            assert_eq!(event.to_ref, "dev");
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+
+    match events::comment_created()
+        .on_pull_request()
+        .number(3)
+        .body("/close")
+        .event()
+    {
+        Event::CommentCreated(event) => {
+            assert!(matches!(event.target, CommentTarget::PullRequest(3)));
+            assert_eq!(event.body, "/close");
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+}
+
+#[tokio::test]
+async fn assertions() {
+    let github = MockGitHub::new();
+    let dispatcher = testing::dispatcher(Triage, &github);
+    dispatcher
+        .dispatch_event(
+            events::issue_opened()
+                .number(2)
+                .title("crash on start")
+                .by("alice")
+                .build(),
+        )
+        .await;
+    github.assert_commented(2, "Thanks @alice");
+    github.assert_labelled(2, "bug");
+    github.assert_not_commented(1);
+
+    dispatcher
+        .dispatch_event(events::pull_request_opened().number(3).build())
+        .await;
+    github.assert_reviewed(3, ReviewEvent::Approve);
+
+    dispatcher
+        .dispatch_event(events::comment_created().number(2).body("/close").build())
+        .await;
+    github.assert_closed(2);
+    assert_eq!(
+        github.calls(),
+        vec![
+            "create_comment",
+            "add_labels",
+            "create_review",
+            "close_issue"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn canned_failures() {
+    let github = MockGitHub::new();
+    github.fail("close_issue", 403);
+    testing::dispatcher(Triage, &github)
+        .dispatch_event(events::comment_created().body("/close").build())
+        .await;
+    assert!(!github.is_closed(1));
+    github.assert_commented(1, "Cannot close");
+
+    let github = MockGitHub::new();
+    testing::dispatcher(Triage, &github)
+        .dispatch_event(events::comment_created().body("hello").build())
+        .await;
+    github.assert_no_changes();
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use async_trait::async_trait;
+use baipiao_bot_rust::testing::MockGitHub;
+use baipiao_bot_rust::workflow::{self, Annotation, WorkflowCommand};
+use baipiao_bot_rust::{Bot, Context, Dispatcher, IssueCreatedEvent};
+use std::sync::Arc;
+use std::{env, fs};
+
+#[test]
+fn commands_are_escaped() {
+    assert_eq!(
+        WorkflowCommand::new("notice", "50% done\nnext").to_string(),
+        "::notice::50%25 done%0Anext"
+    );
+    let command = Annotation::new()
+        .title("Bad: config")
+        .file("a,b.toml")
+        .lines(3, 4)
+        .command("error", "unknown key");
+    assert_eq!(
+        command.to_string(),
+        "::error title=Bad%3A config,file=a%2Cb.toml,line=3,endLine=4::unknown key"
+    );
+}
+
+#[test]
+fn outputs_use_a_delimiter() {
+    let path = env::temp_dir().join(format!("baipiao-output-{}", std::process::id()));
+    env::set_var("GITHUB_OUTPUT", &path);
+    workflow::set_output("labels", "bug\nui").unwrap();
+    let written = fs::read_to_string(&path).unwrap();
+    env::remove_var("GITHUB_OUTPUT");
+    fs::remove_file(&path).unwrap();
+
+    let lines: Vec<&str> = written.lines().collect();
+    let delimiter = lines[0].strip_prefix("labels<<").unwrap();
+    assert_eq!(&lines[1..], &["bug", "ui", delimiter]);
+}
+
+struct Welcome;
+
+#[async_trait]
+impl Bot for Welcome {
+    async fn on_issue_created(&self, ctx: &Context, event: IssueCreatedEvent) {
+        ctx.github
+            .create_comment(&ctx.repo, event.id, "welcome")
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn dispatcher_writes_a_step_summary() {
+    let path = env::temp_dir().join(format!("baipiao-summary-{}", std::process::id()));
+    env::set_var("GITHUB_STEP_SUMMARY", &path);
+    Dispatcher::new(Welcome)
+        .with_api(Arc::new(MockGitHub::default()))
+        .dispatch_event(common::issue_opened("alice", ""))
+        .await;
+    let summary = fs::read_to_string(&path).unwrap();
+    env::remove_var("GITHUB_STEP_SUMMARY");
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        summary,
+        "### baipiao-bot: `issues.opened` on baipiao-bot/baipiao-bot-rust#1\n\n\
+         **Outcome:** handled\n\n\
+         - commented on #1\n"
+    );
+}